@@ -1,4 +1,4 @@
-use rusttpx::{Client, Request, Response, middleware::Middleware, Url};
+use rusttpx::{Client, Request, Response, middleware::{Middleware, RequestAction}, Url};
 use std::error::Error;
 use std::time::Duration;
 use futures_util::StreamExt;
@@ -9,12 +9,12 @@ struct LoggingMiddleware;
 
 #[async_trait::async_trait]
 impl Middleware for LoggingMiddleware {
-    async fn process_request(&self, request: HttpRequest<()>) -> Result<HttpRequest<()>, rusttpx::Error> {
+    async fn process_request(&self, request: HttpRequest<Vec<u8>>) -> Result<RequestAction, rusttpx::Error> {
         println!("🔄 Making request: {} {}", request.method(), request.uri());
-        Ok(request)
+        Ok(RequestAction::Continue(request))
     }
 
-    async fn process_response(&self, response: http::Response<()>) -> Result<http::Response<()>, rusttpx::Error> {
+    async fn process_response(&self, response: http::Response<Vec<u8>>) -> Result<http::Response<Vec<u8>>, rusttpx::Error> {
         println!("✅ Received response: {}", response.status());
         Ok(response)
     }
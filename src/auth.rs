@@ -54,6 +54,13 @@ pub enum AuthType {
         scheme: String,
         credentials: String,
     },
+    /// NTLM authentication, negotiated by the client on a 401
+    #[cfg(feature = "ntlm")]
+    Ntlm {
+        username: String,
+        password: String,
+        domain: String,
+    },
 }
 
 /// API key location
@@ -161,6 +168,20 @@ impl AuthConfig {
         }
     }
 
+    /// Create an NTLM authentication configuration
+    #[cfg(feature = "ntlm")]
+    pub fn ntlm(username: &str, password: &str, domain: &str) -> Self {
+        Self {
+            auth_type: AuthType::Ntlm {
+                username: username.to_string(),
+                password: password.to_string(),
+                domain: domain.to_string(),
+            },
+            headers: HeaderMap::new(),
+            custom_data: HashMap::new(),
+        }
+    }
+
     /// Set the authentication type
     pub fn auth_type(mut self, auth_type: AuthType) -> Self {
         self.auth_type = auth_type;
@@ -230,6 +251,11 @@ impl AuthConfig {
             AuthType::Custom { scheme, credentials } => {
                 Some(format!("{} {}", scheme, credentials))
             }
+            #[cfg(feature = "ntlm")]
+            AuthType::Ntlm { .. } => {
+                // NTLM is a handshake driven by the client, not a static header
+                None
+            }
         }
     }
 
@@ -415,21 +441,26 @@ impl AuthConfig {
 
     /// Create authentication from environment variables
     pub fn from_env() -> Self {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// Create authentication from variables read through `get`
+    pub(crate) fn from_env_with(get: impl Fn(&str) -> Option<String>) -> Self {
         let mut config = Self::new();
 
         // Check for basic auth
-        if let (Ok(username), Ok(password)) = (std::env::var("HTTP_USERNAME"), std::env::var("HTTP_PASSWORD")) {
+        if let (Some(username), Some(password)) = (get("HTTP_USERNAME"), get("HTTP_PASSWORD")) {
             config = config.auth_type(AuthType::Basic { username, password });
         }
 
         // Check for bearer token
-        if let Ok(token) = std::env::var("HTTP_BEARER_TOKEN") {
+        if let Some(token) = get("HTTP_BEARER_TOKEN") {
             config = config.auth_type(AuthType::Bearer { token });
         }
 
         // Check for API key
-        if let (Ok(key), Ok(value)) = (std::env::var("HTTP_API_KEY"), std::env::var("HTTP_API_VALUE")) {
-            let location = std::env::var("HTTP_API_LOCATION")
+        if let (Some(key), Some(value)) = (get("HTTP_API_KEY"), get("HTTP_API_VALUE")) {
+            let location = get("HTTP_API_LOCATION")
                 .map(|loc| match loc.as_str() {
                     "query" => ApiKeyLocation::Query,
                     "body" => ApiKeyLocation::Body,
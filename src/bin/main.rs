@@ -1,75 +1,16 @@
 use clap::{Parser, ValueEnum};
 use http::Method;
 use rusttpx::Client;
-use std::time::Duration;
+use rusttpx::format::{colorize_json, ColorOptions};
+use rusttpx::request::RequestBody;
+use rusttpx::proxy::ProxyConfig;
+use rusttpx::retry::RetryConfig;
+use rusttpx::tls::{self, TlsConfig};
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
 use url::Url;
 use colored::*;
 
-fn print_basic_colorized_json(json: &str) {
-    // Parse the JSON to get proper data type information
-    match serde_json::from_str::<serde_json::Value>(json) {
-        Ok(value) => {
-            print_json_value(&value, 0);
-        }
-        Err(_) => {
-            // Fallback to simple line-by-line if parsing fails
-            for line in json.lines() {
-                println!("{}", line);
-            }
-        }
-    }
-}
-
-fn print_json_value(value: &serde_json::Value, indent: usize) {
-    let indent_str = "  ".repeat(indent);
-    match value {
-        serde_json::Value::Object(map) => {
-            println!("{}{}", indent_str, "{".white());
-            let mut entries: Vec<_> = map.iter().collect();
-            entries.sort_by(|a, b| a.0.cmp(b.0));
-            for (i, (key, val)) in entries.iter().enumerate() {
-                let is_last = i == entries.len() - 1;
-                print!("{}  {}\"{}\"{}: ",
-                    indent_str,
-                    "".white(),
-                    key.yellow(),
-                    "".white()
-                );
-                print_json_value(val, indent + 1);
-                if !is_last {
-                    print!("{}", ",".white());
-                }
-                println!();
-            }
-            print!("{}{}", indent_str, "}".white());
-        }
-        serde_json::Value::Array(arr) => {
-            println!("{}{}", indent_str, "[".white());
-            for (i, item) in arr.iter().enumerate() {
-                let is_last = i == arr.len() - 1;
-                print_json_value(item, indent + 1);
-                if !is_last {
-                    print!("{}", ",".white());
-                }
-                println!();
-            }
-            print!("{}{}", indent_str, "]".white());
-        }
-        serde_json::Value::String(s) => {
-            print!("\"{}\"", s.green());
-        }
-        serde_json::Value::Number(n) => {
-            print!("{}", n.to_string().bright_blue());
-        }
-        serde_json::Value::Bool(b) => {
-            print!("{}", b.to_string().bright_magenta());
-        }
-        serde_json::Value::Null => {
-            print!("{}", "null".bright_red());
-        }
-    }
-}
-
 #[derive(Parser)]
 #[command(name = "rusttpx")]
 #[command(about = "A next-generation HTTP client for Rust")]
@@ -89,9 +30,17 @@ struct Cli {
     headers: Vec<String>,
     
     /// Request body
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with_all = ["form", "files"])]
     body: Option<String>,
     
+    /// Form field sent URL-encoded, or as multipart with --file (format: name=value)
+    #[arg(short = 'F', long)]
+    form: Vec<String>,
+    
+    /// File to upload as multipart form data (format: field=@path)
+    #[arg(long = "file")]
+    files: Vec<String>,
+    
     /// Content type for the request body
     #[arg(long, default_value = "application/json")]
     content_type: String,
@@ -108,6 +57,50 @@ struct Cli {
     #[arg(long)]
     no_follow_redirects: bool,
     
+    /// Retry failed requests up to this many times
+    #[arg(long, value_name = "N")]
+    retry: Option<u32>,
+    
+    /// Delay between retries in seconds
+    #[arg(long, value_name = "SECS", default_value = "1")]
+    retry_delay: f64,
+    
+    /// Status codes to retry on [default: 429,500,502,503,504]
+    #[arg(long, value_name = "CODES", value_delimiter = ',')]
+    retry_on: Vec<u16>,
+    
+    /// Maximum requests per second
+    #[arg(long, value_name = "RPS")]
+    rate_limit: Option<f64>,
+    
+    /// Proxy to send requests through [default: HTTP_PROXY/HTTPS_PROXY]
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+    
+    /// Proxy credentials (format: user:pass)
+    #[arg(long, value_name = "USER:PASS")]
+    proxy_user: Option<String>,
+    
+    /// Ignore proxy environment variables and connect directly
+    #[arg(long, conflicts_with_all = ["proxy", "proxy_user"])]
+    no_proxy: bool,
+    
+    /// Skip verification of the server's TLS certificate
+    #[arg(short = 'k', long)]
+    insecure: bool,
+    
+    /// CA certificate (PEM) to trust in addition to the built-in roots
+    #[arg(long, value_name = "PATH")]
+    cacert: Option<String>,
+    
+    /// Client certificate (PEM) for mutual TLS
+    #[arg(long, value_name = "PATH", requires = "key")]
+    cert: Option<String>,
+    
+    /// Private key (PEM) for the client certificate
+    #[arg(long, value_name = "PATH", requires = "cert")]
+    key: Option<String>,
+    
     /// Show response headers
     #[arg(long)]
     show_headers: bool,
@@ -116,8 +109,16 @@ struct Cli {
     #[arg(long, default_value = "true")]
     show_body: bool,
     
+    /// When to colorize output
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorChoice,
+    
+    /// Print the request, response headers and timing to stderr (repeat for more detail)
+    #[arg(short = 'v', long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    
     /// Show version information
-    #[arg(short, long)]
+    #[arg(short = 'V', long)]
     version: bool,
     
     /// Test the client with various endpoints
@@ -150,6 +151,29 @@ impl From<MethodArg> for Method {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy)]
+enum ColorChoice {
+    /// Colorize when stdout is a terminal and `NO_COLOR` is unset
+    Auto,
+    /// Always colorize
+    Always,
+    /// Never colorize
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").map(|v| v.is_empty()).unwrap_or(true)
+            }
+        }
+    }
+}
+
 #[derive(clap::Subcommand)]
 enum Commands {
     /// Test the client with various endpoints
@@ -164,6 +188,11 @@ enum Commands {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
+    // Decide once so the status line and the JSON printer agree
+    let color = cli.color.enabled();
+    colored::control::set_override(color);
+    let color_options = ColorOptions::new().color(color).respect_no_color(false);
+
     if cli.version {
         println!("rusttpx {}", env!("CARGO_PKG_VERSION"));
         println!("A next-generation HTTP client for Rust");
@@ -274,6 +303,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         client_builder = client_builder.redirect(10); // Follow up to 10 redirects
     }
 
+    let mut proxy_config = match &cli.proxy {
+        Some(proxy) => ProxyConfig::new().proxy(proxy.parse::<Url>()?),
+        None if cli.no_proxy => ProxyConfig::new(),
+        None => ProxyConfig::from_env(),
+    };
+    if let Some(credentials) = &cli.proxy_user {
+        let (username, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+        proxy_config = proxy_config.auth_credentials(username, password);
+    }
+    client_builder = client_builder.proxy_config(proxy_config);
+
+    if cli.insecure || cli.cacert.is_some() || cli.cert.is_some() {
+        let mut tls_config = match &cli.cacert {
+            Some(path) => TlsConfig::with_ca_cert(path)?,
+            None => TlsConfig::new(),
+        };
+        if let (Some(cert), Some(key)) = (&cli.cert, &cli.key) {
+            let identity = tls::utils::load_identity(&cert.into(), &key.into())?;
+            tls_config = tls_config.client_cert(identity);
+        }
+        client_builder = client_builder.tls_config(tls_config.verify(!cli.insecure));
+    }
+
+    if let Some(retries) = cli.retry {
+        let mut retry = RetryConfig::new(retries).delay(Duration::from_secs_f64(cli.retry_delay));
+        if !cli.retry_on.is_empty() {
+            let statuses = cli
+                .retry_on
+                .iter()
+                .map(|&code| http::StatusCode::from_u16(code).map_err(|_| format!("Invalid status code: {}", code)))
+                .collect::<Result<Vec<_>, _>>()?;
+            retry = retry.retry_on(statuses);
+        }
+        client_builder = client_builder.retry(retry);
+    }
+    if let Some(rps) = cli.rate_limit {
+        if !(rps > 0.0 && rps.is_finite()) {
+            return Err("--rate-limit must be a positive number".into());
+        }
+        client_builder = client_builder.rate_limit(rps);
+    }
+
     let client = client_builder.build();
 
     let url = url.parse::<Url>()?;
@@ -293,10 +364,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         request_builder = request_builder
             .header("Content-Type", &cli.content_type)?
             .text(&body_content)?;
+    } else if !cli.files.is_empty() {
+        let mut form = reqwest::multipart::Form::new();
+        for (name, value) in parse_fields(&cli.form)? {
+            form = form.text(name, value);
+        }
+        for (name, value) in parse_fields(&cli.files)? {
+            let path = value
+                .strip_prefix('@')
+                .ok_or_else(|| format!("Expected field=@path, got '{}={}'", name, value))?;
+            let contents = std::fs::read(path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let file_name = std::path::Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            form = form.part(name, reqwest::multipart::Part::bytes(contents).file_name(file_name));
+        }
+        request_builder = request_builder.multipart(form)?;
+    } else if !cli.form.is_empty() {
+        request_builder = request_builder.form(&parse_fields(&cli.form)?)?;
     }
 
+    let request = request_builder.build()?;
+    let dns = if cli.verbose > 0 {
+        print_request(&request, cli.verbose);
+        resolve_time(request.url()).await
+    } else {
+        None
+    };
+
     // Make the request
-    let response = request_builder.send().await?;
+    let started = Instant::now();
+    let response = client.send(request).await?;
+    let ttfb = started.elapsed();
+
+    if cli.verbose > 0 {
+        eprintln!("< {:?} {}", response.version(), response.status());
+        for (name, value) in response.headers() {
+            eprintln!("< {}: {}", name, value.to_str().unwrap_or(""));
+        }
+        eprintln!("<");
+    }
 
     // Display results
     if cli.show_headers {
@@ -318,15 +427,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     if cli.show_body {
-        let content_type = response.headers().get("content-type").and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+        let is_json = response.is_json();
         let body = response.text().await?;
 
-        if content_type.contains("application/json") || content_type.contains("+json") {
+        if is_json {
             // Pretty-print and colorize JSON body
             match serde_json::from_str::<serde_json::Value>(&body) {
                 Ok(json_value) => {
-                    let pretty = serde_json::to_string_pretty(&json_value).unwrap_or(body.clone());
-                    print_basic_colorized_json(&pretty);
+                    let mut stdout = std::io::stdout();
+                    colorize_json(&json_value, &mut stdout, &color_options)?;
+                    println!();
                 }
                 Err(_) => {
                     // If not valid JSON, just print as plain text
@@ -339,5 +449,73 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if cli.verbose > 0 {
+        let dns = dns.map(format_duration).unwrap_or_else(|| "n/a".to_string());
+        eprintln!(
+            "* Timing: dns={} ttfb={} total={}",
+            dns,
+            format_duration(ttfb),
+            format_duration(started.elapsed())
+        );
+    }
+
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Split repeated `name=value` arguments into pairs
+fn parse_fields(fields: &[String]) -> Result<Vec<(String, String)>, String> {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .split_once('=')
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .ok_or_else(|| format!("Expected name=value, got '{}'", field))
+        })
+        .collect()
+}
+
+/// Print the outgoing request line and headers, plus the body at level 2
+fn print_request(request: &rusttpx::Request, verbose: u8) {
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    eprintln!("> {} {}", request.method(), target);
+    if !request.headers().contains_key("host") {
+        match url.port() {
+            Some(port) => eprintln!("> host: {}:{}", url.host_str().unwrap_or(""), port),
+            None => eprintln!("> host: {}", url.host_str().unwrap_or("")),
+        }
+    }
+    for (name, value) in request.headers() {
+        eprintln!("> {}: {}", name, value.to_str().unwrap_or(""));
+    }
+    eprintln!(">");
+
+    if verbose > 1 {
+        match request.body() {
+            Some(RequestBody::Text(text)) => eprintln!("{}", text),
+            Some(RequestBody::Bytes(bytes)) => eprintln!("{}", String::from_utf8_lossy(bytes)),
+            Some(RequestBody::Stream(_)) => eprintln!("[streamed body]"),
+            _ => {}
+        }
+    }
+}
+
+/// Time a DNS lookup of the request host
+///
+/// Note: reqwest doesn't report its own connection phases, so this times a
+/// separate lookup through the system resolver, which reqwest also uses.
+async fn resolve_time(url: &Url) -> Option<Duration> {
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+    let started = Instant::now();
+    let mut addrs = tokio::net::lookup_host((host, port)).await.ok()?;
+    addrs.next().map(|_| started.elapsed())
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
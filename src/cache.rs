@@ -0,0 +1,353 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use http::header::{self, HeaderName};
+use http::{HeaderMap, StatusCode, Version};
+use reqwest::Request as ReqwestRequest;
+use url::Url;
+
+use crate::cookies::CookieJar;
+use crate::error::Result;
+use crate::response::Response;
+
+/// Configuration for the client's response cache
+///
+/// Responses are keyed by method, URL, the configured key headers and the
+/// request body, so POST-based queries with different bodies don't collide.
+/// Only successful responses to requests with in-memory bodies are cached.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Maximum number of cached responses
+    pub max_entries: usize,
+    /// Maximum combined size of cached bodies in bytes
+    pub max_total_size: usize,
+    /// How long a cached response stays fresh
+    pub ttl: Duration,
+    /// Request headers that take part in the cache key
+    pub key_headers: Vec<HeaderName>,
+}
+
+impl CacheConfig {
+    /// Create a new cache configuration
+    pub fn new(max_entries: usize, max_total_size: usize) -> Self {
+        Self {
+            max_entries,
+            max_total_size,
+            ..Self::default()
+        }
+    }
+
+    /// Set the maximum number of cached responses
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Set the maximum combined size of cached bodies
+    pub fn max_total_size(mut self, max_total_size: usize) -> Self {
+        self.max_total_size = max_total_size;
+        self
+    }
+
+    /// Set how long a cached response stays fresh
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Add a request header to the cache key
+    pub fn key_header(mut self, name: HeaderName) -> Self {
+        self.key_headers.push(name);
+        self
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 256,
+            max_total_size: 16 * 1024 * 1024,
+            ttl: Duration::from_secs(300),
+            key_headers: vec![header::ACCEPT, header::AUTHORIZATION, header::CONTENT_TYPE],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    url: Url,
+    body: Vec<u8>,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<u64, CachedResponse>,
+    total_size: usize,
+    clock: u64,
+}
+
+/// LRU response cache shared by a client and its requests
+#[derive(Debug)]
+pub struct ResponseCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl ResponseCache {
+    /// Create a new empty cache
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Get the number of cached responses
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|s| s.entries.len()).unwrap_or(0)
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the combined size of cached bodies in bytes
+    pub fn total_size(&self) -> usize {
+        self.state.lock().map(|s| s.total_size).unwrap_or(0)
+    }
+
+    /// Remove every cached response
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.entries.clear();
+            state.total_size = 0;
+        }
+    }
+
+    /// Compute the cache key for a request, or `None` if its body is a stream
+    pub(crate) fn key_for(&self, request: &ReqwestRequest) -> Option<u64> {
+        let body = match request.body() {
+            Some(body) => body.as_bytes()?,
+            None => &[],
+        };
+
+        let mut hasher = DefaultHasher::new();
+        request.method().hash(&mut hasher);
+        request.url().as_str().hash(&mut hasher);
+        for name in &self.config.key_headers {
+            name.as_str().hash(&mut hasher);
+            for value in request.headers().get_all(name) {
+                value.as_bytes().hash(&mut hasher);
+            }
+        }
+        body.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Look up a fresh cached response, marking it most recently used
+    pub(crate) fn get(&self, key: u64, cookie_jar: Arc<CookieJar>) -> Option<Response> {
+        let mut state = self.state.lock().ok()?;
+        state.clock += 1;
+        let clock = state.clock;
+
+        let expired = state.entries.get(&key)?.stored_at.elapsed() >= self.config.ttl;
+        if expired {
+            if let Some(entry) = state.entries.remove(&key) {
+                state.total_size -= entry.body.len();
+            }
+            return None;
+        }
+
+        let entry = state.entries.get_mut(&key)?;
+        entry.last_used = clock;
+        let entry = entry.clone();
+        drop(state);
+
+        Response::from_parts(
+            entry.status,
+            entry.version,
+            entry.headers,
+            entry.url,
+            entry.body,
+            cookie_jar,
+        )
+        .ok()
+    }
+
+    /// Buffer and cache a successful response, returning an equivalent one
+    pub(crate) async fn store(
+        &self,
+        key: u64,
+        response: Response,
+        cookie_jar: Arc<CookieJar>,
+    ) -> Result<Response> {
+        if !response.is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let url = response.url().clone();
+        let body = response.bytes().await?;
+
+        self.insert(key, CachedResponse {
+            status,
+            version,
+            headers: headers.clone(),
+            url: url.clone(),
+            body: body.clone(),
+            stored_at: Instant::now(),
+            last_used: 0,
+        });
+
+        Response::from_parts(status, version, headers, url, body, cookie_jar)
+    }
+
+    fn insert(&self, key: u64, mut entry: CachedResponse) {
+        let size = entry.body.len();
+        if size > self.config.max_total_size || self.config.max_entries == 0 {
+            return;
+        }
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        state.clock += 1;
+        entry.last_used = state.clock;
+
+        if let Some(old) = state.entries.remove(&key) {
+            state.total_size -= old.body.len();
+        }
+        while state.entries.len() >= self.config.max_entries
+            || state.total_size + size > self.config.max_total_size
+        {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| *k);
+            match oldest.and_then(|k| state.entries.remove(&k)) {
+                Some(evicted) => state.total_size -= evicted.body.len(),
+                None => break,
+            }
+        }
+
+        state.total_size += size;
+        state.entries.insert(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_string, method};
+
+    #[tokio::test]
+    async fn test_post_bodies_do_not_collide() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string("{\"query\":\"a\"}"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("A"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string("{\"query\":\"b\"}"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("B"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::builder()
+            .response_cache(CacheConfig::default())
+            .build();
+        let url: Url = format!("{}/graphql", server.uri()).parse().unwrap();
+        let query = |q: &'static str| {
+            client.post(url.clone()).text(q).unwrap().send_text()
+        };
+
+        assert_eq!(query("{\"query\":\"a\"}").await.unwrap(), "A");
+        assert_eq!(query("{\"query\":\"b\"}").await.unwrap(), "B");
+        // Served from the cache; the mocks only allow one hit each
+        assert_eq!(query("{\"query\":\"a\"}").await.unwrap(), "A");
+        assert_eq!(query("{\"query\":\"b\"}").await.unwrap(), "B");
+        assert_eq!(client.response_cache().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_reach_response_middleware() {
+        use crate::middleware::{MetricsMiddleware, MiddlewareChain};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("cached"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let metrics = MetricsMiddleware::new();
+        let client = crate::Client::builder()
+            .response_cache(CacheConfig::default())
+            .middleware(MiddlewareChain::new().add(metrics.clone()))
+            .build();
+        let url: Url = server.uri().parse().unwrap();
+
+        for _ in 0..2 {
+            let response = client.get(url.clone()).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "cached");
+        }
+        // The hit is counted with its request, not just the fresh response
+        assert_eq!(metrics.request_count().await, 2);
+        assert_eq!(metrics.status_counts().get(&200), Some(&2));
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = ResponseCache::new(CacheConfig::new(2, 1024));
+        let entry = |body: &str| CachedResponse {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: HeaderMap::new(),
+            url: Url::parse("http://example.com/").unwrap(),
+            body: body.as_bytes().to_vec(),
+            stored_at: Instant::now(),
+            last_used: 0,
+        };
+        let jar = Arc::new(CookieJar::new());
+
+        cache.insert(1, entry("one"));
+        cache.insert(2, entry("two"));
+        // Touch 1 so that 2 becomes the least recently used
+        assert!(cache.get(1, jar.clone()).is_some());
+        cache.insert(3, entry("three"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(2, jar.clone()).is_none());
+        assert!(cache.get(1, jar.clone()).is_some());
+        assert!(cache.get(3, jar.clone()).is_some());
+        assert_eq!(cache.total_size(), "one".len() + "three".len());
+
+        // Size limits evict too
+        let cache = ResponseCache::new(CacheConfig::new(10, 8));
+        cache.insert(1, entry("aaaa"));
+        cache.insert(2, entry("bbbb"));
+        cache.insert(3, entry("cccc"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(1, jar).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use reqwest::Response as ReqwestResponse;
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Configuration for the per-host circuit breaker
+///
+/// After `failure_threshold` consecutive failures to a host the circuit
+/// opens and requests to that host fail fast for `cooldown`. The next
+/// request after the cooldown is let through as a probe: success closes
+/// the circuit, failure opens it again.
+#[derive(Debug, Clone)]
+pub struct CircuitConfig {
+    /// Consecutive failures before the circuit opens
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before probing
+    pub cooldown: Duration,
+}
+
+impl CircuitConfig {
+    /// Create a new circuit breaker configuration
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+        }
+    }
+
+    /// Set the number of consecutive failures before the circuit opens
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// Set the cooldown before an open circuit is probed
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of a single host's circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests fail fast until the cooldown elapses
+    Open,
+    /// A probe request is in flight
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct HostCircuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl HostCircuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+        }
+    }
+}
+
+/// Circuit breaker tracking failures per host
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitConfig,
+    hosts: Mutex<HashMap<String, HostCircuit>>,
+}
+
+impl CircuitBreaker {
+    /// Create a new circuit breaker
+    pub fn new(config: CircuitConfig) -> Self {
+        Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &CircuitConfig {
+        &self.config
+    }
+
+    /// Get the current state of the circuit for a host
+    pub fn state(&self, host: &str) -> CircuitState {
+        if let Ok(hosts) = self.hosts.lock() {
+            hosts.get(host).map(|c| c.state).unwrap_or(CircuitState::Closed)
+        } else {
+            CircuitState::Closed
+        }
+    }
+
+    /// Check whether a request to a host may proceed
+    ///
+    /// Moves an open circuit to half-open once the cooldown has elapsed,
+    /// letting exactly one probe through.
+    pub fn check(&self, host: &str) -> Result<()> {
+        self.admit(host).map(|_| ())
+    }
+
+    /// Like `check`, returning whether the request is the half-open probe
+    fn admit(&self, host: &str) -> Result<bool> {
+        let mut hosts = match self.hosts.lock() {
+            Ok(hosts) => hosts,
+            Err(_) => return Ok(false),
+        };
+        let circuit = hosts.entry(host.to_string()).or_insert_with(HostCircuit::new);
+
+        match circuit.state {
+            CircuitState::Closed => Ok(false),
+            CircuitState::Open => {
+                let cooled_down = circuit
+                    .opened_at
+                    .map(|at| at.elapsed() >= self.config.cooldown)
+                    .unwrap_or(true);
+                if cooled_down {
+                    circuit.state = CircuitState::HalfOpen;
+                    Ok(true)
+                } else {
+                    Err(Error::custom(format!("circuit open for {}", host)))
+                }
+            }
+            CircuitState::HalfOpen => Err(Error::custom(format!("circuit open for {}", host))),
+        }
+    }
+
+    /// Record a successful request, closing the circuit
+    pub fn record_success(&self, host: &str) {
+        if let Ok(mut hosts) = self.hosts.lock() {
+            let circuit = hosts.entry(host.to_string()).or_insert_with(HostCircuit::new);
+            circuit.state = CircuitState::Closed;
+            circuit.consecutive_failures = 0;
+            circuit.opened_at = None;
+        }
+    }
+
+    /// Record a failed request, opening the circuit if the threshold is reached
+    pub fn record_failure(&self, host: &str) {
+        if let Ok(mut hosts) = self.hosts.lock() {
+            let circuit = hosts.entry(host.to_string()).or_insert_with(HostCircuit::new);
+            circuit.consecutive_failures += 1;
+            if circuit.state == CircuitState::HalfOpen
+                || circuit.consecutive_failures >= self.config.failure_threshold
+            {
+                circuit.state = CircuitState::Open;
+                circuit.opened_at = Some(Instant::now());
+            }
+        }
+    }
+}
+
+/// Records a failure if a half-open probe is dropped before it finishes
+///
+/// Without it a cancelled probe would leave the circuit half-open, and
+/// `check` rejects every request to a half-open host.
+struct Probe<'a> {
+    breaker: &'a CircuitBreaker,
+    host: &'a str,
+    finished: bool,
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.breaker.record_failure(self.host);
+        }
+    }
+}
+
+/// Send a request through an optional circuit breaker
+///
+/// Network errors and 5xx responses count as failures, as does dropping
+/// the probe sent while the circuit is half-open.
+pub(crate) async fn guard<F>(
+    breaker: Option<&CircuitBreaker>,
+    url: &Url,
+    send: F,
+) -> Result<ReqwestResponse>
+where
+    F: Future<Output = reqwest::Result<ReqwestResponse>>,
+{
+    let breaker = match breaker {
+        Some(breaker) => breaker,
+        None => return send.await.map_err(Error::Network),
+    };
+
+    let host = url.host_str().unwrap_or_default();
+    let probe = breaker.admit(host)?.then(|| Probe { breaker, host, finished: false });
+
+    let result = send.await;
+    if let Some(mut probe) = probe {
+        probe.finished = true;
+    }
+    match result {
+        Ok(response) if response.status().is_server_error() => {
+            breaker.record_failure(host);
+            Ok(response)
+        }
+        Ok(response) => {
+            breaker.record_success(host);
+            Ok(response)
+        }
+        Err(e) => {
+            breaker.record_failure(host);
+            Err(Error::Network(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(CircuitConfig::new(2, Duration::from_secs(60)));
+
+        breaker.record_failure("example.com");
+        assert_eq!(breaker.state("example.com"), CircuitState::Closed);
+        assert!(breaker.check("example.com").is_ok());
+
+        breaker.record_failure("example.com");
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+        assert!(breaker.check("example.com").is_err());
+
+        // Other hosts are unaffected
+        assert!(breaker.check("other.com").is_ok());
+    }
+
+    #[test]
+    fn test_circuit_half_open_failure_reopens() {
+        let breaker = CircuitBreaker::new(CircuitConfig::new(1, Duration::from_millis(0)));
+
+        breaker.record_failure("example.com");
+        assert!(breaker.check("example.com").is_ok());
+        assert_eq!(breaker.state("example.com"), CircuitState::HalfOpen);
+
+        breaker.record_failure("example.com");
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_probe_reopens_circuit() {
+        let breaker = CircuitBreaker::new(CircuitConfig::new(1, Duration::from_millis(0)));
+        let url: Url = "http://example.com/".parse().unwrap();
+        let hang = || guard(Some(&breaker), &url, std::future::pending());
+
+        // A request cancelled while the circuit is closed isn't a failure
+        assert!(tokio::time::timeout(Duration::from_millis(10), hang()).await.is_err());
+        assert_eq!(breaker.state("example.com"), CircuitState::Closed);
+
+        breaker.record_failure("example.com");
+        assert!(tokio::time::timeout(Duration::from_millis(10), hang()).await.is_err());
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+
+        // The next request after the cooldown is admitted as a new probe
+        assert!(breaker.check("example.com").is_ok());
+        assert_eq!(breaker.state("example.com"), CircuitState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_client() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(3)
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::builder()
+            .circuit_breaker(CircuitConfig::new(3, Duration::from_millis(100)))
+            .build();
+        let url: Url = server.uri().parse().unwrap();
+
+        for _ in 0..3 {
+            let response = client.get(url.clone()).send().await.unwrap();
+            assert!(response.is_server_error());
+        }
+
+        // Open: fails fast without reaching the server
+        let err = client.get(url.clone()).send().await.unwrap_err();
+        assert!(err.to_string().contains("circuit open"));
+
+        // After the cooldown a successful probe closes the circuit
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert!(response.is_success());
+
+        let breaker = client.circuit_breaker().unwrap();
+        assert_eq!(breaker.state(url.host_str().unwrap()), CircuitState::Closed);
+    }
+}
@@ -1,17 +1,36 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use std::sync::Arc;
 use reqwest::{Client as ReqwestClient, ClientBuilder as ReqwestBuilder};
-use http::{Method, HeaderMap, HeaderValue};
+use http::{Method, HeaderMap, HeaderValue, StatusCode, Version};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::{Mutex, Semaphore};
 use url::Url;
 
 use crate::error::{Error, Result};
-use crate::request::{Request, RequestBuilder};
-use crate::response::Response;
+use crate::request::{ascii_host, normalize_url, parse_raw_request, Request, RequestBuilder, RequestSnapshot, LastRequest, SendContext};
+use crate::response::{HeaderLimits, RateLimitInfo, Response};
 use crate::cookies::CookieJar;
+use crate::dns::{IpFamily, PreferFamily};
 use crate::timeout::TimeoutConfig;
 use crate::proxy::ProxyConfig;
-use crate::tls::TlsConfig;
+use crate::tls::{Alpn, ClientIdentity, TlsConfig};
 use crate::auth::AuthConfig;
+#[cfg(feature = "ntlm")]
+use crate::auth::AuthType;
+#[cfg(feature = "ntlm")]
+use crate::ntlm::NtlmCredentials;
+use crate::circuit::{CircuitBreaker, CircuitConfig};
+use crate::cache::{CacheConfig, ResponseCache};
+use crate::compression::{self, Encoding};
+use crate::pipeline::BodyPipeline;
+use crate::middleware::{self, MiddlewareChain};
+use crate::middleware::rate_limit::RateLimiter;
+use crate::retry::RetryConfig;
+use crate::redirect::{self, RefererPolicy, Redirects};
+
+/// Timeout applied to health-check requests made by [`Client::ping`]
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Main HTTP client for RustTPX
 ///
@@ -35,10 +54,32 @@ use crate::auth::AuthConfig;
 #[derive(Clone)]
 pub struct Client {
     inner: Arc<ReqwestClient>,
+    connectors: Arc<Connectors>,
     cookie_jar: Arc<CookieJar>,
     timeout_config: TimeoutConfig,
+    method_timeouts: HashMap<Method, Duration>,
     default_headers: HeaderMap,
     base_url: Option<Url>,
+    concurrency_limit: Option<Arc<Semaphore>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    http_version: Option<Version>,
+    normalize_urls: bool,
+    trace_propagation: bool,
+    response_cache: Option<Arc<ResponseCache>>,
+    accept_encodings: Option<Arc<[Encoding]>>,
+    retry: Option<Arc<RetryConfig>>,
+    rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    middleware: Option<Arc<MiddlewareChain>>,
+    lifecycle_log: Option<log::Level>,
+    redirects: Option<Redirects>,
+    header_limits: HeaderLimits,
+    stream_idle_timeout: Option<Duration>,
+    detect_charset: bool,
+    body_pipeline: Option<Arc<BodyPipeline>>,
+    error_on_status: bool,
+    last_request: Option<LastRequest>,
+    #[cfg(feature = "ntlm")]
+    ntlm: Option<Arc<NtlmCredentials>>,
 }
 
 impl Client {
@@ -52,6 +93,13 @@ impl Client {
         ClientBuilder::new()
     }
 
+    /// Create a client configured from environment variables
+    ///
+    /// See [`ClientBuilder::from_env`] for the variables read.
+    pub fn from_env() -> Result<Self> {
+        Ok(ClientBuilder::from_env()?.build())
+    }
+
     /// Create a GET request
     pub fn get<U>(&self, url: U) -> RequestBuilder
     where
@@ -112,27 +160,143 @@ impl Client {
             url = base_url.join(url.as_str()).unwrap_or(url);
         }
 
-        RequestBuilder::new(
-            self.inner.clone(),
+        if self.normalize_urls {
+            url = normalize_url(&url);
+        }
+
+        let method_timeout = self.method_timeouts.get(&method).copied();
+        let context = self.send_context();
+        let builder = RequestBuilder::new(
+            context.client.clone(),
             self.cookie_jar.clone(),
             method,
             url,
             self.timeout_config.clone(),
             self.default_headers.clone(),
         )
+        .context(context);
+        // Set first, so the request's own `timeout` replaces it
+        let builder = match method_timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        };
+
+        match self.http_version {
+            Some(version) => builder.version(version),
+            None => builder,
+        }
     }
 
     /// Send a request and return the response
     pub async fn send(&self, request: Request) -> Result<Response> {
-        let reqwest_response = self.inner
-            .execute(request.into_reqwest_request()?)
-            .await
-            .map_err(Error::Network)?;
+        self.send_with(request, true).await
+    }
 
-        Response::from_reqwest_response(reqwest_response, self.cookie_jar.clone()).await
+    /// Send a request, returning a redirect response instead of following it
+    ///
+    /// Step through a redirect chain with `Response::follow_redirect`, for
+    /// flows that need to look at or act on each hop. Redirects are left
+    /// alone however the client is configured; the rest of the client's
+    /// behavior, such as retries and middleware, applies as usual.
+    pub async fn send_no_redirect_follow(&self, request: Request) -> Result<Response> {
+        let sent = request.clone();
+        let mut response = self.send_with(request, false).await?;
+        response.extensions_mut().insert(redirect::Sent(sent));
+        Ok(response)
+    }
+
+    /// Send a batch of requests, at most `concurrency` at a time
+    ///
+    /// Results come back in the order of `requests`, and each one stands
+    /// on its own: a failed request doesn't stop the rest. A concurrency
+    /// of zero is treated as one. The client's `max_concurrent_requests`
+    /// limit still applies on top of this one.
+    pub async fn send_all(&self, requests: Vec<Request>, concurrency: usize) -> Vec<Result<Response>> {
+        let semaphore = Semaphore::new(concurrency.max(1));
+        let mut pending: FuturesUnordered<_> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, request)| {
+                let semaphore = &semaphore;
+                async move {
+                    let _permit = semaphore.acquire().await;
+                    (index, self.send(request).await)
+                }
+            })
+            .collect();
+
+        let mut results: Vec<Option<Result<Response>>> = (0..pending.len()).map(|_| None).collect();
+        while let Some((index, result)) = pending.next().await {
+            results[index] = Some(result);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    async fn send_with(&self, mut request: Request, follow_redirects: bool) -> Result<Response> {
+        if let Some(timeout) = self.method_timeouts.get(request.method()) {
+            request.default_timeout(*timeout);
+        }
+        self.send_context().send(request, follow_redirects).await
+    }
+
+    /// Collect the settings requests from this client are sent with
+    fn send_context(&self) -> SendContext {
+        let client = self.connectors.current().map(Arc::new);
+        SendContext {
+            client: client.unwrap_or_else(|| self.inner.clone()),
+            cookie_jar: self.cookie_jar.clone(),
+            connectors: Some(self.connectors.clone()),
+            concurrency_limit: self.concurrency_limit.clone(),
+            circuit_breaker: self.circuit_breaker.clone(),
+            trace_propagation: self.trace_propagation,
+            response_cache: self.response_cache.clone(),
+            accept_encodings: self.accept_encodings.clone(),
+            retry: self.retry.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            middleware: self.middleware.clone(),
+            lifecycle_log: self.lifecycle_log,
+            redirects: self.redirects,
+            header_limits: self.header_limits,
+            stream_idle_timeout: self.stream_idle_timeout,
+            detect_charset: self.detect_charset,
+            body_pipeline: self.body_pipeline.clone(),
+            error_on_status: self.error_on_status,
+            last_request: self.last_request.clone(),
+            #[cfg(feature = "ntlm")]
+            ntlm: self.ntlm.clone(),
+        }
+    }
+
+    /// Parse and send a raw HTTP request, as written in `.http` files
+    ///
+    /// The text is a request line, headers, a blank line and an optional
+    /// body. A path target is resolved against the `Host` header, or the
+    /// client's base URL if there is none.
+    pub async fn send_raw(&self, raw: &str) -> Result<Response> {
+        let raw = parse_raw_request(raw, self.base_url.as_ref())?;
+        let mut builder = self.request(raw.method, raw.url);
+        for (name, value) in &raw.headers {
+            // Derived from the URL and body by the transport
+            if name.eq_ignore_ascii_case("host") || name.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+            builder = builder.header(name, value)?;
+        }
+        if !raw.body.is_empty() {
+            builder = builder.bytes(raw.body.into_bytes())?;
+        }
+        builder.send().await
+    }
+
+    /// Get the referer policy redirects are followed with, if any
+    pub(crate) fn referer_policy(&self) -> Option<RefererPolicy> {
+        self.redirects.map(|redirects| redirects.policy)
     }
 
     /// Get the underlying reqwest client
+    ///
+    /// With a maximum connection age this is the client's first reqwest
+    /// client, which is replaced for new requests once it expires.
     pub fn inner(&self) -> &ReqwestClient {
         &self.inner
     }
@@ -152,6 +316,65 @@ impl Client {
         self.base_url.as_ref()
     }
 
+    /// Get the proxy configuration if set
+    pub fn proxy_config(&self) -> Option<&ProxyConfig> {
+        self.connectors.proxy_config.as_ref()
+    }
+
+    /// Get the TLS configuration if set
+    pub fn tls_config(&self) -> Option<&TlsConfig> {
+        self.connectors.tls_config.as_ref()
+    }
+
+    /// Get the circuit breaker if one is configured
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_deref()
+    }
+
+    /// Derive a client with a different base URL
+    ///
+    /// The new client shares the connection pool and cookie jar with this one.
+    pub fn with_base_url(&self, url: Url) -> Client {
+        let mut client = self.clone();
+        client.base_url = Some(url);
+        client
+    }
+
+    /// Derive a client with an extra default header
+    ///
+    /// The new client shares the connection pool and cookie jar with this one.
+    pub fn with_default_header(&self, name: &str, value: &str) -> Result<Client> {
+        let name = name.parse::<http::header::HeaderName>()?;
+        let value = value.parse::<HeaderValue>()?;
+        let mut client = self.clone();
+        client.default_headers.insert(name, value);
+        Ok(client)
+    }
+
+    /// Get the response cache if one is configured
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.response_cache.as_deref()
+    }
+
+    /// Get a snapshot of the last request this client sent
+    ///
+    /// Always `None` unless the client was built with
+    /// `ClientBuilder::record_last_request`. Clones share the record.
+    pub fn last_request(&self) -> Option<RequestSnapshot> {
+        self.last_request.as_ref()?.lock().ok()?.clone()
+    }
+
+    /// Send the last request again
+    ///
+    /// Fails with `Error::InvalidRequest` when nothing was recorded or the
+    /// last request had a streaming body.
+    pub async fn replay_last(&self) -> Result<Response> {
+        let snapshot = self
+            .last_request()
+            .ok_or_else(|| Error::invalid_request("No request has been recorded"))?;
+        self.send(snapshot.to_request()?).await
+    }
+
     /// Check if the client is closed
     pub fn is_closed(&self) -> bool {
         // Reqwest doesn't expose this, so we assume it's always open
@@ -184,43 +407,184 @@ impl Default for Client {
 ///     .build();
 /// ```
 pub struct ClientBuilder {
-    reqwest_builder: ReqwestBuilder,
+    reqwest_options: Vec<ReqwestOption>,
     cookie_jar: Option<CookieJar>,
     timeout_config: TimeoutConfig,
+    method_timeouts: HashMap<Method, Duration>,
     default_headers: HeaderMap,
     base_url: Option<Url>,
     proxy_config: Option<ProxyConfig>,
     tls_config: Option<TlsConfig>,
     auth_config: Option<AuthConfig>,
+    max_concurrent_requests: Option<usize>,
+    circuit_config: Option<CircuitConfig>,
+    http_version: Option<Version>,
+    alpn: Alpn,
+    normalize_urls: bool,
+    trace_propagation: bool,
+    cache_config: Option<CacheConfig>,
+    accept_encodings: Option<Vec<Encoding>>,
+    retry_config: Option<RetryConfig>,
+    respect_rate_limits: bool,
+    rate_limit: Option<f64>,
+    max_connection_age: Option<Duration>,
+    middleware: Option<MiddlewareChain>,
+    lifecycle_log: Option<log::Level>,
+    wire_debug: bool,
+    wire_debug_bodies: bool,
+    max_redirects: Option<usize>,
+    referer_policy: Option<RefererPolicy>,
+    header_limits: HeaderLimits,
+    stream_idle_timeout: Option<Duration>,
+    detect_charset: bool,
+    body_pipeline: Option<BodyPipeline>,
+    error_on_status: bool,
+    record_last_request: bool,
 }
 
+/// User agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("rusttpx/", env!("CARGO_PKG_VERSION"));
+
 impl ClientBuilder {
     /// Create a new client builder
+    ///
+    /// The builder starts with a `User-Agent` of `rusttpx/<version>`.
     pub fn new() -> Self {
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(
+            http::header::USER_AGENT,
+            HeaderValue::from_static(DEFAULT_USER_AGENT),
+        );
+
         Self {
-            reqwest_builder: ReqwestClient::builder(),
+            reqwest_options: Vec::new(),
             cookie_jar: None,
             timeout_config: TimeoutConfig::default(),
-            default_headers: HeaderMap::new(),
+            method_timeouts: HashMap::new(),
+            default_headers,
             base_url: None,
             proxy_config: None,
             tls_config: None,
             auth_config: None,
+            max_concurrent_requests: None,
+            circuit_config: None,
+            http_version: None,
+            alpn: Alpn::default(),
+            normalize_urls: false,
+            trace_propagation: false,
+            cache_config: None,
+            accept_encodings: None,
+            retry_config: None,
+            respect_rate_limits: false,
+            rate_limit: None,
+            max_connection_age: None,
+            middleware: None,
+            lifecycle_log: None,
+            wire_debug: false,
+            wire_debug_bodies: false,
+            header_limits: HeaderLimits::default(),
+            stream_idle_timeout: None,
+            detect_charset: false,
+            body_pipeline: None,
+            error_on_status: false,
+            record_last_request: false,
+            max_redirects: Some(10),
+            referer_policy: None,
         }
     }
 
+    /// Create a builder configured from environment variables
+    ///
+    /// Reads:
+    ///
+    /// - `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, via [`ProxyConfig::from_env`]
+    /// - `HTTP_USERNAME` and `HTTP_PASSWORD`, `HTTP_BEARER_TOKEN`, or
+    ///   `HTTP_API_KEY`, `HTTP_API_VALUE` and `HTTP_API_LOCATION`, via
+    ///   [`AuthConfig::from_env`]
+    /// - `RUSTTPX_TIMEOUT`: the request timeout in seconds, e.g. `2.5`
+    /// - `RUSTTPX_INSECURE`: `1`, `true` or `yes` turns off certificate
+    ///   verification; `0`, `false` or `no` leaves it on
+    /// - `RUSTTPX_BASE_URL`: the base URL relative request URLs are joined to
+    ///
+    /// Unset or empty variables leave the defaults alone. A value that
+    /// doesn't parse is an `Error::Config` naming the variable.
+    pub fn from_env() -> Result<Self> {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// Create a builder from variables read through `get`
+    ///
+    /// [`ClientBuilder::from_env`] with `std::env::var` as the lookup.
+    pub(crate) fn from_env_with(get: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut builder = Self::new();
+        // Empty values count as unset
+        let env_var = |name: &str| get(name).filter(|value| !value.trim().is_empty());
+
+        let proxy = ProxyConfig::from_env_with(&get);
+        if proxy.has_proxy() {
+            builder = builder.proxy_config(proxy);
+        }
+        let auth = AuthConfig::from_env_with(&get);
+        if auth.has_auth() {
+            builder = builder.auth_config(auth);
+        }
+
+        if let Some(value) = env_var("RUSTTPX_TIMEOUT") {
+            let secs = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .ok_or_else(|| Error::config(format!("Invalid RUSTTPX_TIMEOUT: {:?}", value)))?;
+            builder = builder.timeout(Duration::from_secs_f64(secs));
+        }
+        if let Some(value) = env_var("RUSTTPX_INSECURE") {
+            let insecure = match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => true,
+                "0" | "false" | "no" => false,
+                _ => return Err(Error::config(format!("Invalid RUSTTPX_INSECURE: {:?}", value))),
+            };
+            if insecure {
+                builder = builder.tls_config(TlsConfig::insecure());
+            }
+        }
+        if let Some(value) = env_var("RUSTTPX_BASE_URL") {
+            let url = value
+                .trim()
+                .parse::<Url>()
+                .map_err(|e| Error::config(format!("Invalid RUSTTPX_BASE_URL {:?}: {}", value, e)))?;
+            builder = builder.base_url(url);
+        }
+
+        Ok(builder)
+    }
+
     /// Set the default timeout for all requests
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout_config = TimeoutConfig::new(timeout);
-        self.reqwest_builder = self.reqwest_builder.timeout(timeout);
+        self.configure(move |builder| builder.timeout(timeout))
+    }
+
+    /// Set default timeouts by HTTP method
+    ///
+    /// A request whose method is in the map gets that timeout unless it
+    /// sets its own; other methods fall back to the client's `timeout`.
+    /// Replaces timeouts set earlier with `method_timeout`.
+    pub fn method_timeouts(mut self, timeouts: HashMap<Method, Duration>) -> Self {
+        self.method_timeouts = timeouts;
+        self
+    }
+
+    /// Set the default timeout for requests with `method`
+    pub fn method_timeout(mut self, method: Method, timeout: Duration) -> Self {
+        self.method_timeouts.insert(method, timeout);
         self
     }
 
     /// Set the connection timeout
     pub fn connect_timeout(mut self, timeout: Duration) -> Self {
         self.timeout_config = self.timeout_config.connect_timeout(timeout);
-        self.reqwest_builder = self.reqwest_builder.connect_timeout(timeout);
-        self
+        self.configure(move |builder| builder.connect_timeout(timeout))
     }
 
     /// Set the read timeout
@@ -236,14 +600,183 @@ impl ClientBuilder {
     }
 
     /// Set the pool idle timeout
-    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
-        self.reqwest_builder = self.reqwest_builder.pool_idle_timeout(timeout);
-        self
+    pub fn pool_idle_timeout(self, timeout: Duration) -> Self {
+        self.configure(move |builder| builder.pool_idle_timeout(timeout))
     }
 
     /// Set the maximum number of connections in the pool
-    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
-        self.reqwest_builder = self.reqwest_builder.pool_max_idle_per_host(max);
+    pub fn pool_max_idle_per_host(self, max: usize) -> Self {
+        self.configure(move |builder| builder.pool_max_idle_per_host(max))
+    }
+
+    /// Stop reusing connections once they are `age` old, however busy they are
+    ///
+    /// Long-lived connections pin a client to one backend; capping their age
+    /// lets load balancers rebalance. Requests already in flight finish on
+    /// their connection.
+    // Note: hyper's pool has no connection lifetime in this version, so the
+    // whole pool is replaced once it is `age` old and connections opened
+    // late in a pool's life are retired early
+    pub fn max_connection_age(mut self, age: Duration) -> Self {
+        self.max_connection_age = Some(age);
+        self
+    }
+
+    /// Cap the number of requests that may be in flight at once
+    ///
+    /// Requests beyond the cap wait for a permit before being sent. Permits
+    /// are released when the request completes, fails or times out.
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Enable a per-host circuit breaker
+    ///
+    /// Once a host has failed `failure_threshold` times in a row, requests
+    /// to it fail fast with a "circuit open" error until the cooldown passes.
+    pub fn circuit_breaker(mut self, config: CircuitConfig) -> Self {
+        self.circuit_config = Some(config);
+        self
+    }
+
+    /// Retry requests that fail with a retryable status or connection error
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry_config = Some(config);
+        self
+    }
+
+    /// Wait for the server's rate-limit reset before retrying a 429
+    ///
+    /// Turns on [`RetryConfig::respect_rate_limits`] for the client's retry
+    /// configuration, or the default one if there is none, whichever order
+    /// the two are set in.
+    pub fn respect_rate_limits(mut self, enabled: bool) -> Self {
+        self.respect_rate_limits = enabled;
+        self
+    }
+
+    /// Run every request and response through a middleware chain
+    ///
+    /// Middleware runs once per request, before the response cache and
+    /// retries. A middleware that short-circuits answers the request
+    /// without it being sent; that response skips response middleware.
+    /// Response bodies are read into memory so middleware can see them.
+    pub fn middleware(mut self, chain: MiddlewareChain) -> Self {
+        self.middleware = Some(chain);
+        self
+    }
+
+    /// Log each request's start, completion and failure with the `log` crate
+    ///
+    /// Records carry key-values (`event`, `method`, `url`, `status` or
+    /// `error_kind`, `elapsed_ms`) for loggers with structured output, for
+    /// users who don't want `tracing`.
+    pub fn lifecycle_logging(mut self, level: log::Level) -> Self {
+        self.lifecycle_log = Some(level);
+        self
+    }
+
+    /// Log each request and response's headers at `trace` level
+    ///
+    /// Lines go to the `rusttpx::wire` log target, prefixed `> ` when sent
+    /// and `< ` when received, with the HTTP version on the request and
+    /// status lines. Credentials and cookies are logged as `***`. This runs
+    /// as middleware around the client's own, so response bodies are read
+    /// into memory. See `middleware::WIRE_LOG_TARGET`.
+    pub fn wire_debug(mut self, enabled: bool) -> Self {
+        self.wire_debug = enabled;
+        self
+    }
+
+    /// Also log bodies with [`ClientBuilder::wire_debug`], turning it on
+    ///
+    /// Binary bodies are summarized by their size and first bytes.
+    pub fn wire_debug_bodies(mut self, enabled: bool) -> Self {
+        self.wire_debug |= enabled;
+        self.wire_debug_bodies = enabled;
+        self
+    }
+
+    /// Fail responses whose headers add up to more than `max` bytes
+    ///
+    /// Guards against header bombs. reqwest reads headers in full before
+    /// handing over a response, so the size is measured, as the total
+    /// length of header names and values, once they've been received. A
+    /// response over the limit fails with `Error::ResponseParse`.
+    pub fn max_response_header_size(mut self, max: usize) -> Self {
+        self.header_limits.max_size = Some(max);
+        self
+    }
+
+    /// Fail responses with more than `max` header fields
+    ///
+    /// Repeated headers such as `Set-Cookie` count once per field. Like
+    /// `max_response_header_size`, the check runs on received headers and
+    /// fails with `Error::ResponseParse`.
+    pub fn max_response_headers(mut self, max: usize) -> Self {
+        self.header_limits.max_count = Some(max);
+        self
+    }
+
+    /// Fail response body streams when no data arrives for `timeout`
+    ///
+    /// Detects servers that stall partway through a long-lived stream.
+    /// Applies to `bytes_stream` and everything built on it; the wait is
+    /// measured from when the next chunk is asked for, so a slow consumer
+    /// doesn't trip it. The stream yields `Error::Timeout` and ends. This
+    /// is separate from the request `timeout`, which covers the whole
+    /// exchange.
+    pub fn stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Run response bodies through `pipeline` when they're read
+    ///
+    /// See [`BodyPipeline`]. Without one, bodies are returned as the
+    /// connection and middleware leave them.
+    pub fn body_pipeline(mut self, pipeline: BodyPipeline) -> Self {
+        self.body_pipeline = Some(pipeline);
+        self
+    }
+
+    /// Guess the charset of text responses that don't declare one
+    ///
+    /// When enabled, `Response::text` on a body without a `charset`
+    /// parameter honours a byte order mark, keeps valid UTF-8 as is, and
+    /// otherwise decodes with the encoding `chardetng` finds most likely,
+    /// instead of replacing invalid bytes. Off by default.
+    pub fn detect_charset(mut self, enabled: bool) -> Self {
+        self.detect_charset = enabled;
+        self
+    }
+
+    /// Fail with `Error::Status` for 4xx and 5xx responses instead of returning them
+    ///
+    /// Saves calling `error_for_status` after every send. The error message
+    /// includes the start of the response body. Off by default; a request
+    /// can opt out with `RequestBuilder::allow_error_status`.
+    pub fn error_on_status(mut self, enabled: bool) -> Self {
+        self.error_on_status = enabled;
+        self
+    }
+
+    /// Keep a snapshot of the last request sent, for debugging
+    ///
+    /// See `Client::last_request` and `Client::replay_last`. Off by
+    /// default, since every request's in-memory body is copied.
+    pub fn record_last_request(mut self, enabled: bool) -> Self {
+        self.record_last_request = enabled;
+        self
+    }
+
+    /// Limit the rate of requests sent by the client
+    ///
+    /// Every attempt, including retries, waits its turn so that at most
+    /// `requests_per_second` requests go out per second.
+    pub fn rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.rate_limit = Some(requests_per_second);
         self
     }
 
@@ -260,6 +793,18 @@ impl ClientBuilder {
         self.default_header("User-Agent", user_agent)
     }
 
+    /// Don't send the default `rusttpx/<version>` user agent
+    ///
+    /// A user agent set with `user_agent` is kept.
+    pub fn no_default_user_agent(mut self) -> Self {
+        if self.default_headers.get(http::header::USER_AGENT)
+            == Some(&HeaderValue::from_static(DEFAULT_USER_AGENT))
+        {
+            self.default_headers.remove(http::header::USER_AGENT);
+        }
+        self
+    }
+
     /// Set the base URL for all requests
     pub fn base_url(mut self, url: impl Into<Url>) -> Self {
         self.base_url = Some(url.into());
@@ -268,10 +813,110 @@ impl ClientBuilder {
 
     /// Enable or disable HTTP/2
     pub fn http2_prior_knowledge(mut self) -> Self {
-        self.reqwest_builder = self.reqwest_builder.http2_prior_knowledge();
+        self.alpn = Alpn::Http2;
+        self.configure(|builder| builder.http2_prior_knowledge())
+    }
+
+    /// Only use HTTP/1.1, never negotiating HTTP/2
+    pub fn http1_only(mut self) -> Self {
+        self.http_version = None;
+        self.alpn = Alpn::Http1;
+        self.configure(|builder| builder.http1_only())
+    }
+
+    /// Require HTTP/2 negotiated via ALPN
+    ///
+    /// Requests fail with an unsupported version error if the server only
+    /// speaks HTTP/1.1. Plain `http://` URLs have no ALPN, so use
+    /// `http2_prior_knowledge` for h2c servers.
+    // Note: reqwest doesn't allow restricting the ALPN protocol list in this version,
+    // so h2 is enforced by marking every request as HTTP/2
+    pub fn http2_only(mut self) -> Self {
+        self.http_version = Some(Version::HTTP_2);
+        self
+    }
+
+    /// Normalize the percent-encoding of request URLs before sending
+    ///
+    /// Leave disabled for servers that require the exact bytes of the URL.
+    pub fn normalize_urls(mut self, enabled: bool) -> Self {
+        self.normalize_urls = enabled;
+        self
+    }
+
+    /// Advertise exactly these encodings, most preferred first, and decode them
+    ///
+    /// Sends an `Accept-Encoding` with descending q-values (for example
+    /// `br;q=1.0, gzip;q=0.8`). A response using any other encoding fails
+    /// with a compression error.
+    pub fn accept_encodings(mut self, encodings: &[Encoding]) -> Self {
+        if let Ok(value) = HeaderValue::from_str(&compression::accept_encoding_header(encodings)) {
+            self.default_headers.insert(http::header::ACCEPT_ENCODING, value);
+        }
+        self.accept_encodings = Some(encodings.to_vec());
+        self
+    }
+
+    /// Cache successful responses, keyed by the full request including its body
+    ///
+    /// Responses are cached as received, before response middleware runs,
+    /// and cache hits go through the middleware like fresh responses.
+    pub fn response_cache(mut self, config: CacheConfig) -> Self {
+        self.cache_config = Some(config);
         self
     }
 
+    /// Send W3C `traceparent`/`tracestate` headers with every request
+    ///
+    /// A `TraceContext` in the request extensions is continued; otherwise
+    /// each request starts a new trace.
+    pub fn trace_propagation(mut self, enabled: bool) -> Self {
+        self.trace_propagation = enabled;
+        self
+    }
+
+    /// Connect to `addr` for requests to `domain`, bypassing DNS
+    ///
+    /// An internationalized domain matches requests to its punycode form.
+    /// A malformed one is kept as given, so it never matches.
+    pub fn resolve(self, domain: &str, addr: std::net::SocketAddr) -> Self {
+        let domain = ascii_host(domain).unwrap_or_else(|_| domain.to_string());
+        self.configure(move |builder| builder.resolve(&domain, addr))
+    }
+
+    /// Try IPv4 addresses first when connecting to dual-stack hosts
+    ///
+    /// IPv6 addresses are still tried if the IPv4 ones haven't connected
+    /// after the connector's fallback delay. Overrides `prefer_ipv6`.
+    pub fn prefer_ipv4(self) -> Self {
+        self.configure(|builder| builder.dns_resolver(Arc::new(PreferFamily(IpFamily::V4))))
+    }
+
+    /// Try IPv6 addresses first when connecting to dual-stack hosts
+    ///
+    /// IPv4 addresses are still tried if the IPv6 ones haven't connected
+    /// after the connector's fallback delay, so a broken IPv6 route costs
+    /// that delay rather than a connect timeout. Overrides `prefer_ipv4`.
+    pub fn prefer_ipv6(self) -> Self {
+        self.configure(|builder| builder.dns_resolver(Arc::new(PreferFamily(IpFamily::V6))))
+    }
+
+    // Note: reqwest doesn't expose hyper's happy_eyeballs_timeout in this
+    // version, so the delay before racing the other address family
+    // (RFC 8305) is fixed at hyper's 300ms
+    // pub fn happy_eyeballs(self, delay: Duration) -> Self {
+    //     self.configure(move |builder| builder.happy_eyeballs_timeout(delay))
+    // }
+
+    // Note: reqwest doesn't accept a custom connector in this version, and
+    // its own connector only sets TCP_NODELAY and keepalive, so socket
+    // options such as TCP_USER_TIMEOUT, IP_TOS (DSCP marking) and
+    // SO_REUSEADDR can't be applied to its sockets
+    // pub fn socket_option(mut self, option: SocketOption) -> Self {
+    //     self.socket_options.push(option);
+    //     self
+    // }
+
     /// Set the cookie jar
     pub fn cookie_jar(mut self, cookie_jar: CookieJar) -> Self {
         self.cookie_jar = Some(cookie_jar);
@@ -291,6 +936,9 @@ impl ClientBuilder {
     }
 
     /// Set authentication configuration
+    ///
+    /// Static credentials are sent as default headers. NTLM credentials are
+    /// used to answer `WWW-Authenticate: NTLM` challenges.
     pub fn auth_config(mut self, config: AuthConfig) -> Self {
         self.auth_config = Some(config);
         self
@@ -305,56 +953,136 @@ impl ClientBuilder {
 
     /// Set the maximum redirects to follow
     pub fn redirect(mut self, max_redirects: usize) -> Self {
-        self.reqwest_builder = self.reqwest_builder.redirect(
+        self.max_redirects = Some(max_redirects);
+        self.configure(move |builder| builder.redirect(
             reqwest::redirect::Policy::limited(max_redirects)
-        );
-        self
+        ))
     }
 
     /// Disable redirects
     pub fn no_redirect(mut self) -> Self {
-        self.reqwest_builder = self.reqwest_builder.redirect(
+        self.max_redirects = None;
+        self.configure(|builder| builder.redirect(
             reqwest::redirect::Policy::none()
-        );
-        self
+        ))
     }
 
     /// Set the referer policy
-    pub fn referer(mut self, referer: bool) -> Self {
-        self.reqwest_builder = self.reqwest_builder.referer(referer);
+    pub fn referer(self, referer: bool) -> Self {
+        self.configure(move |builder| builder.referer(referer))
+    }
+
+    /// Record TLS details of each connection on its responses
+    ///
+    /// See `Response::tls_info`. Off by default, as it copies the server's
+    /// certificate for every connection.
+    pub fn tls_info(self, enabled: bool) -> Self {
+        self.configure(move |builder| builder.tls_info(enabled))
+    }
+
+    /// Decide the `Referer` sent on each redirect with `policy`
+    ///
+    /// Redirects are then followed by the client rather than reqwest, up to
+    /// the `redirect` limit and not at all after `no_redirect`. This takes
+    /// precedence over `referer`.
+    pub fn referer_policy(mut self, policy: RefererPolicy) -> Self {
+        self.referer_policy = Some(policy);
+        self
+    }
+
+    /// Record a setting for the underlying reqwest builder
+    ///
+    /// Settings are replayed whenever a connector is built, so clients for
+    /// per-request identities share the client's configuration.
+    fn configure<F>(mut self, option: F) -> Self
+    where
+        F: Fn(ReqwestBuilder) -> ReqwestBuilder + Send + Sync + 'static,
+    {
+        self.reqwest_options.push(Arc::new(option));
         self
     }
 
     /// Build the client
-    pub fn build(self) -> Client {
-        // Apply proxy configuration
-        let reqwest_builder = if let Some(proxy_config) = self.proxy_config {
-            proxy_config.apply_to_builder(self.reqwest_builder)
+    pub fn build(mut self) -> Client {
+        let redirects = self.referer_policy.map(|policy| Redirects {
+            policy,
+            limit: self.max_redirects,
+        });
+        if redirects.is_some() {
+            self = self.configure(|builder| {
+                builder.redirect(reqwest::redirect::Policy::none()).referer(false)
+            });
+        }
+        if self.wire_debug {
+            self.middleware = Some(middleware::with_wire_debug(self.middleware.take(), self.wire_debug_bodies));
+        }
+        let retry = if self.respect_rate_limits {
+            Some(self.retry_config.unwrap_or_default().respect_rate_limits(true))
         } else {
-            self.reqwest_builder
+            self.retry_config
         };
-
-        // Apply TLS configuration
-        let reqwest_builder = if let Some(tls_config) = self.tls_config {
-            tls_config.apply_to_builder(reqwest_builder)
-        } else {
-            reqwest_builder
+        let connectors = Connectors {
+            options: self.reqwest_options,
+            proxy_config: self.proxy_config,
+            tls_config: self.tls_config,
+            alpn: self.alpn,
+            max_connection_age: self.max_connection_age,
+            current: std::sync::Mutex::new(None),
+            derived: std::sync::Mutex::new(HashMap::new()),
         };
 
+        let mut default_headers = self.default_headers;
+        #[cfg(feature = "ntlm")]
+        let mut ntlm = None;
+        if let Some(auth_config) = &self.auth_config {
+            let _ = auth_config.apply_to_headers(&mut default_headers);
+            #[cfg(feature = "ntlm")]
+            if let AuthType::Ntlm { username, password, domain } = auth_config.get_auth_type() {
+                ntlm = Some(Arc::new(NtlmCredentials::new(username, password, domain)));
+            }
+        }
+
         // Build the reqwest client
-        let reqwest_client = reqwest_builder
+        let reqwest_client = connectors
+            .builder(&ConnectorOptions::default())
             .build()
             .expect("Failed to build reqwest client");
+        if connectors.max_connection_age.is_some() {
+            *connectors.current.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some(Connector::new(reqwest_client.clone()));
+        }
 
         // Create cookie jar
         let cookie_jar = self.cookie_jar.unwrap_or_else(CookieJar::new);
 
         Client {
             inner: Arc::new(reqwest_client),
+            connectors: Arc::new(connectors),
             cookie_jar: Arc::new(cookie_jar),
             timeout_config: self.timeout_config,
-            default_headers: self.default_headers,
+            method_timeouts: self.method_timeouts,
+            default_headers,
             base_url: self.base_url,
+            concurrency_limit: self.max_concurrent_requests.map(|max| Arc::new(Semaphore::new(max))),
+            circuit_breaker: self.circuit_config.map(|config| Arc::new(CircuitBreaker::new(config))),
+            http_version: self.http_version,
+            normalize_urls: self.normalize_urls,
+            trace_propagation: self.trace_propagation,
+            response_cache: self.cache_config.map(|config| Arc::new(ResponseCache::new(config))),
+            accept_encodings: self.accept_encodings.map(Arc::from),
+            retry: retry.map(Arc::new),
+            rate_limiter: self.rate_limit.map(|rps| Arc::new(Mutex::new(RateLimiter::new(rps)))),
+            middleware: self.middleware.map(Arc::new),
+            lifecycle_log: self.lifecycle_log,
+            redirects,
+            header_limits: self.header_limits,
+            stream_idle_timeout: self.stream_idle_timeout,
+            detect_charset: self.detect_charset,
+            body_pipeline: self.body_pipeline.map(Arc::new),
+            error_on_status: self.error_on_status,
+            last_request: self.record_last_request.then(LastRequest::default),
+            #[cfg(feature = "ntlm")]
+            ntlm,
         }
     }
 }
@@ -365,6 +1093,156 @@ impl Default for ClientBuilder {
     }
 }
 
+/// A setting recorded for the underlying reqwest builder
+type ReqwestOption = Arc<dyn Fn(ReqwestBuilder) -> ReqwestBuilder + Send + Sync>;
+
+/// Builds reqwest clients from a client's configuration
+///
+/// Each set of `ConnectorOptions` a request needs, such as a client
+/// identity chosen with `RequestBuilder::client_identity` or a connect
+/// timeout chosen with `RequestBuilder::connect_timeout`, gets its own
+/// reqwest client, built on first use and kept for the life of the client.
+/// Its connections are pooled separately from the client's own, so a TLS
+/// connection authenticated with one certificate is never reused for
+/// another. With a maximum connection age, clients are replaced once they
+/// reach that age.
+pub(crate) struct Connectors {
+    options: Vec<ReqwestOption>,
+    proxy_config: Option<ProxyConfig>,
+    tls_config: Option<TlsConfig>,
+    alpn: Alpn,
+    max_connection_age: Option<Duration>,
+    current: std::sync::Mutex<Option<Connector>>,
+    derived: std::sync::Mutex<HashMap<ConnectorOptions, Connector>>,
+}
+
+/// Settings a derived reqwest client is built with, on top of the client's
+///
+/// The default is the client's own configuration.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct ConnectorOptions {
+    /// Client certificate to present
+    pub(crate) identity: Option<ClientIdentity>,
+    /// Time limit for connecting
+    pub(crate) connect_timeout: Option<Duration>,
+    /// Return redirects instead of following them
+    pub(crate) no_redirects: bool,
+    /// Close connections after each request instead of pooling them
+    pub(crate) no_pooling: bool,
+    /// Send plain HTTP requests with an absolute-form target
+    pub(crate) absolute_form: bool,
+}
+
+/// A reqwest client and when its connection pool was created
+struct Connector {
+    client: ReqwestClient,
+    created: Instant,
+}
+
+impl Connector {
+    fn new(client: ReqwestClient) -> Self {
+        Self {
+            client,
+            created: Instant::now(),
+        }
+    }
+}
+
+impl Connectors {
+    /// Create a reqwest builder with the client's settings applied
+    fn builder(&self, options: &ConnectorOptions) -> ReqwestBuilder {
+        let mut builder = self
+            .options
+            .iter()
+            .fold(ReqwestClient::builder(), |builder, option| option(builder));
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if options.no_redirects {
+            builder = builder.redirect(reqwest::redirect::Policy::none()).referer(false);
+        }
+        if options.no_pooling {
+            builder = builder.pool_max_idle_per_host(0);
+        }
+
+        // Apply proxy configuration
+        if let Some(proxy_config) = &self.proxy_config {
+            builder = proxy_config.clone().apply_to_builder(builder);
+        }
+        // hyper writes absolute-form targets for proxied connections, so
+        // treat each origin as its own proxy. Added after the configured
+        // proxies, which take precedence.
+        if options.absolute_form {
+            builder = builder.proxy(reqwest::Proxy::custom(|url| {
+                if url.scheme() != "http" {
+                    return None;
+                }
+                format!("http://{}:{}", url.host_str()?, url.port_or_known_default()?)
+                    .parse::<Url>()
+                    .ok()
+            }));
+        }
+
+        // Apply TLS configuration, with the identity replacing the client certificate
+        let tls_config = match &options.identity {
+            Some(identity) => Some(
+                self.tls_config
+                    .clone()
+                    .unwrap_or_default()
+                    .client_cert(identity.identity().clone()),
+            ),
+            None => self.tls_config.clone(),
+        };
+        if let Some(tls_config) = tls_config {
+            builder = tls_config.apply_with_alpn(builder, self.alpn);
+        }
+
+        builder
+    }
+
+    /// Check whether a connector's connections have reached the maximum age
+    fn is_expired(&self, connector: &Connector) -> bool {
+        self.max_connection_age
+            .is_some_and(|age| connector.created.elapsed() >= age)
+    }
+
+    /// Get the client's own reqwest client when connections have a maximum age
+    ///
+    /// Returns `None` without a maximum age, as the client then keeps a
+    /// single reqwest client for its whole life.
+    pub(crate) fn current(&self) -> Option<ReqwestClient> {
+        self.max_connection_age?;
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let expired = match current.as_ref() {
+            Some(connector) => self.is_expired(connector),
+            None => true,
+        };
+        if expired {
+            // Keep the previous client if a replacement can't be built
+            if let Ok(client) = self.builder(&ConnectorOptions::default()).build() {
+                *current = Some(Connector::new(client));
+            }
+        }
+        current.as_ref().map(|connector| connector.client.clone())
+    }
+
+    /// Get the reqwest client built with `options`, building it if needed
+    pub(crate) fn get(&self, options: ConnectorOptions) -> Result<ReqwestClient> {
+        let mut derived = self.derived.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(connector) = derived.get(&options) {
+            if !self.is_expired(connector) {
+                return Ok(connector.client.clone());
+            }
+        }
+        let client = self.builder(&options).build().map_err(|e| match options.identity {
+            Some(_) => Error::tls(format!("Failed to build client for identity: {}", e)),
+            None => Error::config(format!("Failed to build client: {}", e)),
+        })?;
+        derived.insert(options, Connector::new(client.clone()));
+        Ok(client)
+    }
+}
+
 /// Convenience methods for common HTTP operations
 impl Client {
     /// Send a GET request and return JSON
@@ -375,6 +1253,18 @@ impl Client {
         self.get(url).send_json().await
     }
 
+    /// Send a GET request and return JSON with the server's rate-limit state
+    ///
+    /// See [`Response::rate_limit_info`] for the headers read.
+    pub async fn get_json_with_rate_limit<T>(&self, url: impl Into<Url>) -> Result<(T, Option<RateLimitInfo>)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let response = self.get(url).send().await?;
+        let rate_limit = response.rate_limit_info();
+        Ok((response.json().await?, rate_limit))
+    }
+
     /// Send a POST request with JSON body and return JSON
     pub async fn post_json<T, U>(&self, url: impl Into<Url>, body: &T) -> Result<U>
     where
@@ -409,11 +1299,33 @@ impl Client {
     {
         self.delete(url).send_json().await
     }
+
+    /// Check that a host responds and return the round-trip latency
+    ///
+    /// Issues a HEAD request, falling back to GET if the server answers
+    /// `405 Method Not Allowed`. Any status code counts as a response;
+    /// only network and timeout failures are returned as errors, even with
+    /// `error_on_status`.
+    pub async fn ping(&self, url: impl Into<Url>) -> Result<Duration> {
+        let url = url.into();
+        let start = Instant::now();
+        let response = self.head(url.clone()).timeout(PING_TIMEOUT).allow_error_status().send().await?;
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            self.get(url).timeout(PING_TIMEOUT).allow_error_status().send().await?;
+        }
+        Ok(start.elapsed())
+    }
+
+    /// Check whether a host responds at all (see [`Client::ping`])
+    pub async fn is_reachable(&self, url: impl Into<Url>) -> bool {
+        self.ping(url).await.is_ok()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tls_fixtures::{fixture, ok, pem_der, TlsServer};
 
     #[tokio::test]
     async fn test_client_creation() {
@@ -438,4 +1350,726 @@ mod tests {
         let request = client.get("https://httpbin.org/get");
         assert_eq!(request.method(), &Method::GET);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_derived_clients_share_cookie_jar() {
+        let client = Client::new();
+        let api = client.with_base_url("https://api.example.com/".parse().unwrap());
+        let tagged = client.with_default_header("X-Tag", "1").unwrap();
+
+        assert_eq!(api.base_url().unwrap().as_str(), "https://api.example.com/");
+        assert!(client.base_url().is_none());
+        assert!(client.with_default_header("bad header", "1").is_err());
+
+        let request = tagged.get("https://example.com/".parse::<Url>().unwrap()).build().unwrap();
+        assert_eq!(request.headers().get("X-Tag").unwrap(), "1");
+        let request = client.get("https://example.com/".parse::<Url>().unwrap()).build().unwrap();
+        assert!(request.headers().get("X-Tag").is_none());
+
+        // A request's own value replaces the default; more values are appended
+        let request = tagged
+            .get("https://example.com/".parse::<Url>().unwrap())
+            .header("X-Tag", "2")
+            .unwrap()
+            .header("X-Tag", "3")
+            .unwrap()
+            .build()
+            .unwrap();
+        let tags: Vec<_> = request.headers().get_all("X-Tag").iter().collect();
+        assert_eq!(tags, ["2", "3"]);
+
+        api.cookie_jar().add_simple("session", "abc");
+        assert!(client.cookie_jar().has_cookie("session"));
+        assert!(tagged.cookie_jar().has_cookie("session"));
+    }
+
+    #[tokio::test]
+    async fn test_http_version_controls() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let client = Client::builder().http1_only().build();
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert_eq!(response.version(), Version::HTTP_11);
+
+        let client = Client::builder().http2_prior_knowledge().build();
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert_eq!(response.version(), Version::HTTP_2);
+
+        // Without TLS there is no ALPN, so an HTTP/2 requirement cannot be met
+        let client = Client::builder().http2_only().build();
+        assert!(client.get(url).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_normalize_urls() {
+        let url: Url = "http://example.com/a%2fb".parse().unwrap();
+
+        let client = Client::builder().normalize_urls(true).build();
+        assert_eq!(client.get(url.clone()).url().path(), "/a%2Fb");
+
+        let client = Client::new();
+        assert_eq!(client.get(url).url().path(), "/a%2fb");
+    }
+
+    #[tokio::test]
+    async fn test_idn_host() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let addr = *server.address();
+        let client = Client::builder().resolve("例え.jp", addr).build();
+
+        // The connection and `Host` use punycode, the display URL Unicode
+        let url: Url = format!("http://例え.jp:{}/path", addr.port()).parse().unwrap();
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.url().host_str(), Some("xn--r8jz45g.jp"));
+        assert_eq!(response.display_url(), format!("http://例え.jp:{}/path", addr.port()));
+
+        // A `Host` override is converted too
+        client.get(server.uri().parse::<Url>().unwrap()).host("bücher.example").unwrap().send().await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let host = |i: usize| requests[i].headers.get(&"host".into()).map(|v| v.as_str().to_string());
+        assert_eq!(host(0), Some(format!("xn--r8jz45g.jp:{}", addr.port())));
+        assert_eq!(host(1).as_deref(), Some("xn--bcher-kva.example"));
+
+        let result = client.get(server.uri().parse::<Url>().unwrap()).host("xn--a.example");
+        assert!(matches!(result, Err(Error::Url(url::ParseError::IdnaError))));
+    }
+
+    #[tokio::test]
+    async fn test_response_header_limits() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(path("/normal"))
+            .respond_with(ResponseTemplate::new(200).insert_header("X-Small", "ok"))
+            .mount(&server)
+            .await;
+        let mut many = ResponseTemplate::new(200);
+        for i in 0..50 {
+            many = many.insert_header(format!("X-Header-{}", i).as_str(), "value");
+        }
+        Mock::given(path("/many"))
+            .respond_with(many)
+            .mount(&server)
+            .await;
+        Mock::given(path("/large"))
+            .respond_with(ResponseTemplate::new(200).insert_header("X-Large", "x".repeat(8192).as_str()))
+            .mount(&server)
+            .await;
+        let base: Url = server.uri().parse().unwrap();
+        let client = Client::builder()
+            .max_response_headers(20)
+            .max_response_header_size(4096)
+            .build();
+
+        let response = client.get(base.join("/normal").unwrap()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let result = client.get(base.join("/many").unwrap()).send().await;
+        assert!(matches!(result, Err(Error::ResponseParse(_))), "{:?}", result.err());
+        let request = client.get(base.join("/large").unwrap()).build().unwrap();
+        let result = client.send(request).await;
+        assert!(matches!(result, Err(Error::ResponseParse(_))), "{:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_last() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        // Nothing is recorded unless asked for
+        let client = Client::new();
+        client.get(url.clone()).send().await.unwrap();
+        assert!(client.last_request().is_none());
+        assert!(client.replay_last().await.is_err());
+
+        let client = Client::builder().record_last_request(true).build();
+        client
+            .post(url.join("/items").unwrap())
+            .header("X-Attempt", "1")
+            .unwrap()
+            .text("payload")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let snapshot = client.last_request().unwrap();
+        assert_eq!(snapshot.method(), Method::POST);
+        assert_eq!(snapshot.url().path(), "/items");
+        assert_eq!(snapshot.headers()["x-attempt"], "1");
+        assert_eq!(snapshot.body(), Some(&b"payload"[..]));
+
+        client.replay_last().await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let hits: Vec<_> = requests.iter().filter(|r| r.url.path() == "/items").collect();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].body, b"payload");
+        assert_eq!(hits[1].headers.get(&"x-attempt".into()).unwrap().as_str(), "1");
+
+        // A consumed stream isn't kept, and can't be replayed
+        let stream = futures::stream::iter(vec![Ok::<_, std::io::Error>(b"streamed".to_vec())]);
+        let request = Request::new(Method::POST, url)
+            .body_stream(crate::request::StreamBody::new(reqwest::Body::wrap_stream(stream)));
+        client.send(request).await.unwrap();
+        let snapshot = client.last_request().unwrap();
+        assert!(!snapshot.is_replayable());
+        assert_eq!(snapshot.body(), None);
+        assert!(matches!(client.replay_last().await, Err(Error::InvalidRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_method_timeouts() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(300)))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .method_timeouts(HashMap::from([
+                (Method::GET, Duration::from_millis(100)),
+                (Method::POST, Duration::from_secs(2)),
+            ]))
+            .build();
+        let timed_out = |result: &Result<Response>| matches!(result, Err(Error::Network(e)) if e.is_timeout());
+
+        let result = client.get(url.clone()).send().await;
+        assert!(timed_out(&result), "{:?}", result.err());
+        let result = client.send(Request::new(Method::GET, url.clone())).await;
+        assert!(timed_out(&result), "{:?}", result.err());
+        assert_eq!(client.post(url.clone()).send().await.unwrap().status(), 200);
+
+        // The request's own timeout and the global one still apply
+        let response = client.get(url.clone()).timeout(Duration::from_secs(2)).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(client.put(url).send().await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_timeout() {
+        use futures::StreamExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Like httpbin's /drip: sends a chunk, then one more after `/drip/<gap ms>`
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        head.push(socket.read_u8().await.unwrap());
+                    }
+                    let head = String::from_utf8(head).unwrap();
+                    let gap: u64 = head.split(['/', ' ']).nth(3).unwrap().parse().unwrap();
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n")
+                        .await;
+                    tokio::time::sleep(Duration::from_millis(gap)).await;
+                    let _ = socket.write_all(b"1\r\nb\r\n0\r\n\r\n").await;
+                });
+            }
+        });
+
+        let client = Client::builder().stream_idle_timeout(Duration::from_millis(200)).build();
+        let drip = |gap: u64| {
+            let request = client.get(format!("http://{}/drip/{}", addr, gap).parse::<Url>().unwrap());
+            async move { request.send().await.unwrap().bytes_stream().collect::<Vec<_>>().await }
+        };
+
+        let chunks = drip(50).await;
+        assert_eq!(chunks.into_iter().map(|c| c.unwrap()).collect::<Vec<_>>().concat(), b"ab");
+
+        let chunks = drip(1000).await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap(), b"a");
+        assert!(matches!(chunks[1], Err(Error::Timeout { .. })), "{:?}", chunks[1]);
+    }
+
+    #[tokio::test]
+    async fn test_default_user_agent() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        Client::new().get(url.clone()).send().await.unwrap();
+        Client::builder().user_agent("MyApp/1.0").unwrap().build().get(url.clone()).send().await.unwrap();
+        Client::builder().no_default_user_agent().build().get(url).send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let user_agent = |i: usize| {
+            requests[i].headers.get(&"user-agent".into()).map(|v| v.as_str().to_string())
+        };
+        assert_eq!(user_agent(0), Some(format!("rusttpx/{}", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(user_agent(1).as_deref(), Some("MyApp/1.0"));
+        assert_eq!(user_agent(2), None);
+    }
+
+    #[tokio::test]
+    async fn test_send_raw() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::{body_string, header, method, path};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users/1"))
+            .and(header("accept", "application/json"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .and(body_string("{\"name\": \"widget\"}"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::new();
+        let host = server.address().to_string();
+
+        let raw = format!("GET /users/1 HTTP/1.1\nHost: {}\nAccept: application/json\n", host);
+        assert_eq!(client.send_raw(&raw).await.unwrap().status(), StatusCode::OK);
+
+        let raw = format!(
+            "POST {}/items HTTP/1.1\nContent-Type: application/json\n\n{{\"name\": \"widget\"}}\n",
+            server.uri()
+        );
+        assert_eq!(client.send_raw(&raw).await.unwrap().status(), StatusCode::CREATED);
+
+        let err = client.send_raw("NOT A REQUEST LINE\n").await.unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Server that records the peak number of requests handled at once
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let current = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        {
+            let current = current.clone();
+            let peak = peak.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let current = current.clone();
+                    let peak = peak.clone();
+                    tokio::spawn(async move {
+                        let mut buf = [0u8; 1024];
+                        let _ = socket.read(&mut buf).await;
+                        let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        current.fetch_sub(1, Ordering::SeqCst);
+                        let _ = socket
+                            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                            .await;
+                    });
+                }
+            });
+        }
+
+        let client = Client::builder().max_concurrent_requests(5).build();
+        let url: Url = format!("http://{}/", addr).parse().unwrap();
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let client = client.clone();
+                let url = url.clone();
+                tokio::spawn(async move { client.get(url).send().await })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().unwrap().is_success());
+        }
+
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(peak > 0 && peak <= 5, "peak concurrency was {}", peak);
+    }
+
+    #[tokio::test]
+    async fn test_max_connection_age() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Keep-alive server that counts the connections it accepts
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        {
+            let connections = connections.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(async move {
+                        let mut buf = [0u8; 1024];
+                        while let Ok(n) = socket.read(&mut buf).await {
+                            if n == 0 {
+                                break;
+                            }
+                            let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+                        }
+                    });
+                }
+            });
+        }
+
+        let client = Client::builder().max_connection_age(Duration::from_millis(200)).build();
+        let url: Url = format!("http://{}/", addr).parse().unwrap();
+        client.get(url.clone()).send().await.unwrap();
+        client.get(url.clone()).send().await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Once the connection is too old the next request opens a new one
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        client.get(url).send().await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ping_reachable() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let client = Client::new();
+        let url: Url = server.uri().parse().unwrap();
+        assert!(client.ping(url.clone()).await.is_ok());
+        assert!(client.is_reachable(url.clone()).await);
+
+        // Error statuses still count as responses, and the 405 still falls back to GET
+        let client = Client::builder().error_on_status(true).build();
+        assert!(client.ping(url.clone()).await.is_ok());
+        assert!(client.is_reachable(url).await);
+    }
+
+    #[tokio::test]
+    async fn test_ping_unreachable() {
+        // Bind and immediately drop a listener to get a closed port
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = Client::new();
+        let url: Url = format!("http://{}/", addr).parse().unwrap();
+        assert!(client.ping(url.clone()).await.is_err());
+        assert!(!client.is_reachable(url).await);
+    }
+
+    /// An HTTPS server that requires a client certificate and echoes its name
+    fn mtls_server() -> Url {
+        TlsServer::new("server.crt", "server.key").client_ca("client-ca.crt").serve(|connection| {
+            let peer = connection.peer_certificates().and_then(|certs| certs.first().cloned())?;
+            ["client-a", "client-b"]
+                .into_iter()
+                .find(|name| peer.0 == pem_der(&format!("{}.crt", name)))
+                .map(ok)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_client_identity_per_request() {
+        let url = mtls_server();
+        let server_cert = std::fs::read(fixture("server.crt")).unwrap();
+        let client = Client::builder()
+            .tls_config(TlsConfig::new().add_root_cert(reqwest::Certificate::from_pem(&server_cert).unwrap()))
+            .build();
+        let identity = |name: &str| {
+            ClientIdentity::from_pem_files(
+                &fixture(&format!("{}.crt", name)).into(),
+                &fixture(&format!("{}.key", name)).into(),
+            )
+            .unwrap()
+        };
+
+        // The client itself has no certificate to present
+        assert!(client.get(url.clone()).send().await.is_err());
+
+        for name in ["client-a", "client-b", "client-a"] {
+            let response = client.get(url.clone()).client_identity(identity(name)).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), name);
+        }
+        // Reusing an identity reuses its connector
+        assert_eq!(client.connectors.derived.lock().unwrap().len(), 2);
+
+        // The identity travels with a built request
+        let request = client.get(url).client_identity(identity("client-b")).build().unwrap();
+        assert_eq!(client.send(request).await.unwrap().text().await.unwrap(), "client-b");
+    }
+
+    #[tokio::test]
+    async fn test_response_tls_info() {
+        let url = mtls_server();
+        let server_cert = std::fs::read(fixture("server.crt")).unwrap();
+        let tls_config = || TlsConfig::new().add_root_cert(reqwest::Certificate::from_pem(&server_cert).unwrap());
+        let identity = ClientIdentity::from_pem_files(
+            &fixture("client-a.crt").into(),
+            &fixture("client-a.key").into(),
+        )
+        .unwrap();
+
+        let client = Client::builder().tls_config(tls_config()).tls_info(true).build();
+        let response = client.get(url.clone()).client_identity(identity.clone()).send().await.unwrap();
+        let tls_info = response.tls_info().unwrap();
+        assert_eq!(tls_info.peer_certificate(), Some(&pem_der("server.crt")[..]));
+
+        // Off by default
+        let client = Client::builder().tls_config(tls_config()).build();
+        let response = client.get(url).client_identity(identity).send().await.unwrap();
+        assert!(response.tls_info().is_none());
+
+        // Plaintext connections have no TLS details
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("GET"))
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let client = Client::builder().tls_info(true).build();
+        let response = client.get(server.uri().parse::<Url>().unwrap()).send().await.unwrap();
+        assert!(response.tls_info().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_per_request() {
+        // A non-routable address, so the connection attempt hangs until the timeout
+        let url: Url = "http://10.255.255.1:81/".parse().unwrap();
+        let client = Client::new();
+        for _ in 0..2 {
+            let started = Instant::now();
+            let result = client.get(url.clone()).connect_timeout(Duration::from_millis(1)).send().await;
+            assert!(matches!(result, Err(Error::Network(_))));
+            assert!(started.elapsed() < Duration::from_secs(1));
+        }
+        // Both requests went through one connector built with the timeout
+        let keys: Vec<_> = client.connectors.derived.lock().unwrap().keys().cloned().collect();
+        let expected = ConnectorOptions {
+            connect_timeout: Some(Duration::from_millis(1)),
+            ..ConnectorOptions::default()
+        };
+        assert_eq!(keys, vec![expected]);
+
+        let request = RequestBuilder::new(
+            client.inner.clone(),
+            Arc::new(CookieJar::new()),
+            Method::GET,
+            url,
+            TimeoutConfig::default(),
+            HeaderMap::new(),
+        );
+        let result = request.connect_timeout(Duration::from_millis(1)).send().await;
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_prefer_ip_family() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        // wiremock listens on IPv4 only, so preferring IPv6 has to fall back
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = format!("http://localhost:{}/", server.address().port()).parse().unwrap();
+
+        for client in [Client::builder().prefer_ipv4().build(), Client::builder().prefer_ipv6().build()] {
+            let started = Instant::now();
+            let response = client.get(url.clone()).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(started.elapsed() < Duration::from_secs(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_json_with_rate_limit() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("RateLimit-Limit", "60")
+                    .insert_header("RateLimit-Remaining", "59")
+                    .insert_header("RateLimit-Reset", "60")
+                    .set_body_json(serde_json::json!({"login": "octocat"})),
+            )
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let started = std::time::SystemTime::now();
+        let (user, rate_limit): (serde_json::Value, _) =
+            Client::new().get_json_with_rate_limit(url).await.unwrap();
+        assert_eq!(user["login"], "octocat");
+        let rate_limit = rate_limit.unwrap();
+        assert_eq!((rate_limit.limit, rate_limit.remaining), (Some(60), Some(59)));
+        assert!(rate_limit.reset.unwrap() >= started + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_from_env() {
+        let mut vars = HashMap::from([
+            ("RUSTTPX_BASE_URL", "https://api.example.com/v1/"),
+            ("RUSTTPX_TIMEOUT", "2.5"),
+            ("RUSTTPX_INSECURE", "true"),
+            ("HTTPS_PROXY", "http://proxy.example.com:3128"),
+            ("HTTP_BEARER_TOKEN", "env-token"),
+        ]);
+        let from = |vars: &HashMap<&str, &str>| {
+            ClientBuilder::from_env_with(|name| vars.get(name).map(|value| value.to_string()))
+        };
+
+        let client = from(&vars).unwrap().build();
+        assert_eq!(client.base_url().unwrap().as_str(), "https://api.example.com/v1/");
+        assert_eq!(client.timeout_config().get_timeout(), Some(Duration::from_millis(2500)));
+        assert!(!client.tls_config().unwrap().is_verify_enabled());
+        let proxy = client.proxy_config().unwrap();
+        assert_eq!(proxy.get_https_proxy().unwrap().as_str(), "http://proxy.example.com:3128/");
+        assert!(proxy.get_http_proxy().is_none());
+        assert_eq!(client.default_headers["authorization"], "Bearer env-token");
+
+        vars.insert("RUSTTPX_TIMEOUT", "soon");
+        let invalid = from(&vars).err().unwrap();
+        assert!(invalid.to_string().contains("RUSTTPX_TIMEOUT"));
+
+        vars.insert("RUSTTPX_TIMEOUT", " ");
+        vars.remove("RUSTTPX_BASE_URL");
+        vars.remove("RUSTTPX_INSECURE");
+        let client = from(&vars).unwrap().build();
+        assert!(client.base_url().is_none());
+        assert!(client.tls_config().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_error_on_status() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(404).set_body_string("no such widget"))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let client = Client::builder().error_on_status(true).build();
+        let err = client.get(url.clone()).send().await.unwrap_err();
+        let status_error = err.as_status_error().unwrap();
+        assert_eq!(status_error.status(), StatusCode::NOT_FOUND);
+        assert!(status_error.is_client_error());
+        assert!(err.to_string().contains("no such widget"), "{}", err);
+        let err = client.send(Request::new(Method::GET, url.clone())).await.unwrap_err();
+        assert!(matches!(err, Error::Status(_)));
+
+        let response = client.get(url.clone()).allow_error_status().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.text().await.unwrap(), "no such widget");
+        let request = client.get(url).allow_error_status().build().unwrap();
+        assert_eq!(client.send(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_send_all() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Echoes the request path, counting how many requests are in flight
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (counter, max) = (in_flight.clone(), peak.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (counter, max) = (counter.clone(), max.clone());
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        head.push(socket.read_u8().await.unwrap());
+                    }
+                    let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    counter.fetch_sub(1, Ordering::SeqCst);
+                    let head = String::from_utf8(head).unwrap();
+                    let path = head.split(' ').nth(1).unwrap().to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        path.len(),
+                        path
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        let client = Client::new();
+        let mut requests: Vec<Request> = (0..20)
+            .map(|i| Request::new(Method::GET, format!("http://{}/{}", addr, i).parse().unwrap()))
+            .collect();
+        // Nothing listens on port 1, so this one fails on its own
+        requests[7] = Request::new(Method::GET, "http://127.0.0.1:1/".parse().unwrap());
+
+        let results = client.send_all(requests, 4).await;
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.into_iter().enumerate() {
+            if i == 7 {
+                assert!(result.is_err());
+                continue;
+            }
+            assert_eq!(result.unwrap().text().await.unwrap(), format!("/{}", i));
+        }
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+        assert!(peak.load(Ordering::SeqCst) > 1);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+    }
+}
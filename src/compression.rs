@@ -0,0 +1,341 @@
+use std::sync::Arc;
+use http::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+
+use crate::cookies::CookieJar;
+use crate::error::{Error, Result};
+use crate::response::Response;
+
+/// Content codings the client can advertise and decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Brotli (`br`)
+    Brotli,
+    /// Gzip (`gzip`)
+    Gzip,
+    /// Zlib-wrapped deflate (`deflate`)
+    Deflate,
+    /// No encoding (`identity`)
+    Identity,
+}
+
+impl Encoding {
+    /// Get the token used in `Accept-Encoding` and `Content-Encoding`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    /// Parse a content-coding token
+    pub fn parse(token: &str) -> Option<Self> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(Encoding::Brotli),
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            "identity" => Some(Encoding::Identity),
+            _ => None,
+        }
+    }
+}
+
+/// Build an `Accept-Encoding` value listing encodings in preference order
+///
+/// The first encoding gets `q=1.0` and each following one 0.2 less,
+/// down to a floor of `q=0.1`.
+pub fn accept_encoding_header(encodings: &[Encoding]) -> String {
+    encodings
+        .iter()
+        .enumerate()
+        .map(|(i, encoding)| {
+            let q = (10 - 2 * i as i32).max(1);
+            format!("{};q={}.{}", encoding.as_str(), q / 10, q % 10)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Decode a response body encoded with one of the accepted encodings
+///
+/// Returns the response unchanged when it has no `Content-Encoding`.
+/// An encoding outside `accepted` is an error. The decoded response drops
+/// the `Content-Encoding` and `Content-Length` headers, which described the
+/// encoded body, and keeps the encoded body for `Response::raw_bytes`.
+pub(crate) async fn decode_response(
+    response: Response,
+    accepted: &[Encoding],
+    cookie_jar: Arc<CookieJar>,
+) -> Result<Response> {
+    let mut codings = Vec::new();
+    for value in response.headers().get_all(CONTENT_ENCODING) {
+        let value = value
+            .to_str()
+            .map_err(|_| Error::compression("Invalid Content-Encoding header"))?;
+        for token in value.split(',').filter(|t| !t.trim().is_empty()) {
+            let encoding = Encoding::parse(token)
+                .filter(|e| accepted.contains(e) || *e == Encoding::Identity)
+                .ok_or_else(|| {
+                    Error::compression(format!("Unexpected Content-Encoding: {}", token.trim()))
+                })?;
+            codings.push(encoding);
+        }
+    }
+    if codings.is_empty() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let version = response.version();
+    let mut headers = response.headers().clone();
+    let url = response.url().clone();
+    let tls_info = response.tls_info().cloned();
+    let raw_body = response.bytes().await?;
+
+    // Codings are listed in the order they were applied, so undo them in reverse
+    let mut body = raw_body.clone();
+    for encoding in codings.iter().rev() {
+        body = decode(*encoding, &body)?;
+    }
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+
+    Ok(Response::from_parts(status, version, headers, url, body, cookie_jar)?
+        .with_tls_info(tls_info)
+        .with_raw_body(Some(raw_body)))
+}
+
+#[cfg(feature = "compression")]
+pub(crate) fn decode(encoding: Encoding, body: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    let result = match encoding {
+        Encoding::Identity => return Ok(body.to_vec()),
+        Encoding::Gzip => flate2::read::GzDecoder::new(body).read_to_end(&mut decoded),
+        Encoding::Deflate => flate2::read::ZlibDecoder::new(body).read_to_end(&mut decoded),
+        Encoding::Brotli => brotli::Decompressor::new(body, 4096).read_to_end(&mut decoded),
+    };
+    result.map_err(|e| Error::compression(format!("Failed to decode {} body: {}", encoding.as_str(), e)))?;
+    Ok(decoded)
+}
+
+#[cfg(not(feature = "compression"))]
+pub(crate) fn decode(encoding: Encoding, body: &[u8]) -> Result<Vec<u8>> {
+    match encoding {
+        Encoding::Identity => Ok(body.to_vec()),
+        other => Err(Error::compression(format!(
+            "Decoding {} requires the compression feature",
+            other.as_str()
+        ))),
+    }
+}
+
+/// Incremental decoder undoing a body's content codings chunk by chunk
+///
+/// Decoder state is kept between chunks, so chunk boundaries can fall
+/// anywhere in the encoded data.
+pub(crate) struct StreamDecoder {
+    /// One stage per coding, in the order they are undone
+    stages: Vec<DecodeStage>,
+}
+
+impl StreamDecoder {
+    /// Create a decoder for the codings in a response's `Content-Encoding`
+    pub(crate) fn for_headers(headers: &http::HeaderMap) -> Result<Self> {
+        let mut stages = Vec::new();
+        for value in headers.get_all(CONTENT_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| Error::compression("Invalid Content-Encoding header"))?;
+            for token in value.split(',').filter(|t| !t.trim().is_empty()) {
+                let encoding = Encoding::parse(token).ok_or_else(|| {
+                    Error::compression(format!("Unexpected Content-Encoding: {}", token.trim()))
+                })?;
+                if encoding != Encoding::Identity {
+                    stages.push(DecodeStage::new(encoding)?);
+                }
+            }
+        }
+        stages.reverse();
+        Ok(Self { stages })
+    }
+
+    /// Decode the next chunk, returning whatever output it completes
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        let mut data = chunk.to_vec();
+        for stage in &mut self.stages {
+            if data.is_empty() {
+                break;
+            }
+            data = stage.write(&data)?;
+        }
+        Ok(data)
+    }
+
+    /// Flush the decoders at the end of the body
+    ///
+    /// Fails if the body ended partway through the encoded data.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for stage in &mut self.stages {
+            if !data.is_empty() {
+                data = stage.write(&data)?;
+            }
+            data.extend(stage.finish()?);
+        }
+        Ok(data)
+    }
+}
+
+/// Push-based decoder for a single coding
+struct DecodeStage {
+    encoding: Encoding,
+    #[cfg(feature = "compression")]
+    writer: DecodeWriter,
+}
+
+#[cfg(feature = "compression")]
+enum DecodeWriter {
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+    Deflate(flate2::write::ZlibDecoder<Vec<u8>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+#[cfg(feature = "compression")]
+impl DecodeStage {
+    fn new(encoding: Encoding) -> Result<Self> {
+        let writer = match encoding {
+            Encoding::Gzip => DecodeWriter::Gzip(flate2::write::GzDecoder::new(Vec::new())),
+            Encoding::Deflate => DecodeWriter::Deflate(flate2::write::ZlibDecoder::new(Vec::new())),
+            Encoding::Brotli => DecodeWriter::Brotli(Box::new(brotli::DecompressorWriter::new(Vec::new(), 4096))),
+            Encoding::Identity => unreachable!("identity needs no decoder"),
+        };
+        Ok(Self { encoding, writer })
+    }
+
+    fn write(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Write;
+
+        let result = match &mut self.writer {
+            DecodeWriter::Gzip(decoder) => decoder.write_all(input).map(|_| std::mem::take(decoder.get_mut())),
+            DecodeWriter::Deflate(decoder) => decoder.write_all(input).map(|_| std::mem::take(decoder.get_mut())),
+            DecodeWriter::Brotli(decoder) => decoder.write_all(input).map(|_| std::mem::take(decoder.get_mut())),
+        };
+        result.map_err(|e| self.error(e))
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let result = match &mut self.writer {
+            DecodeWriter::Gzip(decoder) => decoder.try_finish().map(|_| std::mem::take(decoder.get_mut())),
+            DecodeWriter::Deflate(decoder) => decoder.try_finish().map(|_| std::mem::take(decoder.get_mut())),
+            DecodeWriter::Brotli(decoder) => decoder.close().map(|_| std::mem::take(decoder.get_mut())),
+        };
+        result.map_err(|e| self.error(e))
+    }
+
+    fn error(&self, e: std::io::Error) -> Error {
+        Error::compression(format!("Failed to decode {} body: {}", self.encoding.as_str(), e))
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+impl DecodeStage {
+    fn new(encoding: Encoding) -> Result<Self> {
+        Err(Error::compression(format!(
+            "Decoding {} requires the compression feature",
+            encoding.as_str()
+        )))
+    }
+
+    fn write(&mut self, _input: &[u8]) -> Result<Vec<u8>> {
+        unreachable!("no {} decoder without the compression feature", self.encoding.as_str())
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        unreachable!("no {} decoder without the compression feature", self.encoding.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_encoding_header_order() {
+        assert_eq!(
+            accept_encoding_header(&[Encoding::Brotli, Encoding::Gzip]),
+            "br;q=1.0, gzip;q=0.8"
+        );
+        assert_eq!(
+            accept_encoding_header(&[Encoding::Gzip, Encoding::Deflate, Encoding::Brotli, Encoding::Identity]),
+            "gzip;q=1.0, deflate;q=0.8, br;q=0.6, identity;q=0.4"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_decoding() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        // A gzip member holding "hello world"
+        let gzipped: Vec<u8> = vec![
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85, 0x11, 0x4a, 0x0d, 0x0b,
+            0x00, 0x00, 0x00,
+        ];
+
+        let server = MockServer::start().await;
+        Mock::given(path("/gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_bytes(gzipped.clone()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/br"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "br")
+                    .set_body_bytes(vec![0u8; 4]),
+            )
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::builder()
+            .accept_encodings(&[Encoding::Gzip, Encoding::Deflate])
+            .build();
+        let get = |route: &str| {
+            client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send()
+        };
+
+        #[cfg(feature = "compression")]
+        {
+            let response = get("/gzip").await.unwrap();
+            assert!(response.header("content-encoding").is_none());
+            assert_eq!(response.text().await.unwrap(), "hello world");
+
+            // The wire bytes are still there, gzip magic and all
+            let raw = get("/gzip").await.unwrap().raw_bytes().await.unwrap();
+            assert_eq!(&raw[..2], &[0x1f, 0x8b]);
+            assert_eq!(raw, gzipped);
+            assert_eq!(get("/gzip").await.unwrap().bytes().await.unwrap(), b"hello world");
+        }
+
+        let err = get("/br").await.unwrap_err();
+        assert!(matches!(err, Error::Compression(_)));
+        assert!(err.to_string().contains("Unexpected Content-Encoding: br"));
+
+        let requests = server.received_requests().await.unwrap();
+        let accept_encoding: Vec<&str> = requests[0]
+            .headers
+            .get(&"accept-encoding".into())
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str())
+            .collect();
+        assert_eq!(accept_encoding.join(", "), "gzip;q=1.0, deflate;q=0.8");
+    }
+}
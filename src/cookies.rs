@@ -1,5 +1,5 @@
 use std::sync::Mutex;
-use cookie::{Cookie, CookieJar as CookieJarInner};
+use cookie::{Cookie, SameSite};
 use url::Url;
 
 use crate::error::{Error, Result};
@@ -7,24 +7,80 @@ use crate::error::{Error, Result};
 /// Cookie jar for managing cookies across requests
 ///
 /// This provides a thread-safe way to store and retrieve cookies
-/// for HTTP requests and responses.
+/// for HTTP requests and responses. Cookies are identified by their
+/// name, domain and path, so the same name may be stored for several
+/// domains at once.
 #[derive(Debug)]
 pub struct CookieJar {
-    inner: Mutex<CookieJarInner>,
+    inner: Mutex<Vec<Cookie<'static>>>,
+}
+
+/// Check whether two cookies share the same name, domain and path
+fn same_identity(a: &Cookie<'_>, b: &Cookie<'_>) -> bool {
+    a.name() == b.name()
+        && domain_eq(a.domain(), b.domain())
+        && a.path().unwrap_or("/") == b.path().unwrap_or("/")
+}
+
+/// Compare optional cookie domains case-insensitively
+fn domain_eq(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Check whether a request path falls under a cookie path (RFC 6265 §5.1.4)
+fn path_matches(cookie_path: &str, request_path: &str) -> bool {
+    if request_path == cookie_path {
+        return true;
+    }
+    request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+/// Get the host that identifies a URL's site
+///
+/// Domain names are lowercased and lose any trailing dot; IP addresses
+/// are kept as addresses.
+fn site_of(url: &Url) -> Option<url::Host<String>> {
+    match url.host()? {
+        url::Host::Domain(domain) => Some(url::Host::Domain(domain.trim_end_matches('.').to_ascii_lowercase())),
+        host => Some(host.to_owned()),
+    }
+}
+
+/// Check whether two URLs belong to the same site (scheme and host)
+///
+/// Without a public suffix list, a registrable domain such as
+/// `example.co.uk` can't be told apart from a shared suffix such as `co.uk`
+/// or `github.io`, so hosts have to match exactly. `www.example.com` and
+/// `example.com` therefore count as different sites, which can only
+/// withhold cookies, never leak them.
+fn same_site(a: &Url, b: &Url) -> bool {
+    match (site_of(a), site_of(b)) {
+        (Some(ha), Some(hb)) => a.scheme() == b.scheme() && ha == hb,
+        _ => false,
+    }
 }
 
 impl CookieJar {
     /// Create a new empty cookie jar
     pub fn new() -> Self {
         Self {
-            inner: Mutex::new(CookieJarInner::new()),
+            inner: Mutex::new(Vec::new()),
         }
     }
 
-    /// Add a cookie to the jar
+    /// Add a cookie to the jar, replacing any cookie with the same name, domain and path
     pub fn add(&self, cookie: Cookie<'static>) {
         if let Ok(mut jar) = self.inner.lock() {
-            jar.add(cookie);
+            if let Some(existing) = jar.iter_mut().find(|c| same_identity(c, &cookie)) {
+                *existing = cookie;
+            } else {
+                jar.push(cookie);
+            }
         }
     }
 
@@ -37,11 +93,17 @@ impl CookieJar {
     }
 
     /// Add a cookie from a response header
-    pub fn add_cookie_from_response(&self, cookie_str: &str, _url: &Url) {
-        if let Ok(mut jar) = self.inner.lock() {
-            if let Ok(cookie) = Cookie::parse(cookie_str) {
-                jar.add(cookie.into_owned());
+    ///
+    /// Cookies without a `Domain` attribute are scoped to the host of the
+    /// response URL.
+    pub fn add_cookie_from_response(&self, cookie_str: &str, url: &Url) {
+        if let Ok(mut cookie) = Cookie::parse(cookie_str) {
+            if cookie.domain().is_none() {
+                if let Some(host) = url.host_str() {
+                    cookie.set_domain(host.to_string());
+                }
             }
+            self.add(cookie.into_owned());
         }
     }
 
@@ -66,6 +128,38 @@ impl CookieJar {
         }
     }
 
+    /// Get the cookies to send with a request, honoring `Secure`, `Path` and `SameSite`
+    ///
+    /// `origin` is the URL of the page or request that initiated this one;
+    /// `None` means the request was made directly and is treated as same-site.
+    /// `is_navigation` marks a top-level GET, the only kind of cross-site
+    /// request that receives `SameSite=Lax` cookies. `Strict` cookies are
+    /// never sent cross-site.
+    pub fn cookies_for_request(
+        &self,
+        url: &Url,
+        is_navigation: bool,
+        origin: Option<&Url>,
+    ) -> Vec<Cookie<'static>> {
+        let cross_site = origin.map(|origin| !same_site(origin, url)).unwrap_or(false);
+
+        self.cookies_for_url(url)
+            .into_iter()
+            .filter(|cookie| !cookie.secure().unwrap_or(false) || url.scheme() == "https")
+            .filter(|cookie| path_matches(cookie.path().unwrap_or("/"), url.path()))
+            .filter(|cookie| {
+                if !cross_site {
+                    return true;
+                }
+                match cookie.same_site() {
+                    Some(SameSite::Strict) => false,
+                    Some(SameSite::Lax) => is_navigation,
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
     /// Get all cookies as a string for a request header
     pub fn cookies_string_for_url(&self, url: &Url) -> String {
         let cookies = self.cookies_for_url(url);
@@ -76,26 +170,38 @@ impl CookieJar {
             .join("; ")
     }
 
-    /// Remove a cookie by name
+    /// Remove all cookies with the given name, regardless of domain and path
     pub fn remove(&self, name: &str) {
         if let Ok(mut jar) = self.inner.lock() {
-            let name_owned = name.to_string();
-            jar.remove(Cookie::build(name_owned).build());
+            jar.retain(|c| c.name() != name);
+        }
+    }
+
+    /// Remove the cookie with the given name, domain and path
+    ///
+    /// Other cookies sharing the name but stored for a different domain or
+    /// path are left intact. A cookie without a `Path` attribute matches `/`.
+    pub fn remove_for(&self, name: &str, domain: &str, path: &str) {
+        let target = Cookie::build((name.to_string(), ""))
+            .domain(domain.to_string())
+            .path(path.to_string())
+            .build();
+        if let Ok(mut jar) = self.inner.lock() {
+            jar.retain(|c| !same_identity(c, &target));
         }
     }
 
     /// Clear all cookies
     pub fn clear(&self) {
-        if let Ok(_jar) = self.inner.lock() {
-            // Note: CookieJar doesn't have a clear method in this version
-            // jar.clear();
+        if let Ok(mut jar) = self.inner.lock() {
+            jar.clear();
         }
     }
 
     /// Get the number of cookies in the jar
     pub fn len(&self) -> usize {
         if let Ok(jar) = self.inner.lock() {
-            jar.iter().count()
+            jar.len()
         } else {
             0
         }
@@ -109,7 +215,7 @@ impl CookieJar {
     /// Get all cookies
     pub fn all_cookies(&self) -> Vec<Cookie<'static>> {
         if let Ok(jar) = self.inner.lock() {
-            jar.iter().cloned().collect()
+            jar.clone()
         } else {
             Vec::new()
         }
@@ -118,16 +224,18 @@ impl CookieJar {
     /// Check if a cookie exists
     pub fn has_cookie(&self, name: &str) -> bool {
         if let Ok(jar) = self.inner.lock() {
-            jar.get(name).is_some()
+            jar.iter().any(|c| c.name() == name)
         } else {
             false
         }
     }
 
     /// Get a specific cookie by name
+    ///
+    /// If the name is stored for several domains, the first one added is returned.
     pub fn get_cookie(&self, name: &str) -> Option<Cookie<'static>> {
         if let Ok(jar) = self.inner.lock() {
-            jar.get(name).cloned()
+            jar.iter().find(|c| c.name() == name).cloned()
         } else {
             None
         }
@@ -331,6 +439,98 @@ mod tests {
         assert!(cookie.http_only().unwrap());
     }
 
+    #[test]
+    fn test_cookie_jar_clear() {
+        let jar = CookieJar::new();
+        jar.add_simple("a", "1");
+        jar.add_simple("b", "2");
+        jar.add_simple("c", "3");
+        assert_eq!(jar.len(), 3);
+
+        jar.clear();
+        assert!(jar.is_empty());
+        assert_eq!(jar.len(), 0);
+        assert!(!jar.has_cookie("a"));
+    }
+
+    #[test]
+    fn test_cookie_jar_remove_for() {
+        let jar = CookieJar::new();
+        jar.add(CookieBuilder::new("id", "a").domain("a.example.com").path("/").build());
+        jar.add(CookieBuilder::new("id", "b").domain("b.example.com").path("/").build());
+        assert_eq!(jar.len(), 2);
+
+        jar.remove_for("id", "a.example.com", "/");
+        assert_eq!(jar.len(), 1);
+
+        let remaining = jar.get_cookie("id").unwrap();
+        assert_eq!(remaining.domain(), Some("b.example.com"));
+        assert_eq!(remaining.value(), "b");
+    }
+
+    #[test]
+    fn test_cookies_for_request_same_site() {
+        let jar = CookieJar::new();
+        jar.add(
+            CookieBuilder::new("strict", "1")
+                .domain("example.com")
+                .same_site(cookie::SameSite::Strict)
+                .build(),
+        );
+        jar.add(
+            CookieBuilder::new("lax", "2")
+                .domain("example.com")
+                .same_site(cookie::SameSite::Lax)
+                .build(),
+        );
+
+        let url = Url::parse("https://example.com/").unwrap();
+        let same = Url::parse("https://EXAMPLE.com./page").unwrap();
+        let other = Url::parse("https://evil.test/").unwrap();
+
+        let names = |cookies: Vec<Cookie<'static>>| {
+            cookies.iter().map(|c| c.name().to_string()).collect::<Vec<_>>()
+        };
+
+        // Same-site requests get both cookies
+        assert_eq!(names(jar.cookies_for_request(&url, false, Some(&same))), ["strict", "lax"]);
+
+        // Cross-site navigation omits Strict but keeps Lax
+        assert_eq!(names(jar.cookies_for_request(&url, true, Some(&other))), ["lax"]);
+
+        // Cross-site subresource requests omit both
+        assert!(jar.cookies_for_request(&url, false, Some(&other)).is_empty());
+
+        // Subdomains count as other sites, so Strict cookies stay put
+        let sub = Url::parse("https://www.example.com/page").unwrap();
+        assert_eq!(names(jar.cookies_for_request(&url, true, Some(&sub))), ["lax"]);
+
+        let site = |a: &str, b: &str| same_site(&Url::parse(a).unwrap(), &Url::parse(b).unwrap());
+        assert!(site("http://10.0.1.5/", "http://10.0.1.5:8080/x"));
+        assert!(!site("http://10.0.1.5/", "http://192.168.1.5/"));
+        assert!(!site("http://[::1]/", "http://[::2]/"));
+        assert!(!site("https://a.co.uk/", "https://b.co.uk/"));
+        assert!(!site("https://x.github.io/", "https://y.github.io/"));
+        assert!(!site("http://example.com/", "https://example.com/"));
+    }
+
+    #[test]
+    fn test_cookies_for_request_secure_and_path() {
+        let jar = CookieJar::new();
+        jar.add(CookieBuilder::new("sec", "1").domain("example.com").secure(true).build());
+        jar.add(CookieBuilder::new("api", "2").domain("example.com").path("/api").build());
+
+        let http = Url::parse("http://example.com/api/users").unwrap();
+        let cookies = jar.cookies_for_request(&http, true, None);
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].name(), "api");
+
+        let https = Url::parse("https://example.com/apis").unwrap();
+        let cookies = jar.cookies_for_request(&https, true, None);
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].name(), "sec");
+    }
+
     #[test]
     fn test_cookie_jar_clone() {
         let jar = CookieJar::new();
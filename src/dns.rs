@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+
+/// Address family tried first when a host has both IPv4 and IPv6 addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn matches(self, addr: &SocketAddr) -> bool {
+        match self {
+            IpFamily::V4 => addr.is_ipv4(),
+            IpFamily::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+/// System resolver that lists addresses of the preferred family first
+///
+/// hyper's connector tries addresses of the first address's family, and
+/// only starts on the other family if those haven't connected after its
+/// fallback delay, so the order decides which family wins a healthy race.
+#[derive(Debug)]
+pub(crate) struct PreferFamily(pub(crate) IpFamily);
+
+impl Resolve for PreferFamily {
+    fn resolve(&self, name: Name) -> Resolving {
+        let family = self.0;
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addrs: Addrs = Box::new(order(addrs.collect(), family).into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Move addresses of `family` to the front, keeping the resolver's order otherwise
+fn order(mut addrs: Vec<SocketAddr>, family: IpFamily) -> Vec<SocketAddr> {
+    addrs.sort_by_key(|addr| !family.matches(addr));
+    addrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order() {
+        let addrs: Vec<SocketAddr> = ["[::1]:0", "127.0.0.1:0", "[::2]:0", "127.0.0.2:0"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let ordered = |family| {
+            order(addrs.clone(), family).iter().map(|a| a.ip().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(ordered(IpFamily::V4), ["127.0.0.1", "127.0.0.2", "::1", "::2"]);
+        assert_eq!(ordered(IpFamily::V6), ["::1", "::2", "127.0.0.1", "127.0.0.2"]);
+    }
+}
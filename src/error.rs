@@ -66,6 +66,10 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    /// Error status responses (4xx and 5xx)
+    #[error(transparent)]
+    Status(#[from] StatusError),
+
     /// Generic error with custom message
     #[error("{0}")]
     Custom(String),
@@ -156,6 +160,14 @@ impl Error {
         matches!(self, Error::Auth(_))
     }
 
+    /// Get the status error if this is an error status response
+    pub fn as_status_error(&self) -> Option<&StatusError> {
+        match self {
+            Error::Status(e) => Some(e),
+            _ => None,
+        }
+    }
+
     /// Get the underlying reqwest error if this is a network error
     pub fn as_network_error(&self) -> Option<&reqwest::Error> {
         match self {
@@ -263,9 +275,3 @@ impl StatusError {
         matches!(self, StatusError::Server { .. })
     }
 }
-
-impl From<StatusError> for Error {
-    fn from(err: StatusError) -> Self {
-        Error::Custom(err.to_string())
-    }
-} 
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::io::{self, Write};
+use serde_json::Value;
+
+const RESET: &str = "\x1b[0m";
+const KEY: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[94m";
+const BOOL: &str = "\x1b[95m";
+const NULL: &str = "\x1b[91m";
+const SYNTAX: &str = "\x1b[37m";
+
+/// Options controlling colorized output
+#[derive(Debug, Clone)]
+pub struct ColorOptions {
+    /// Emit ANSI color codes
+    pub color: bool,
+    /// Disable color when the `NO_COLOR` environment variable is set
+    pub respect_no_color: bool,
+}
+
+impl ColorOptions {
+    /// Create options with color enabled and `NO_COLOR` respected
+    pub fn new() -> Self {
+        Self {
+            color: true,
+            respect_no_color: true,
+        }
+    }
+
+    /// Create options that never emit color
+    pub fn plain() -> Self {
+        Self {
+            color: false,
+            respect_no_color: true,
+        }
+    }
+
+    /// Enable or disable color, e.g. based on whether output is a terminal
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set whether the `NO_COLOR` environment variable disables color
+    pub fn respect_no_color(mut self, respect: bool) -> Self {
+        self.respect_no_color = respect;
+        self
+    }
+
+    /// Check whether color codes will be emitted
+    pub fn is_enabled(&self) -> bool {
+        let no_color = self.respect_no_color
+            && std::env::var_os("NO_COLOR").map(|v| !v.is_empty()).unwrap_or(false);
+        self.color && !no_color
+    }
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write a JSON value pretty-printed and colorized by data type
+///
+/// Keys are yellow, strings green, numbers bright blue, booleans bright
+/// magenta, null bright red and punctuation white. Object keys are sorted.
+/// With color disabled the output matches `response::pretty_json`.
+pub fn colorize_json<W: Write>(value: &Value, writer: &mut W, options: &ColorOptions) -> io::Result<()> {
+    let painter = Painter {
+        enabled: options.is_enabled(),
+    };
+    write_value(value, writer, &painter, 0)
+}
+
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    fn paint<W: Write>(&self, writer: &mut W, color: &str, text: &str) -> io::Result<()> {
+        if self.enabled {
+            write!(writer, "{}{}{}", color, text, RESET)
+        } else {
+            write!(writer, "{}", text)
+        }
+    }
+}
+
+fn write_value<W: Write>(value: &Value, writer: &mut W, painter: &Painter, indent: usize) -> io::Result<()> {
+    match value {
+        Value::Object(map) if map.is_empty() => painter.paint(writer, SYNTAX, "{}"),
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+
+            painter.paint(writer, SYNTAX, "{")?;
+            for (i, (key, val)) in entries.iter().enumerate() {
+                if i > 0 {
+                    painter.paint(writer, SYNTAX, ",")?;
+                }
+                write!(writer, "\n{}", "  ".repeat(indent + 1))?;
+                painter.paint(writer, KEY, &Value::String(key.to_string()).to_string())?;
+                painter.paint(writer, SYNTAX, ":")?;
+                write!(writer, " ")?;
+                write_value(val, writer, painter, indent + 1)?;
+            }
+            write!(writer, "\n{}", "  ".repeat(indent))?;
+            painter.paint(writer, SYNTAX, "}")
+        }
+        Value::Array(items) if items.is_empty() => painter.paint(writer, SYNTAX, "[]"),
+        Value::Array(items) => {
+            painter.paint(writer, SYNTAX, "[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    painter.paint(writer, SYNTAX, ",")?;
+                }
+                write!(writer, "\n{}", "  ".repeat(indent + 1))?;
+                write_value(item, writer, painter, indent + 1)?;
+            }
+            write!(writer, "\n{}", "  ".repeat(indent))?;
+            painter.paint(writer, SYNTAX, "]")
+        }
+        Value::String(_) => painter.paint(writer, STRING, &value.to_string()),
+        Value::Number(n) => painter.paint(writer, NUMBER, &n.to_string()),
+        Value::Bool(b) => painter.paint(writer, BOOL, &b.to_string()),
+        Value::Null => painter.paint(writer, NULL, "null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(value: &Value, options: &ColorOptions) -> String {
+        let mut out = Vec::new();
+        colorize_json(value, &mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_colorize_json() {
+        let value = serde_json::json!({"name": "ada", "age": 36, "admin": true, "tags": [], "manager": null});
+
+        let colored = render(&value, &ColorOptions::new().respect_no_color(false));
+        assert!(colored.contains("\x1b[33m\"name\"\x1b[0m"));
+        assert!(colored.contains("\x1b[32m\"ada\"\x1b[0m"));
+        assert!(colored.contains("\x1b[94m36\x1b[0m"));
+        assert!(colored.contains("\x1b[95mtrue\x1b[0m"));
+        assert!(colored.contains("\x1b[91mnull\x1b[0m"));
+
+        let plain = render(&value, &ColorOptions::plain());
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(plain, crate::response::pretty_json(&value));
+    }
+}
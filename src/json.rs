@@ -0,0 +1,216 @@
+//! JSON helpers
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{Error, Result};
+
+/// Content type of a JSON Merge Patch document
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Content type of a JSON Patch document
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Apply a JSON Merge Patch (RFC 7386) to `target` in place
+///
+/// Members of an object patch are merged recursively and a `null` member
+/// removes the key from `target`. Any other patch, arrays included,
+/// replaces `target` outright.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Builder for a JSON Patch (RFC 6902) document
+///
+/// Operations serialize, in the order they were added, to the array of
+/// operation objects the RFC describes. Every path is checked to be a
+/// well-formed JSON Pointer (RFC 6901) when it's added.
+///
+/// # Examples
+///
+/// ```rust
+/// use rusttpx::json::JsonPatch;
+///
+/// let patch = JsonPatch::new()
+///     .test("/version", 3)?
+///     .replace("/title", "Hello!")?
+///     .remove("/draft")?;
+/// assert_eq!(patch.len(), 3);
+/// # Ok::<(), rusttpx::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct JsonPatch {
+    operations: Vec<Value>,
+}
+
+impl JsonPatch {
+    /// Create an empty patch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `value` at `path`
+    pub fn add<T: Serialize>(self, path: &str, value: T) -> Result<Self> {
+        let value = serde_json::to_value(value)?;
+        self.push(json!({"op": "add", "path": pointer(path)?, "value": value}))
+    }
+
+    /// Remove the value at `path`
+    pub fn remove(self, path: &str) -> Result<Self> {
+        self.push(json!({"op": "remove", "path": pointer(path)?}))
+    }
+
+    /// Replace the value at `path` with `value`
+    pub fn replace<T: Serialize>(self, path: &str, value: T) -> Result<Self> {
+        let value = serde_json::to_value(value)?;
+        self.push(json!({"op": "replace", "path": pointer(path)?, "value": value}))
+    }
+
+    /// Move the value at `from` to `path`
+    ///
+    /// This is the RFC's `move` operation, which is a Rust keyword.
+    pub fn move_from(self, from: &str, path: &str) -> Result<Self> {
+        self.push(json!({"op": "move", "from": pointer(from)?, "path": pointer(path)?}))
+    }
+
+    /// Copy the value at `from` to `path`
+    pub fn copy(self, from: &str, path: &str) -> Result<Self> {
+        self.push(json!({"op": "copy", "from": pointer(from)?, "path": pointer(path)?}))
+    }
+
+    /// Require the value at `path` to equal `value` for the patch to apply
+    pub fn test<T: Serialize>(self, path: &str, value: T) -> Result<Self> {
+        let value = serde_json::to_value(value)?;
+        self.push(json!({"op": "test", "path": pointer(path)?, "value": value}))
+    }
+
+    /// Get the operations added so far
+    pub fn operations(&self) -> &[Value] {
+        &self.operations
+    }
+
+    /// Get the number of operations
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Check if the patch has no operations
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    fn push(mut self, operation: Value) -> Result<Self> {
+        self.operations.push(operation);
+        Ok(self)
+    }
+}
+
+/// Check that `path` is a JSON Pointer: empty, or `/`-separated tokens in
+/// which `~` only appears as the escapes `~0` and `~1`
+fn pointer(path: &str) -> Result<&str> {
+    let invalid = |reason: &str| Error::invalid_request(format!("Invalid JSON Pointer {:?}: {}", path, reason));
+    if !path.is_empty() && !path.starts_with('/') {
+        return Err(invalid("must be empty or start with '/'"));
+    }
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' && !matches!(chars.next(), Some('0') | Some('1')) {
+            return Err(invalid("'~' must be followed by '0' or '1'"));
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch() {
+        let mut target = json!({
+            "title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        });
+        merge_patch(&mut target, &json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null},
+            "tags": ["example"]
+        }));
+        assert_eq!(target, json!({
+            "title": "Hello!",
+            "author": {"givenName": "John"},
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        }));
+
+        // Cases from RFC 7386, Appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (json!({"a": "b", "b": "c"}), json!({"a": null}), json!({"b": "c"})),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}}), json!({"a": {"b": "d"}})),
+            (json!({"a": [{"b": "c"}]}), json!({"a": [1]}), json!({"a": [1]})),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"e": null}), json!({"a": 1}), json!({"e": null, "a": 1})),
+            (json!([1, 2]), json!({"a": "b", "c": null}), json!({"a": "b"})),
+            (json!({}), json!({"a": {"bb": {"ccc": null}}}), json!({"a": {"bb": {}}})),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge_patch(&mut target, &patch);
+            assert_eq!(target, expected, "patch {}", patch);
+        }
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let patch = JsonPatch::new()
+            .test("/version", 3)
+            .and_then(|p| p.add("/tags/-", "new"))
+            .and_then(|p| p.remove("/draft"))
+            .and_then(|p| p.replace("/title", json!({"text": "Hello!"})))
+            .and_then(|p| p.move_from("/a~1b", "/c"))
+            .and_then(|p| p.copy("/author", "/editors/0"))
+            .and_then(|p| p.add("", json!({})))
+            .unwrap();
+        assert_eq!(patch.len(), 7);
+        assert_eq!(serde_json::to_value(&patch).unwrap(), json!([
+            {"op": "test", "path": "/version", "value": 3},
+            {"op": "add", "path": "/tags/-", "value": "new"},
+            {"op": "remove", "path": "/draft"},
+            {"op": "replace", "path": "/title", "value": {"text": "Hello!"}},
+            {"op": "move", "from": "/a~1b", "path": "/c"},
+            {"op": "copy", "from": "/author", "path": "/editors/0"},
+            {"op": "add", "path": "", "value": {}}
+        ]));
+
+        for path in ["title", "/a~2b", "/trailing~"] {
+            let err = JsonPatch::new().remove(path).unwrap_err();
+            assert!(err.to_string().contains(path), "{}", err);
+        }
+        assert!(JsonPatch::new().copy("nope", "/x").is_err());
+        assert!(JsonPatch::new().is_empty());
+    }
+}
@@ -42,12 +42,34 @@ pub mod error;
 pub mod transport;
 pub mod middleware;
 pub mod auth;
+#[cfg(feature = "ntlm")]
+pub mod ntlm;
 pub mod cookies;
 pub mod multipart;
 pub mod streaming;
 pub mod timeout;
 pub mod proxy;
 pub mod tls;
+mod ocsp;
+mod pac;
+mod dns;
+pub mod circuit;
+pub mod retry;
+pub mod redirect;
+mod throttle;
+pub mod trace;
+pub mod cache;
+pub mod compression;
+pub mod format;
+pub mod url_utils;
+pub mod json;
+pub mod pipeline;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(test)]
+pub(crate) mod tls_fixtures;
 
 // Re-export main types for convenience
 pub use client::{Client, ClientBuilder};
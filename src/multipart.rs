@@ -1,5 +1,8 @@
 use std::path::PathBuf;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use futures::{Stream, StreamExt};
+use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use http::HeaderMap;
 use reqwest::multipart::Form;
 use serde::Serialize;
 
@@ -14,6 +17,8 @@ pub struct MultipartBuilder {
     form: Form,
     fields: HashMap<String, String>,
     files: HashMap<String, FileData>,
+    parts: Vec<EncodedPart>,
+    boundary: String,
 }
 
 /// File data for multipart uploads
@@ -64,10 +69,14 @@ impl FileData {
 impl MultipartBuilder {
     /// Create a new multipart builder
     pub fn new() -> Self {
+        let form = Form::new();
+        let boundary = form.boundary().to_string();
         Self {
-            form: Form::new(),
+            form,
             fields: HashMap::new(),
             files: HashMap::new(),
+            parts: Vec::new(),
+            boundary,
         }
     }
 
@@ -76,6 +85,7 @@ impl MultipartBuilder {
         let name_owned = name.to_string();
         let value_owned = value.to_string();
         self.form = self.form.text(name_owned.clone(), value_owned.clone());
+        self.parts.push(EncodedPart::text(name, value));
         self.fields.insert(name_owned, value_owned);
         self
     }
@@ -95,6 +105,7 @@ impl MultipartBuilder {
         let name_owned = name.to_string();
         match std::fs::read(&file_data.path) {
             Ok(data) => {
+                self.parts.push(EncodedPart::file(name, &data, &filename, None));
                 let part = reqwest::multipart::Part::bytes(data)
                     .file_name(filename);
                 self.form = self.form.part(name_owned, part);
@@ -123,6 +134,7 @@ impl MultipartBuilder {
         let filename_owned = filename.to_string();
         match std::fs::read(&file_data.path) {
             Ok(data) => {
+                self.parts.push(EncodedPart::file(name, &data, filename, None));
                 let part = reqwest::multipart::Part::bytes(data)
                     .file_name(filename_owned);
                 self.form = self.form.part(name_owned, part);
@@ -150,10 +162,17 @@ impl MultipartBuilder {
         let name_owned = name.to_string();
         match std::fs::read(&file_data.path) {
             Ok(data) => {
+                let encoded = EncodedPart {
+                    name: name.to_string(),
+                    filename: None,
+                    content_type: Some(content_type.to_string()),
+                    data: data.clone(),
+                };
                 let part = reqwest::multipart::Part::bytes(data)
                     .mime_str(content_type)
                     .map_err(|e| Error::multipart(format!("Invalid content type: {}", e)))?;
                 self.form = self.form.part(name_owned, part);
+                self.parts.push(encoded);
             }
             Err(_) => {
                 return Err(Error::multipart(format!("Failed to read file: {}", path.display())));
@@ -165,6 +184,7 @@ impl MultipartBuilder {
 
     /// Add bytes as a file
     pub fn bytes(mut self, name: &str, data: Vec<u8>, filename: &str) -> Self {
+        self.parts.push(EncodedPart::file(name, &data, filename, None));
         let name_owned = name.to_string();
         let filename_owned = filename.to_string();
         let part = reqwest::multipart::Part::bytes(data)
@@ -177,11 +197,13 @@ impl MultipartBuilder {
     pub fn bytes_with_content_type(mut self, name: &str, data: Vec<u8>, filename: &str, content_type: &str) -> Result<Self> {
         let name_owned = name.to_string();
         let filename_owned = filename.to_string();
+        let encoded = EncodedPart::file(name, &data, filename, Some(content_type));
         let part = reqwest::multipart::Part::bytes(data)
             .file_name(filename_owned)
             .mime_str(content_type)
             .map_err(|e| Error::multipart(format!("Invalid content type: {}", e)))?;
         self.form = self.form.part(name_owned, part);
+        self.parts.push(encoded);
         Ok(self)
     }
 
@@ -199,6 +221,7 @@ impl MultipartBuilder {
             let name_owned = name.clone();
             let value_owned = value.clone();
             self.form = self.form.text(name_owned, value_owned);
+            self.parts.push(EncodedPart::text(&name, &value));
             self.fields.insert(name, value);
         }
         self
@@ -252,6 +275,29 @@ impl MultipartBuilder {
         let boundary = self.form.boundary().to_string();
         (self.form, boundary)
     }
+
+    /// Use a fixed boundary instead of a random one
+    ///
+    /// The boundary must follow RFC 2046: 1 to 70 letters, digits or
+    /// `'()+_,-./:=?` and spaces, not ending in a space. It applies to
+    /// `content_type` and `to_bytes`, which encode the form the same way
+    /// every time; the reqwest `Form` from `build` always gets a random
+    /// boundary.
+    pub fn boundary(mut self, boundary: &str) -> Result<Self> {
+        validate_boundary(boundary)?;
+        self.boundary = boundary.to_string();
+        Ok(self)
+    }
+
+    /// Get the `Content-Type` header value for `to_bytes`
+    pub fn content_type(&self) -> String {
+        form_data_content_type(&self.boundary)
+    }
+
+    /// Encode the form as a request body, parts in the order they were added
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_parts(&self.boundary, &self.parts)
+    }
 }
 
 impl Default for MultipartBuilder {
@@ -281,6 +327,45 @@ impl MultipartForm {
         }
     }
 
+    /// Use a fixed boundary instead of a random one
+    ///
+    /// The boundary must follow the RFC 2046 rules described on
+    /// `MultipartBuilder::boundary`.
+    pub fn with_boundary(mut self, boundary: &str) -> Result<Self> {
+        validate_boundary(boundary)?;
+        self.boundary = boundary.to_string();
+        Ok(self)
+    }
+
+    /// Get the `Content-Type` header value for `to_bytes`
+    pub fn content_type(&self) -> String {
+        form_data_content_type(&self.boundary)
+    }
+
+    /// Encode the form as a request body using its boundary
+    ///
+    /// Fields come first, then files, each sorted by name, so the same form
+    /// always encodes to the same bytes. Files are read from disk.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort();
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut parts: Vec<EncodedPart> = fields
+            .into_iter()
+            .map(|(name, value)| EncodedPart::text(name, value))
+            .collect();
+        for (name, file_data) in files {
+            let data = std::fs::read(&file_data.path).map_err(|_| {
+                Error::multipart(format!("Failed to read file: {}", file_data.path.display()))
+            })?;
+            let filename = file_data.get_filename();
+            parts.push(EncodedPart::file(name, &data, &filename, file_data.content_type.as_deref()));
+        }
+        Ok(encode_parts(&self.boundary, &parts))
+    }
+
     /// Add a text field
     pub fn add_field(mut self, name: &str, value: &str) -> Self {
         self.fields.insert(name.to_string(), value.to_string());
@@ -430,6 +515,306 @@ fn generate_boundary() -> String {
     format!("----WebKitFormBoundary{}", hex::encode(bytes))
 }
 
+/// Check a boundary against the RFC 2046 grammar
+fn validate_boundary(boundary: &str) -> Result<()> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "'()+_,-./:=? ".contains(c);
+    if boundary.is_empty() || boundary.len() > 70 {
+        return Err(Error::multipart(format!(
+            "Boundary must be 1 to 70 characters, got {}",
+            boundary.len()
+        )));
+    }
+    if let Some(c) = boundary.chars().find(|c| !allowed(*c)) {
+        return Err(Error::multipart(format!("Invalid character {:?} in boundary", c)));
+    }
+    if boundary.ends_with(' ') {
+        return Err(Error::multipart("Boundary can't end with a space"));
+    }
+    Ok(())
+}
+
+/// Build a `multipart/form-data` content type for `boundary`
+///
+/// Boundaries may hold characters, such as spaces, `/` or `=`, that aren't
+/// allowed in a bare parameter value, so those are quoted.
+fn form_data_content_type(boundary: &str) -> String {
+    let is_token = boundary
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+    if is_token {
+        format!("multipart/form-data; boundary={}", boundary)
+    } else {
+        format!("multipart/form-data; boundary=\"{}\"", boundary)
+    }
+}
+
+/// A form part kept in memory so the form can be encoded by hand
+#[derive(Debug, Clone)]
+struct EncodedPart {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+impl EncodedPart {
+    fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            filename: None,
+            content_type: None,
+            data: value.as_bytes().to_vec(),
+        }
+    }
+
+    fn file(name: &str, data: &[u8], filename: &str, content_type: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            filename: Some(filename.to_string()),
+            content_type: content_type.map(str::to_string),
+            data: data.to_vec(),
+        }
+    }
+}
+
+/// Encode parts as `multipart/form-data`
+///
+/// Quotes and line breaks in names and filenames are percent-encoded, as
+/// browsers do.
+fn encode_parts(boundary: &str, parts: &[EncodedPart]) -> Vec<u8> {
+    let quote = |value: &str| value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A");
+    let mut body = Vec::new();
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", quote(&part.name));
+        if let Some(filename) = &part.filename {
+            disposition.push_str(&format!("; filename=\"{}\"", quote(filename)));
+        }
+        body.extend_from_slice(disposition.as_bytes());
+        if let Some(content_type) = &part.content_type {
+            body.extend_from_slice(format!("\r\nContent-Type: {}", content_type).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(&part.data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// One part of a multipart response
+#[derive(Debug, Clone)]
+pub struct Part {
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl Part {
+    /// Get the part's headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get a header as a string, if present and valid
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    /// Get the part's `Content-Type`
+    pub fn content_type(&self) -> Option<&str> {
+        self.header(CONTENT_TYPE.as_str())
+    }
+
+    /// Get the part's body
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Take the part's body
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Get the part's body as UTF-8 text
+    pub fn text(&self) -> Result<String> {
+        String::from_utf8(self.body.clone()).map_err(|e| Error::response_parse(e.to_string()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SplitState {
+    Preamble,
+    AfterDelimiter,
+    Headers,
+    Body,
+    Done,
+}
+
+/// Incremental splitter for a multipart body
+///
+/// Bytes are fed in chunks of any size. Each part comes out once its
+/// closing delimiter has been read; until then only the part being read
+/// and a delimiter's worth of lookahead are held. The preamble and the
+/// epilogue after `--boundary--` are skipped.
+#[derive(Debug)]
+pub(crate) struct PartSplitter {
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    state: SplitState,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl PartSplitter {
+    pub(crate) fn new(boundary: &str) -> Self {
+        Self {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first delimiter may open the body, without a line break before it
+            buffer: b"\r\n".to_vec(),
+            state: SplitState::Preamble,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Feed the next chunk, pushing every part it completes onto `out`
+    pub(crate) fn feed(&mut self, chunk: &[u8], out: &mut Vec<Part>) -> Result<()> {
+        self.buffer.extend_from_slice(chunk);
+        loop {
+            match self.state {
+                SplitState::Preamble | SplitState::Body => match find(&self.buffer, &self.delimiter) {
+                    Some(at) => {
+                        if self.state == SplitState::Body {
+                            self.body.extend_from_slice(&self.buffer[..at]);
+                            out.push(Part {
+                                headers: std::mem::take(&mut self.headers),
+                                body: std::mem::take(&mut self.body),
+                            });
+                        }
+                        self.buffer.drain(..at + self.delimiter.len());
+                        self.state = SplitState::AfterDelimiter;
+                    }
+                    None => {
+                        // Keep enough to recognize a delimiter split across chunks
+                        let keep = self.buffer.len().min(self.delimiter.len() - 1);
+                        let done: Vec<u8> = self.buffer.drain(..self.buffer.len() - keep).collect();
+                        if self.state == SplitState::Body {
+                            self.body.extend(done);
+                        }
+                        return Ok(());
+                    }
+                },
+                SplitState::AfterDelimiter => {
+                    if self.buffer.starts_with(b"--") {
+                        self.state = SplitState::Done;
+                        continue;
+                    }
+                    // Transport padding may follow the delimiter on its line
+                    let Some(at) = find(&self.buffer, b"\r\n") else {
+                        return Ok(());
+                    };
+                    if self.buffer[..at].iter().any(|b| *b != b' ' && *b != b'\t') {
+                        return Err(Error::response_parse("Invalid multipart boundary line"));
+                    }
+                    self.buffer.drain(..at + 2);
+                    self.state = SplitState::Headers;
+                }
+                SplitState::Headers => {
+                    // A part without headers starts with the blank line
+                    let end = if self.buffer.starts_with(b"\r\n") {
+                        0
+                    } else {
+                        match find(&self.buffer, b"\r\n\r\n") {
+                            Some(at) => at + 2,
+                            None => return Ok(()),
+                        }
+                    };
+                    let head: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                    self.headers = parse_part_headers(&head[..end])?;
+                    self.state = SplitState::Body;
+                }
+                SplitState::Done => {
+                    self.buffer.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Check that the body ended with the closing delimiter
+    pub(crate) fn finish(&self) -> Result<()> {
+        match self.state {
+            SplitState::Done => Ok(()),
+            _ => Err(Error::response_parse("Multipart body ended before the closing boundary")),
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn parse_part_headers(head: &[u8]) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for line in head.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let colon = line
+            .iter()
+            .position(|b| *b == b':')
+            .ok_or_else(|| Error::response_parse("Invalid multipart part header"))?;
+        let name = HeaderName::from_bytes(&line[..colon])
+            .map_err(|e| Error::response_parse(format!("Invalid multipart part header: {}", e)))?;
+        let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii())
+            .map_err(|e| Error::response_parse(format!("Invalid multipart part header: {}", e)))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Split a streamed multipart body into its parts
+///
+/// Without a boundary, the stream yields a single `Error::ResponseParse`.
+pub(crate) fn response_parts<S>(stream: S, boundary: Option<String>) -> impl Stream<Item = Result<Part>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    let (splitter, failed) = match boundary {
+        Some(boundary) => (Some(PartSplitter::new(&boundary)), None),
+        None => (None, Some(Error::response_parse("Response isn't multipart or has no boundary"))),
+    };
+    let ended = splitter.is_none();
+    let state = (Box::pin(stream), splitter, VecDeque::<Part>::new(), failed, ended);
+    futures::stream::unfold(state, |(mut stream, mut splitter, mut ready, mut failed, mut ended)| async move {
+        loop {
+            // Parts completed before an error still come first
+            if let Some(part) = ready.pop_front() {
+                return Some((Ok(part), (stream, splitter, ready, failed, ended)));
+            }
+            if let Some(e) = failed.take() {
+                return Some((Err(e), (stream, splitter, ready, None, true)));
+            }
+            let parts = splitter.as_mut().filter(|_| !ended)?;
+            let mut complete = Vec::new();
+            let fed = match stream.next().await {
+                Some(Ok(chunk)) => parts.feed(&chunk, &mut complete),
+                Some(Err(e)) => Err(e),
+                None => {
+                    ended = true;
+                    parts.finish()
+                }
+            };
+            ready.extend(complete);
+            if let Err(e) = fed {
+                failed = Some(e);
+                ended = true;
+            }
+        }
+    })
+}
+
 /// Multipart utilities
 pub mod utils {
     use super::*;
@@ -595,4 +980,88 @@ mod tests {
         assert!(boundary2.starts_with("----WebKitFormBoundary"));
         assert_ne!(boundary1, boundary2);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_fixed_boundary() {
+        let builder = MultipartBuilder::new()
+            .boundary("fixed-boundary_123")
+            .unwrap()
+            .text("title", "Report")
+            .bytes_with_content_type("data", b"{\"a\":1}".to_vec(), "data.json", "application/json")
+            .unwrap();
+        assert_eq!(builder.content_type(), "multipart/form-data; boundary=fixed-boundary_123");
+        let expected = concat!(
+            "--fixed-boundary_123\r\n",
+            "Content-Disposition: form-data; name=\"title\"\r\n",
+            "\r\n",
+            "Report\r\n",
+            "--fixed-boundary_123\r\n",
+            "Content-Disposition: form-data; name=\"data\"; filename=\"data.json\"\r\n",
+            "Content-Type: application/json\r\n",
+            "\r\n",
+            "{\"a\":1}\r\n",
+            "--fixed-boundary_123--\r\n",
+        );
+        assert_eq!(String::from_utf8(builder.to_bytes()).unwrap(), expected);
+        assert_eq!(builder.to_bytes(), builder.to_bytes());
+
+        let form = MultipartForm::new()
+            .with_boundary("simple boundary")
+            .unwrap()
+            .add_field("b", "2")
+            .add_field("a", "1");
+        assert_eq!(form.content_type(), "multipart/form-data; boundary=\"simple boundary\"");
+        let body = String::from_utf8(form.to_bytes().unwrap()).unwrap();
+        assert!(body.starts_with("--simple boundary\r\nContent-Disposition: form-data; name=\"a\""));
+        assert!(body.ends_with("\r\n2\r\n--simple boundary--\r\n"));
+
+        let builder = MultipartBuilder::new().boundary("a/b=c:d").unwrap();
+        assert_eq!(builder.content_type(), "multipart/form-data; boundary=\"a/b=c:d\"");
+        let builder = MultipartBuilder::new().boundary("it's+ok.").unwrap();
+        assert_eq!(builder.content_type(), "multipart/form-data; boundary=it's+ok.");
+
+        for invalid in ["", "ends in space ", "semi;colon", "quote\"", &"x".repeat(71)] {
+            assert!(MultipartBuilder::new().boundary(invalid).is_err(), "{:?}", invalid);
+            assert!(MultipartForm::new().with_boundary(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_parts() {
+        let body = concat!(
+            "preamble to ignore\r\n",
+            "--batch_1\r\n",
+            "Content-Type: application/json\r\n",
+            "Content-ID: <a>\r\n",
+            "\r\n",
+            "{\"id\": 1}\r\n",
+            "--batch_1  \r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "second part\r\nwith --batch lines\r\n",
+            "--batch_1--\r\n",
+            "epilogue\r\n",
+        );
+        // Three bytes at a time, so delimiters and headers straddle chunks
+        let chunks: Vec<Result<Vec<u8>>> = body.as_bytes().chunks(3).map(|c| Ok(c.to_vec())).collect();
+        let parts: Vec<Part> = response_parts(futures::stream::iter(chunks), Some("batch_1".to_string()))
+            .map(|part| part.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].content_type(), Some("application/json"));
+        assert_eq!(parts[0].header("content-id"), Some("<a>"));
+        assert_eq!(parts[0].body(), b"{\"id\": 1}");
+        assert_eq!(parts[1].content_type(), Some("text/plain"));
+        assert_eq!(parts[1].text().unwrap(), "second part\r\nwith --batch lines");
+
+        // A body cut off before the closing delimiter
+        let cut = body.find("Content-Type: text").unwrap();
+        let truncated = futures::stream::iter(vec![Ok(body.as_bytes()[..cut].to_vec())]);
+        let results: Vec<_> = response_parts(truncated, Some("batch_1".to_string())).collect().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results.last(), Some(Err(Error::ResponseParse(_)))));
+    }
+}
@@ -0,0 +1,391 @@
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use hmac::{Hmac, Mac};
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use md4::{Digest, Md4};
+use reqwest::{Request as ReqwestRequest, Response as ReqwestResponse};
+
+use crate::error::{Error, Result};
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+/// Flags offered in the negotiate message
+const NEGOTIATE_FLAGS: u32 = NEGOTIATE_UNICODE
+    | NEGOTIATE_OEM
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+/// `MsvAvTimestamp` attribute id in the challenge's target info
+const AV_TIMESTAMP: u16 = 7;
+
+/// Seconds between 1601-01-01 (the FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+type HmacMd5 = Hmac<md5::Md5>;
+
+/// Credentials for NTLM authentication
+#[derive(Debug, Clone)]
+pub struct NtlmCredentials {
+    /// User name
+    pub username: String,
+    /// Password
+    pub password: String,
+    /// Windows domain, may be empty
+    pub domain: String,
+}
+
+impl NtlmCredentials {
+    /// Create new NTLM credentials
+    pub fn new(username: &str, password: &str, domain: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+            domain: domain.to_string(),
+        }
+    }
+}
+
+/// A parsed NTLM challenge (Type 2) message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// Flags the server agreed to
+    pub flags: u32,
+    /// The server's 8-byte nonce
+    pub server_challenge: [u8; 8],
+    /// AV pairs describing the server, echoed in the NTLMv2 response
+    pub target_info: Vec<u8>,
+}
+
+impl Challenge {
+    /// Parse a raw challenge message
+    pub fn parse(message: &[u8]) -> Result<Self> {
+        let invalid = || Error::auth("Invalid NTLM challenge message");
+        if message.len() < 32 || &message[..8] != SIGNATURE || read_u32(message, 8) != Some(2) {
+            return Err(invalid());
+        }
+
+        let flags = read_u32(message, 20).ok_or_else(invalid)?;
+        let mut server_challenge = [0u8; 8];
+        server_challenge.copy_from_slice(&message[24..32]);
+        let target_info = if message.len() >= 48 {
+            read_field(message, 40).ok_or_else(invalid)?.to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            flags,
+            server_challenge,
+            target_info,
+        })
+    }
+
+    /// Parse the challenge from a `WWW-Authenticate: NTLM <base64>` value
+    pub fn from_header(value: &str) -> Result<Self> {
+        let encoded = value
+            .trim()
+            .strip_prefix("NTLM ")
+            .ok_or_else(|| Error::auth("WWW-Authenticate has no NTLM challenge"))?;
+        let message = BASE64
+            .decode(encoded.trim())
+            .map_err(|e| Error::auth(format!("Invalid NTLM challenge encoding: {}", e)))?;
+        Self::parse(&message)
+    }
+
+    /// Get the server timestamp from the target info, if present
+    fn timestamp(&self) -> Option<u64> {
+        let mut info = self.target_info.as_slice();
+        while info.len() >= 4 {
+            let id = u16::from_le_bytes([info[0], info[1]]);
+            let len = u16::from_le_bytes([info[2], info[3]]) as usize;
+            let value = info.get(4..4 + len)?;
+            if id == AV_TIMESTAMP && len == 8 {
+                return Some(u64::from_le_bytes(value.try_into().ok()?));
+            }
+            info = &info[4 + len..];
+        }
+        None
+    }
+}
+
+/// Build the negotiate (Type 1) message
+pub fn negotiate_message() -> Vec<u8> {
+    let mut message = Vec::with_capacity(32);
+    message.extend_from_slice(SIGNATURE);
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // Empty domain and workstation fields
+    message.extend_from_slice(&[0u8; 16]);
+    message
+}
+
+/// Build the authenticate (Type 3) message answering a challenge with NTLMv2
+///
+/// `timestamp` is a Windows FILETIME; the server's own timestamp from the
+/// challenge is used instead when it sends one.
+pub fn authenticate_message(
+    credentials: &NtlmCredentials,
+    challenge: &Challenge,
+    client_challenge: [u8; 8],
+    timestamp: u64,
+) -> Vec<u8> {
+    let timestamp = challenge.timestamp().unwrap_or(timestamp);
+    let response_key = ntowfv2(credentials);
+
+    let mut blob = vec![0x01, 0x01, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.to_le_bytes());
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0u8; 4]);
+    blob.extend_from_slice(&challenge.target_info);
+    blob.extend_from_slice(&[0u8; 4]);
+
+    let mut nt_response = hmac_md5(&response_key, &[&challenge.server_challenge, &blob]).to_vec();
+    nt_response.extend_from_slice(&blob);
+    let mut lm_response =
+        hmac_md5(&response_key, &[&challenge.server_challenge, &client_challenge]).to_vec();
+    lm_response.extend_from_slice(&client_challenge);
+
+    let domain = utf16le(&credentials.domain);
+    let user = utf16le(&credentials.username);
+    let workstation = Vec::new();
+    let session_key = Vec::new();
+    let payloads = [&domain, &user, &workstation, &lm_response, &nt_response, &session_key];
+
+    const HEADER_LEN: usize = 64;
+    let mut message = Vec::with_capacity(HEADER_LEN + payloads.iter().map(|p| p.len()).sum::<usize>());
+    message.extend_from_slice(SIGNATURE);
+    message.extend_from_slice(&3u32.to_le_bytes());
+
+    // Field headers in wire order: LM, NT, domain, user, workstation, session key
+    let mut offsets = [0usize; 6];
+    let mut offset = HEADER_LEN;
+    for (i, payload) in payloads.iter().enumerate() {
+        offsets[i] = offset;
+        offset += payload.len();
+    }
+    for i in [3, 4, 0, 1, 2, 5] {
+        let len = payloads[i].len() as u16;
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&(offsets[i] as u32).to_le_bytes());
+    }
+    let flags = challenge.flags & NEGOTIATE_FLAGS | NEGOTIATE_UNICODE | NEGOTIATE_NTLM;
+    message.extend_from_slice(&flags.to_le_bytes());
+
+    for payload in payloads {
+        message.extend_from_slice(payload);
+    }
+    message
+}
+
+/// Compute the NTLMv2 response key from the password hash and identity
+fn ntowfv2(credentials: &NtlmCredentials) -> [u8; 16] {
+    let nt_hash = Md4::digest(utf16le(&credentials.password));
+    let identity = utf16le(&format!("{}{}", credentials.username.to_uppercase(), credentials.domain));
+    hmac_md5(&nt_hash, &[&identity])
+}
+
+fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = HmacMd5::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+}
+
+fn read_u32(message: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(message.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Read the payload described by the length/offset field header at `at`
+fn read_field(message: &[u8], at: usize) -> Option<&[u8]> {
+    let len = u16::from_le_bytes(message.get(at..at + 2)?.try_into().ok()?) as usize;
+    let offset = read_u32(message, at + 4)? as usize;
+    message.get(offset..offset.checked_add(len)?)
+}
+
+fn current_filetime() -> u64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000 + since_epoch.subsec_nanos() as u64 / 100
+}
+
+fn offers_ntlm(headers: &HeaderMap) -> bool {
+    headers.get_all(WWW_AUTHENTICATE).iter().any(|value| {
+        value
+            .to_str()
+            .map(|v| v.trim().eq_ignore_ascii_case("NTLM") || v.trim().starts_with("NTLM "))
+            .unwrap_or(false)
+    })
+}
+
+fn set_authorization(request: &mut ReqwestRequest, message: &[u8]) {
+    let value = format!("NTLM {}", BASE64.encode(message));
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+}
+
+/// Send a request, answering an NTLM 401 with the negotiate/authenticate handshake
+///
+/// NTLM authenticates the connection rather than the request, so the 401
+/// bodies are drained to return the connection to the pool, where the next
+/// request to the same host picks it up again.
+pub(crate) async fn handshake<F, Fut>(
+    credentials: Option<&NtlmCredentials>,
+    request: ReqwestRequest,
+    send: F,
+) -> Result<ReqwestResponse>
+where
+    F: Fn(ReqwestRequest) -> Fut,
+    Fut: Future<Output = Result<ReqwestResponse>>,
+{
+    let credentials = match credentials {
+        Some(credentials) => credentials,
+        None => return send(request).await,
+    };
+    let retries = request.try_clone().zip(request.try_clone());
+    let response = send(request).await?;
+    let (mut negotiate, mut authenticate) = match retries {
+        Some(retries) if response.status() == StatusCode::UNAUTHORIZED && offers_ntlm(response.headers()) => retries,
+        _ => return Ok(response),
+    };
+    let _ = response.bytes().await;
+
+    set_authorization(&mut negotiate, &negotiate_message());
+    let response = send(negotiate).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+    let challenge = response
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| value.trim().starts_with("NTLM "))
+        .map(Challenge::from_header);
+    let challenge = match challenge {
+        Some(challenge) => challenge?,
+        None => return Ok(response),
+    };
+    let _ = response.bytes().await;
+
+    let message = authenticate_message(credentials, &challenge, rand::random(), current_filetime());
+    set_authorization(&mut authenticate, &message);
+    send(authenticate).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CHALLENGE_MESSAGE from the MS-NLMP NTLMv2 example (section 4.2.4.3)
+    const SPEC_CHALLENGE: &str = "4e544c4d53535000020000000c000c003800000033828ae2\
+        0123456789abcdef00000000000000002400240044000000\
+        060070170000000f53006500720076006500720002000c00\
+        44006f006d00610069006e0001000c005300650072007600\
+        6500720000000000";
+
+    fn spec_credentials() -> NtlmCredentials {
+        NtlmCredentials::new("User", "Password", "Domain")
+    }
+
+    fn spec_challenge() -> Challenge {
+        Challenge::parse(&hex::decode(SPEC_CHALLENGE).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate_message() {
+        let encoded = BASE64.encode(negotiate_message());
+        assert!(encoded.starts_with("TlRMTVNTUAABAAAA"));
+        let decoded = BASE64.decode(&encoded).unwrap();
+        assert_eq!(decoded.len(), 32);
+        assert_eq!(read_u32(&decoded, 12), Some(NEGOTIATE_FLAGS));
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let challenge = spec_challenge();
+        assert_eq!(challenge.flags, 0xe28a_8233);
+        assert_eq!(challenge.server_challenge, [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        assert_eq!(challenge.target_info.len(), 36);
+        assert_eq!(challenge.timestamp(), None);
+
+        let header = format!("NTLM {}", BASE64.encode(hex::decode(SPEC_CHALLENGE).unwrap()));
+        assert_eq!(Challenge::from_header(&header).unwrap(), challenge);
+        assert!(Challenge::from_header("NTLM bm90IG50bG0=").is_err());
+        assert!(Challenge::from_header("Basic realm=x").is_err());
+    }
+
+    #[test]
+    fn test_ntlmv2_spec_vectors() {
+        assert_eq!(
+            hex::encode(ntowfv2(&spec_credentials())),
+            "0c868a403bfd7a93a3001ef22ef02e3f"
+        );
+
+        let message = authenticate_message(&spec_credentials(), &spec_challenge(), [0xaa; 8], 0);
+        let encoded = BASE64.encode(&message);
+        assert!(encoded.starts_with("TlRMTVNTUAADAAAA"));
+        let message = BASE64.decode(encoded).unwrap();
+
+        let lm = read_field(&message, 12).unwrap();
+        assert_eq!(hex::encode(lm), "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa");
+        let nt = read_field(&message, 20).unwrap();
+        assert_eq!(hex::encode(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+        assert_eq!(read_field(&message, 28).unwrap(), utf16le("Domain").as_slice());
+        assert_eq!(read_field(&message, 36).unwrap(), utf16le("User").as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_client_handshake() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::header_regex;
+
+        let challenge = BASE64.encode(hex::decode(SPEC_CHALLENGE).unwrap());
+        let server = MockServer::start().await;
+        Mock::given(header_regex("authorization", "^NTLM TlRMTVNTUAADAAAA"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("welcome"))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(header_regex("authorization", "^NTLM TlRMTVNTUAABAAAA"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .insert_header("www-authenticate", format!("NTLM {}", challenge).as_str()),
+            )
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(401).insert_header("www-authenticate", "NTLM"))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::builder()
+            .auth_config(crate::auth::AuthConfig::ntlm("User", "Password", "Domain"))
+            .build();
+        let url: url::Url = format!("{}/intranet", server.uri()).parse().unwrap();
+        assert_eq!(client.get(url).send_text().await.unwrap(), "welcome");
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+}
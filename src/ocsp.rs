@@ -0,0 +1,651 @@
+//! Verification of stapled OCSP responses
+//!
+//! With `TlsConfig::require_ocsp_stapling`, connections go through a rustls
+//! configuration whose verifier checks the certificate chain as usual, then
+//! requires the OCSP response the server stapled to the handshake to be
+//! signed by the certificate's issuer (or a responder it delegated to),
+//! current, and to report the certificate as good.
+
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ring::digest;
+use ring::signature::{self, VerificationAlgorithm};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, PrivateKey, RootCertStore, ServerName};
+
+use crate::error::{Error, Result};
+use crate::tls::{Alpn, TlsConfig};
+
+/// Clock skew allowed when checking a response's validity period
+const ALLOWED_SKEW_SECS: i64 = 300;
+
+const OID_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+const OID_OCSP_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const OID_RSA_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+const OID_RSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_RSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_RSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_ENUMERATED: u8 = 0x0a;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+
+/// Build the rustls configuration used when stapling is required
+///
+/// A configuration that can't be built still fails closed: its verifier
+/// rejects every server with the reason.
+pub(crate) fn client_config(tls_config: &TlsConfig, alpn: Alpn) -> ClientConfig {
+    build_client_config(tls_config, alpn).unwrap_or_else(|e| {
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(Unusable(e.to_string())))
+            .with_no_client_auth()
+    })
+}
+
+fn build_client_config(tls_config: &TlsConfig, alpn: Alpn) -> Result<ClientConfig> {
+    // reqwest certificates and identities can't be read back, so refuse
+    // them rather than connect without them
+    if !tls_config.root_certs.is_empty() {
+        return Err(Error::tls(
+            "Roots from add_root_cert can't be used with require_ocsp_stapling; use ca_cert_path",
+        ));
+    }
+    if tls_config.client_cert.is_some() {
+        return Err(Error::tls(
+            "Client certificates given as an Identity can't be used with require_ocsp_stapling; \
+             use client_cert_path and client_key_path",
+        ));
+    }
+    let mut roots = Vec::new();
+    if let Some(path) = &tls_config.ca_cert_path {
+        roots.extend(read_pem_certs(path)?);
+    }
+    if let Ok(native) = rustls_native_certs::load_native_certs() {
+        roots.extend(native.iter().map(|cert| cert.as_ref().to_vec()));
+    }
+    let mut root_store = RootCertStore::empty();
+    root_store.add_parsable_certificates(&roots);
+
+    let mut versions = Vec::new();
+    if tls_config.tls_version.tls_1_3 {
+        versions.push(&rustls::version::TLS13);
+    }
+    if tls_config.tls_version.tls_1_2 {
+        versions.push(&rustls::version::TLS12);
+    }
+    let verifier = StaplingVerifier {
+        webpki: WebPkiVerifier::new(root_store, None),
+        roots,
+    };
+    let builder = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&versions)
+        .map_err(|e| Error::tls(format!("Invalid TLS versions: {}", e)))?
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let mut config = match (&tls_config.client_cert_path, &tls_config.client_key_path) {
+        (Some(cert_path), Some(key_path)) => builder
+            .with_client_auth_cert(
+                read_pem_certs(cert_path)?.into_iter().map(Certificate).collect(),
+                read_pem_key(key_path)?,
+            )
+            .map_err(|e| Error::tls(format!("Invalid client certificate: {}", e)))?,
+        _ => builder.with_no_client_auth(),
+    };
+    // reqwest leaves protocol negotiation to preconfigured TLS
+    config.alpn_protocols = alpn.protocols();
+    Ok(config)
+}
+
+fn read_pem_certs(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| Error::tls(format!("Failed to read {}: {}", path.display(), e)))?;
+    rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| Error::tls(format!("Invalid certificate in {}: {}", path.display(), e)))
+}
+
+fn read_pem_key(path: &Path) -> Result<PrivateKey> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| Error::tls(format!("Failed to read {}: {}", path.display(), e)))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| Error::tls(format!("Invalid key in {}: {}", path.display(), e)))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| Error::tls(format!("No private key in {}", path.display())))
+}
+
+/// Verifies the chain, then the stapled OCSP response
+struct StaplingVerifier {
+    webpki: WebPkiVerifier,
+    /// DER of the trusted roots, to find the issuer of certificates they sign directly
+    roots: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for StaplingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .webpki
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        if ocsp_response.is_empty() {
+            return Err(rustls::Error::General("Server did not staple an OCSP response".into()));
+        }
+        let leaf = Cert::parse(&end_entity.0).map_err(|_| CertificateError::BadEncoding)?;
+        let issuer = intermediates
+            .iter()
+            .map(|cert| cert.0.as_slice())
+            .chain(self.roots.iter().map(Vec::as_slice))
+            .filter_map(|der| Cert::parse(der).ok())
+            .find(|cert| cert.subject == leaf.issuer)
+            .ok_or(CertificateError::UnknownIssuer)?;
+        check_response(ocsp_response, &leaf, &issuer, unix_time(now))?;
+        Ok(verified)
+    }
+}
+
+/// Rejects every server, for configurations that couldn't be built
+struct Unusable(String);
+
+impl ServerCertVerifier for Unusable {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Err(rustls::Error::General(self.0.clone()))
+    }
+}
+
+/// Check that an OCSP response vouches for `leaf` at `now`
+fn check_response(response: &[u8], leaf: &Cert, issuer: &Cert, now: i64) -> std::result::Result<(), rustls::Error> {
+    let malformed = |_| rustls::Error::General("Malformed OCSP response".into());
+    let response = BasicResponse::parse(response).map_err(malformed)?;
+
+    // The issuer signs responses itself or certifies a responder to do so
+    if !issuer.verifies(response.signature_algorithm, response.tbs, response.signature) {
+        let responder = response
+            .certs
+            .iter()
+            .filter_map(|der| Cert::parse(der).ok())
+            .find(|cert| {
+                cert.issuer == issuer.subject
+                    && cert.is_ocsp_signer()
+                    && issuer.verifies(cert.signature_algorithm, cert.tbs, cert.signature)
+            });
+        let signed = responder
+            .is_some_and(|cert| cert.verifies(response.signature_algorithm, response.tbs, response.signature));
+        if !signed {
+            return Err(rustls::Error::General("OCSP response is not signed by the issuer".into()));
+        }
+    }
+
+    let single = response
+        .responses
+        .iter()
+        .find(|single| single.is_for(leaf, issuer))
+        .ok_or_else(|| rustls::Error::General("OCSP response does not cover the certificate".into()))?;
+    if single.this_update > now + ALLOWED_SKEW_SECS {
+        return Err(rustls::Error::General("OCSP response is not yet valid".into()));
+    }
+    match single.next_update {
+        Some(next_update) if next_update + ALLOWED_SKEW_SECS >= now => {}
+        _ => return Err(rustls::Error::General("OCSP response has expired".into())),
+    }
+    match single.status {
+        Status::Good => Ok(()),
+        Status::Revoked => Err(CertificateError::Revoked.into()),
+        Status::Unknown => Err(rustls::Error::General("OCSP responder does not know the certificate".into())),
+    }
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+/// The parts of an X.509 certificate needed to check OCSP responses
+struct Cert<'a> {
+    tbs: &'a [u8],
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+    serial: &'a [u8],
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    /// The key's algorithm identifier contents: its OID and parameters
+    key_algorithm: &'a [u8],
+    key: &'a [u8],
+    extensions: Option<&'a [u8]>,
+}
+
+impl<'a> Cert<'a> {
+    fn parse(der: &'a [u8]) -> DerResult<Self> {
+        let mut cert = Reader::new(Reader::new(der).expect(TAG_SEQUENCE)?);
+        let tbs = cert.next()?;
+        let signature_algorithm = cert.expect(TAG_SEQUENCE)?;
+        let signature = bit_string(cert.expect(TAG_BIT_STRING)?)?;
+
+        let mut fields = Reader::new(tbs.contents);
+        fields.optional(0xa0)?;
+        let serial = fields.expect(TAG_INTEGER)?;
+        fields.expect(TAG_SEQUENCE)?;
+        let issuer = fields.next()?.raw;
+        fields.expect(TAG_SEQUENCE)?;
+        let subject = fields.next()?.raw;
+        let mut spki = Reader::new(fields.expect(TAG_SEQUENCE)?);
+        let key_algorithm = spki.expect(TAG_SEQUENCE)?;
+        let key = bit_string(spki.expect(TAG_BIT_STRING)?)?;
+        fields.optional(0x81)?;
+        fields.optional(0x82)?;
+        let extensions = match fields.optional(0xa3)? {
+            Some(extensions) => Some(Reader::new(extensions).expect(TAG_SEQUENCE)?),
+            None => None,
+        };
+
+        Ok(Self {
+            tbs: tbs.raw,
+            signature_algorithm,
+            signature,
+            serial,
+            issuer,
+            subject,
+            key_algorithm,
+            key,
+            extensions,
+        })
+    }
+
+    /// Check that this certificate's key made `signature` over `message`
+    fn verifies(&self, algorithm: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        let Ok(algorithm) = Reader::new(algorithm).expect(TAG_OID) else {
+            return false;
+        };
+        let curve = self.curve();
+        let verifier: &dyn VerificationAlgorithm = match (algorithm, curve) {
+            (OID_ECDSA_SHA256, Some(OID_P256)) => &signature::ECDSA_P256_SHA256_ASN1,
+            (OID_ECDSA_SHA384, Some(OID_P256)) => &signature::ECDSA_P256_SHA384_ASN1,
+            (OID_ECDSA_SHA256, Some(OID_P384)) => &signature::ECDSA_P384_SHA256_ASN1,
+            (OID_ECDSA_SHA384, Some(OID_P384)) => &signature::ECDSA_P384_SHA384_ASN1,
+            (OID_RSA_SHA1, None) => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+            (OID_RSA_SHA256, None) => &signature::RSA_PKCS1_2048_8192_SHA256,
+            (OID_RSA_SHA384, None) => &signature::RSA_PKCS1_2048_8192_SHA384,
+            (OID_RSA_SHA512, None) => &signature::RSA_PKCS1_2048_8192_SHA512,
+            _ => return false,
+        };
+        signature::UnparsedPublicKey::new(verifier, self.key)
+            .verify(message, signature)
+            .is_ok()
+    }
+
+    /// Get the named curve of an EC key
+    fn curve(&self) -> Option<&'a [u8]> {
+        let mut algorithm = Reader::new(self.key_algorithm);
+        if algorithm.expect(TAG_OID).ok()? != OID_EC_PUBLIC_KEY {
+            return None;
+        }
+        algorithm.expect(TAG_OID).ok()
+    }
+
+    /// Check for the OCSP signing extended key usage
+    fn is_ocsp_signer(&self) -> bool {
+        let Some(extensions) = self.extensions else {
+            return false;
+        };
+        let mut extensions = Reader::new(extensions);
+        while let Ok(extension) = extensions.expect(TAG_SEQUENCE) {
+            let mut extension = Reader::new(extension);
+            if extension.expect(TAG_OID).ok() != Some(OID_EXTENDED_KEY_USAGE) {
+                continue;
+            }
+            let _critical = extension.optional(TAG_BOOLEAN);
+            let Ok(value) = extension.expect(TAG_OCTET_STRING) else {
+                return false;
+            };
+            let Ok(usages) = Reader::new(value).expect(TAG_SEQUENCE) else {
+                return false;
+            };
+            let mut usages = Reader::new(usages);
+            while let Ok(usage) = usages.expect(TAG_OID) {
+                if usage == OID_OCSP_SIGNING {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// A decoded `BasicOCSPResponse`
+struct BasicResponse<'a> {
+    tbs: &'a [u8],
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+    responses: Vec<SingleResponse<'a>>,
+    certs: Vec<&'a [u8]>,
+}
+
+impl<'a> BasicResponse<'a> {
+    fn parse(der: &'a [u8]) -> DerResult<Self> {
+        let mut response = Reader::new(Reader::new(der).expect(TAG_SEQUENCE)?);
+        // successful(0); other statuses carry no response
+        if response.expect(TAG_ENUMERATED)? != [0u8].as_slice() {
+            return Err(Malformed);
+        }
+        let bytes = Reader::new(response.expect(0xa0)?).expect(TAG_SEQUENCE)?;
+        let mut bytes = Reader::new(bytes);
+        if bytes.expect(TAG_OID)? != OID_OCSP_BASIC {
+            return Err(Malformed);
+        }
+        let basic = Reader::new(bytes.expect(TAG_OCTET_STRING)?).expect(TAG_SEQUENCE)?;
+
+        let mut basic = Reader::new(basic);
+        let tbs = basic.next()?;
+        let signature_algorithm = basic.expect(TAG_SEQUENCE)?;
+        let signature = bit_string(basic.expect(TAG_BIT_STRING)?)?;
+        let mut certs = Vec::new();
+        if let Some(list) = basic.optional(0xa0)? {
+            let mut list = Reader::new(Reader::new(list).expect(TAG_SEQUENCE)?);
+            while !list.is_empty() {
+                certs.push(list.next()?.raw);
+            }
+        }
+
+        let mut data = Reader::new(tbs.contents);
+        data.optional(0xa0)?;
+        // responderID: byName [1] or byKey [2]
+        data.next()?;
+        data.expect(TAG_GENERALIZED_TIME)?;
+        let mut list = Reader::new(data.expect(TAG_SEQUENCE)?);
+        let mut responses = Vec::new();
+        while !list.is_empty() {
+            responses.push(SingleResponse::parse(list.expect(TAG_SEQUENCE)?)?);
+        }
+
+        Ok(Self {
+            tbs: tbs.raw,
+            signature_algorithm,
+            signature,
+            responses,
+            certs,
+        })
+    }
+}
+
+/// The status of one certificate in an OCSP response
+struct SingleResponse<'a> {
+    hash_algorithm: &'a [u8],
+    issuer_name_hash: &'a [u8],
+    issuer_key_hash: &'a [u8],
+    serial: &'a [u8],
+    status: Status,
+    this_update: i64,
+    next_update: Option<i64>,
+}
+
+enum Status {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+impl<'a> SingleResponse<'a> {
+    fn parse(der: &'a [u8]) -> DerResult<Self> {
+        let mut single = Reader::new(der);
+        let mut cert_id = Reader::new(single.expect(TAG_SEQUENCE)?);
+        let hash_algorithm = Reader::new(cert_id.expect(TAG_SEQUENCE)?).expect(TAG_OID)?;
+        let issuer_name_hash = cert_id.expect(TAG_OCTET_STRING)?;
+        let issuer_key_hash = cert_id.expect(TAG_OCTET_STRING)?;
+        let serial = cert_id.expect(TAG_INTEGER)?;
+        let status = match single.next()?.tag {
+            0x80 => Status::Good,
+            0xa1 => Status::Revoked,
+            0x82 => Status::Unknown,
+            _ => return Err(Malformed),
+        };
+        let this_update = generalized_time(single.expect(TAG_GENERALIZED_TIME)?)?;
+        let next_update = match single.optional(0xa0)? {
+            Some(time) => Some(generalized_time(Reader::new(time).expect(TAG_GENERALIZED_TIME)?)?),
+            None => None,
+        };
+
+        Ok(Self {
+            hash_algorithm,
+            issuer_name_hash,
+            issuer_key_hash,
+            serial,
+            status,
+            this_update,
+            next_update,
+        })
+    }
+
+    /// Check whether this response's certificate ID names `leaf`
+    fn is_for(&self, leaf: &Cert, issuer: &Cert) -> bool {
+        let algorithm = match self.hash_algorithm {
+            OID_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            OID_SHA256 => &digest::SHA256,
+            _ => return false,
+        };
+        self.serial == leaf.serial
+            && self.issuer_name_hash == digest::digest(algorithm, leaf.issuer).as_ref()
+            && self.issuer_key_hash == digest::digest(algorithm, issuer.key).as_ref()
+    }
+}
+
+/// Marks DER that doesn't have the expected structure
+#[derive(Debug)]
+struct Malformed;
+
+type DerResult<T> = std::result::Result<T, Malformed>;
+
+/// A DER element
+struct Element<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    /// The whole encoding, including tag and length
+    raw: &'a [u8],
+}
+
+/// Reads consecutive DER elements
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.input.first().copied()
+    }
+
+    fn next(&mut self) -> DerResult<Element<'a>> {
+        let input = self.input;
+        let (&tag, rest) = input.split_first().ok_or(Malformed)?;
+        // High tag numbers never appear in the structures read here
+        if tag & 0x1f == 0x1f {
+            return Err(Malformed);
+        }
+        let (&first, mut rest) = rest.split_first().ok_or(Malformed)?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(Malformed);
+            }
+            let (bytes, remaining) = rest.split_at(count);
+            rest = remaining;
+            bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize)
+        };
+        if rest.len() < len {
+            return Err(Malformed);
+        }
+        let header = input.len() - rest.len();
+        self.input = &rest[len..];
+        Ok(Element {
+            tag,
+            contents: &rest[..len],
+            raw: &input[..header + len],
+        })
+    }
+
+    /// Read an element with `tag` and return its contents
+    fn expect(&mut self, tag: u8) -> DerResult<&'a [u8]> {
+        match self.peek_tag() {
+            Some(next) if next == tag => Ok(self.next()?.contents),
+            _ => Err(Malformed),
+        }
+    }
+
+    /// Read an element with `tag` if it comes next
+    fn optional(&mut self, tag: u8) -> DerResult<Option<&'a [u8]>> {
+        if self.peek_tag() == Some(tag) {
+            self.expect(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Get the bytes of a BIT STRING with no unused bits
+fn bit_string(contents: &[u8]) -> DerResult<&[u8]> {
+    match contents.split_first() {
+        Some((0, bits)) => Ok(bits),
+        _ => Err(Malformed),
+    }
+}
+
+/// Parse a `YYYYMMDDHHMMSSZ` GeneralizedTime into seconds since the epoch
+fn generalized_time(contents: &[u8]) -> DerResult<i64> {
+    let text = std::str::from_utf8(contents).map_err(|_| Malformed)?;
+    let digits = text.strip_suffix('Z').filter(|d| d.len() == 14).ok_or(Malformed)?;
+    let field = |range: std::ops::Range<usize>| digits[range].parse::<i64>().map_err(|_| Malformed);
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+
+    // Days since the epoch in the proleptic Gregorian calendar
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Ok(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls_fixtures::{fixture, ok, pem_der, TlsServer};
+
+    /// An HTTPS server for `localhost` that staples `ocsp` when given
+    fn https_server(ocsp: Option<Vec<u8>>) -> url::Url {
+        TlsServer::new("ocsp-server.crt", "ocsp-server.key").ocsp(ocsp).serve(|_| Some(ok("ok")))
+    }
+
+    #[test]
+    fn test_check_stapled_response() {
+        let leaf_der = pem_der("ocsp-server.crt");
+        let issuer_der = pem_der("ocsp-ca.crt");
+        let leaf = Cert::parse(&leaf_der).unwrap();
+        let issuer = Cert::parse(&issuer_der).unwrap();
+        let good = std::fs::read(fixture("ocsp-good.der")).unwrap();
+        let revoked = std::fs::read(fixture("ocsp-revoked.der")).unwrap();
+        let now = unix_time(SystemTime::now());
+
+        check_response(&good, &leaf, &issuer, now).unwrap();
+        assert_eq!(
+            check_response(&revoked, &leaf, &issuer, now).unwrap_err(),
+            rustls::Error::InvalidCertificate(CertificateError::Revoked)
+        );
+        // Past its nextUpdate in 2126
+        assert!(check_response(&good, &leaf, &issuer, generalized_time(b"21270101000000Z").unwrap()).is_err());
+        // Only the issuer can vouch for the certificate
+        assert!(check_response(&good, &leaf, &leaf, now).is_err());
+
+        let mut tampered = good.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(check_response(&tampered, &leaf, &issuer, now).is_err());
+        assert!(check_response(b"\x30\x03\x0a\x01\x01", &leaf, &issuer, now).is_err());
+    }
+
+    #[test]
+    fn test_client_config_follows_client_settings() {
+        let tls_config = TlsConfig::new().ca_cert_path(fixture("ocsp-ca.crt").into()).require_ocsp_stapling(true);
+        let alpn = |alpn| build_client_config(&tls_config, alpn).unwrap().alpn_protocols;
+        assert_eq!(alpn(Alpn::default()), [b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert_eq!(alpn(Alpn::Http1), [b"http/1.1".to_vec()]);
+        assert_eq!(alpn(Alpn::Http2), [b"h2".to_vec()]);
+
+        let root = reqwest::Certificate::from_pem(&std::fs::read(fixture("ocsp-ca.crt")).unwrap()).unwrap();
+        let err = build_client_config(&tls_config.clone().add_root_cert(root), Alpn::default()).unwrap_err();
+        assert!(err.to_string().contains("add_root_cert"), "{}", err);
+    }
+
+    #[test]
+    fn test_generalized_time() {
+        assert_eq!(generalized_time(b"19700101000000Z").unwrap(), 0);
+        assert_eq!(generalized_time(b"20000301123456Z").unwrap(), 951_914_096);
+        assert!(generalized_time(b"2000030112Z").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_require_ocsp_stapling() {
+        let good = std::fs::read(fixture("ocsp-good.der")).unwrap();
+        let revoked = std::fs::read(fixture("ocsp-revoked.der")).unwrap();
+        let client = crate::Client::builder()
+            .tls_config(
+                TlsConfig::new()
+                    .ca_cert_path(fixture("ocsp-ca.crt").into())
+                    .require_ocsp_stapling(true),
+            )
+            .build();
+
+        let response = client.get(https_server(Some(good))).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert!(client.get(https_server(None)).send().await.is_err());
+        assert!(client.get(https_server(Some(revoked))).send().await.is_err());
+    }
+}
@@ -0,0 +1,877 @@
+//! Evaluation of proxy auto-configuration (PAC) scripts
+//!
+//! PAC files are JavaScript, but almost all of them stick to a small
+//! subset: function declarations, `var`, `if`/`else`, `return`, string
+//! comparisons and the standard helpers like `dnsDomainIs` and `isInNet`.
+//! This module interprets that subset directly rather than pulling in a
+//! JavaScript engine. Loops, objects and regular expressions aren't
+//! supported and are reported as parse errors.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Maximum depth of nested calls to functions defined in the script
+const MAX_CALL_DEPTH: usize = 64;
+
+/// How long the DNS helpers reuse an answer
+const DNS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Number of cached hosts past which expired answers are dropped
+const DNS_CACHE_PRUNE_AT: usize = 1024;
+
+/// A parsed PAC script
+#[derive(Debug)]
+pub(crate) struct Script {
+    functions: HashMap<String, Function>,
+    globals: Vec<Stmt>,
+    dns: DnsCache,
+}
+
+impl Script {
+    /// Parse a script, which must define `FindProxyForURL`
+    pub(crate) fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let mut functions = HashMap::new();
+        let mut globals = Vec::new();
+        while !parser.at_end() {
+            if parser.eat_ident("function") {
+                let name = parser.ident()?;
+                functions.insert(name, parser.function()?);
+            } else {
+                globals.push(parser.statement()?);
+            }
+        }
+        if !functions.contains_key("FindProxyForURL") {
+            return Err(Error::proxy("PAC script doesn't define FindProxyForURL"));
+        }
+        Ok(Self {
+            functions,
+            globals,
+            dns: DnsCache::default(),
+        })
+    }
+
+    /// Call `FindProxyForURL` for `url`, returning the script's answer
+    pub(crate) fn find_proxy(&self, url: &Url) -> Result<String> {
+        let mut globals = HashMap::new();
+        let mut interpreter = Interpreter { script: self, depth: 0 };
+        interpreter.block(&self.globals, &mut globals, &HashMap::new())?;
+
+        let args = vec![
+            Value::Str(url.as_str().to_string()),
+            Value::Str(url.host_str().unwrap_or("").to_string()),
+        ];
+        let result = interpreter.call("FindProxyForURL", args, &globals)?;
+        Ok(result.to_string())
+    }
+}
+
+/// Pick the proxy to use from a `FindProxyForURL` result
+///
+/// The result lists alternatives separated by semicolons. The first
+/// `DIRECT`, `PROXY` or `HTTPS` entry wins; `None` means connecting
+/// directly. Other kinds, such as `SOCKS`, are skipped.
+pub(crate) fn select_proxy(result: &str) -> Option<Url> {
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        let scheme = match parts.next().map(|kind| kind.to_ascii_uppercase()).as_deref() {
+            Some("DIRECT") => return None,
+            Some("PROXY") | Some("HTTP") => "http",
+            Some("HTTPS") => "https",
+            _ => continue,
+        };
+        if let Some(Ok(url)) = parts.next().map(|addr| format!("{}://{}", scheme, addr).parse()) {
+            return Some(url);
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Punct(&'static str),
+}
+
+/// Punctuation, longest first so `===` isn't read as `==` and `=`
+const PUNCTUATION: &[&str] = &[
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", ",", ";", "=", "!", "+",
+    "-", "<", ">", "?", ":", ".",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        if c.is_whitespace() {
+            i += 1;
+        } else if rest.starts_with("//") {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if rest.starts_with("/*") {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(Error::proxy("Unterminated string in PAC script")),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(&other) => value.push(other),
+                            None => return Err(Error::proxy("Unterminated string in PAC script")),
+                        }
+                    }
+                    Some(&other) => value.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let number = number
+                .parse()
+                .map_err(|_| Error::proxy(format!("Invalid number '{}' in PAC script", number)))?;
+            tokens.push(Token::Num(number));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+            i += punct.len();
+            tokens.push(Token::Punct(punct));
+        } else {
+            return Err(Error::proxy(format!("Unexpected character '{}' in PAC script", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Var(Vec<(String, Option<Expr>)>),
+    Assign(String, Expr),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    Return(Option<Expr>),
+    Block(Vec<Stmt>),
+    Expr(Expr),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Method(Box<Expr>, String, Vec<Expr>),
+    Property(Box<Expr>, String),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| Error::proxy("Unexpected end of PAC script"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_ident(&mut self, name: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(ident)) if ident == name) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(Error::proxy(format!("Expected '{}' in PAC script, found {:?}", punct, self.peek())))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            other => Err(Error::proxy(format!("Expected a name in PAC script, found {:?}", other))),
+        }
+    }
+
+    fn function(&mut self) -> Result<Function> {
+        self.expect("(")?;
+        let mut params = Vec::new();
+        if !self.eat(")") {
+            loop {
+                params.push(self.ident()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        self.expect("{")?;
+        Ok(Function {
+            params,
+            body: self.block()?,
+        })
+    }
+
+    /// Statements up to and including the closing brace
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        while !self.eat("}") {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        if self.eat("{") {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if self.eat(";") {
+            return Ok(Stmt::Block(Vec::new()));
+        }
+        if self.eat_ident("if") {
+            self.expect("(")?;
+            let condition = self.expression()?;
+            self.expect(")")?;
+            let then = Box::new(self.statement()?);
+            let otherwise = if self.eat_ident("else") {
+                Some(Box::new(self.statement()?))
+            } else {
+                None
+            };
+            return Ok(Stmt::If(condition, then, otherwise));
+        }
+        let statement = if self.eat_ident("return") {
+            if matches!(self.peek(), Some(Token::Punct(";")) | Some(Token::Punct("}"))) {
+                Stmt::Return(None)
+            } else {
+                Stmt::Return(Some(self.expression()?))
+            }
+        } else if self.eat_ident("var") {
+            let mut declarations = Vec::new();
+            loop {
+                let name = self.ident()?;
+                let value = if self.eat("=") { Some(self.expression()?) } else { None };
+                declarations.push((name, value));
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            Stmt::Var(declarations)
+        } else if matches!(
+            (self.peek(), self.tokens.get(self.pos + 1)),
+            (Some(Token::Ident(_)), Some(Token::Punct("=")))
+        ) {
+            let name = self.ident()?;
+            self.pos += 1;
+            Stmt::Assign(name, self.expression()?)
+        } else {
+            Stmt::Expr(self.expression()?)
+        };
+        // Semicolons are optional before a closing brace or the end
+        if !self.eat(";") && !matches!(self.peek(), None | Some(Token::Punct("}"))) {
+            return Err(Error::proxy(format!("Expected ';' in PAC script, found {:?}", self.peek())));
+        }
+        Ok(statement)
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        let condition = self.binary(0)?;
+        if self.eat("?") {
+            let then = self.expression()?;
+            self.expect(":")?;
+            let otherwise = self.expression()?;
+            return Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)));
+        }
+        Ok(condition)
+    }
+
+    /// Binary operators by precedence level, loosest first
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: &[&[&str]] = &[
+            &["||"],
+            &["&&"],
+            &["===", "!==", "==", "!="],
+            &["<=", ">=", "<", ">"],
+            &["+", "-"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Punct(op)) = self.peek().filter(|t| matches!(t, Token::Punct(p) if LEVELS[level].contains(p))) {
+            let op = *op;
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let mut expr = self.primary()?;
+        while self.eat(".") {
+            let name = self.ident()?;
+            expr = if self.eat("(") {
+                Expr::Method(Box::new(expr), name, self.arguments()?)
+            } else {
+                Expr::Property(Box::new(expr), name)
+            };
+        }
+        Ok(expr)
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expression()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next()? {
+            Token::Str(value) => Ok(Expr::Literal(Value::Str(value))),
+            Token::Num(value) => Ok(Expr::Literal(Value::Num(value))),
+            Token::Punct("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" | "undefined" => Ok(Expr::Literal(Value::Undefined)),
+                _ if self.eat("(") => Ok(Expr::Call(name, self.arguments()?)),
+                _ => Ok(Expr::Var(name)),
+            },
+            other => Err(Error::proxy(format!("Unsupported syntax in PAC script at {:?}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Undefined,
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Str(s) => !s.is_empty(),
+            Value::Num(n) => *n != 0.0 && !n.is_nan(),
+            Value::Bool(b) => *b,
+            Value::Undefined => false,
+        }
+    }
+
+    fn number(&self) -> f64 {
+        match self {
+            Value::Str(s) => s.trim().parse().unwrap_or(f64::NAN),
+            Value::Num(n) => *n,
+            Value::Bool(b) => *b as u8 as f64,
+            Value::Undefined => f64::NAN,
+        }
+    }
+
+    /// JavaScript's `==`, which converts between strings, numbers and booleans
+    fn loose_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Undefined, Value::Undefined) => true,
+            (Value::Undefined, _) | (_, Value::Undefined) => false,
+            _ => self.number() == other.number(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Num(n) if n.fract() == 0.0 && n.is_finite() => write!(f, "{}", *n as i64),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Undefined => write!(f, "undefined"),
+        }
+    }
+}
+
+type Scope = HashMap<String, Value>;
+
+struct Interpreter<'a> {
+    script: &'a Script,
+    depth: usize,
+}
+
+impl Interpreter<'_> {
+    /// Run statements, returning the value of a `return` if one ran
+    fn block(&mut self, statements: &[Stmt], locals: &mut Scope, globals: &Scope) -> Result<Option<Value>> {
+        for statement in statements {
+            if let Some(value) = self.statement(statement, locals, globals)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn statement(&mut self, statement: &Stmt, locals: &mut Scope, globals: &Scope) -> Result<Option<Value>> {
+        match statement {
+            Stmt::Var(declarations) => {
+                for (name, value) in declarations {
+                    let value = match value {
+                        Some(expr) => self.eval(expr, locals, globals)?,
+                        None => Value::Undefined,
+                    };
+                    locals.insert(name.clone(), value);
+                }
+            }
+            Stmt::Assign(name, expr) => {
+                let value = self.eval(expr, locals, globals)?;
+                locals.insert(name.clone(), value);
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.eval(condition, locals, globals)?.truthy() {
+                    return self.statement(then, locals, globals);
+                } else if let Some(otherwise) = otherwise {
+                    return self.statement(otherwise, locals, globals);
+                }
+            }
+            Stmt::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval(expr, locals, globals)?,
+                    None => Value::Undefined,
+                };
+                return Ok(Some(value));
+            }
+            Stmt::Block(statements) => return self.block(statements, locals, globals),
+            Stmt::Expr(expr) => {
+                self.eval(expr, locals, globals)?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn eval(&mut self, expr: &Expr, locals: &Scope, globals: &Scope) -> Result<Value> {
+        Ok(match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Var(name) => locals
+                .get(name)
+                .or_else(|| globals.get(name))
+                .cloned()
+                .ok_or_else(|| Error::proxy(format!("'{}' is not defined in PAC script", name)))?,
+            Expr::Not(expr) => Value::Bool(!self.eval(expr, locals, globals)?.truthy()),
+            Expr::Neg(expr) => Value::Num(-self.eval(expr, locals, globals)?.number()),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, locals, globals)?;
+                // `||` and `&&` short-circuit and return an operand
+                match *op {
+                    "||" if left.truthy() => return Ok(left),
+                    "&&" if !left.truthy() => return Ok(left),
+                    "||" | "&&" => return self.eval(right, locals, globals),
+                    _ => {}
+                }
+                let right = self.eval(right, locals, globals)?;
+                binary(op, left, right)
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.eval(condition, locals, globals)?.truthy() {
+                    self.eval(then, locals, globals)?
+                } else {
+                    self.eval(otherwise, locals, globals)?
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg, locals, globals))
+                    .collect::<Result<Vec<_>>>()?;
+                self.call(name, args, globals)?
+            }
+            Expr::Method(target, name, args) => {
+                let target = self.eval(target, locals, globals)?.to_string();
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg, locals, globals))
+                    .collect::<Result<Vec<_>>>()?;
+                string_method(&target, name, &args)?
+            }
+            Expr::Property(target, name) => match name.as_str() {
+                "length" => Value::Num(self.eval(target, locals, globals)?.to_string().chars().count() as f64),
+                _ => return Err(Error::proxy(format!("Unsupported property '{}' in PAC script", name))),
+            },
+        })
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>, globals: &Scope) -> Result<Value> {
+        let Some(function) = self.script.functions.get(name) else {
+            return builtin(name, &args, &self.script.dns);
+        };
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(Error::proxy("PAC script recursed too deeply"));
+        }
+        let mut locals: Scope = function
+            .params
+            .iter()
+            .cloned()
+            .zip(args.into_iter().chain(std::iter::repeat(Value::Undefined)))
+            .collect();
+        self.depth += 1;
+        let result = self.block(&function.body, &mut locals, globals);
+        self.depth -= 1;
+        Ok(result?.unwrap_or(Value::Undefined))
+    }
+}
+
+fn binary(op: &str, left: Value, right: Value) -> Value {
+    match op {
+        "+" => match (&left, &right) {
+            (Value::Str(_), _) | (_, Value::Str(_)) => Value::Str(format!("{}{}", left, right)),
+            _ => Value::Num(left.number() + right.number()),
+        },
+        "-" => Value::Num(left.number() - right.number()),
+        "==" => Value::Bool(left.loose_eq(&right)),
+        "!=" => Value::Bool(!left.loose_eq(&right)),
+        "===" => Value::Bool(left == right),
+        "!==" => Value::Bool(left != right),
+        _ => {
+            let ordering = match (&left, &right) {
+                (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+                _ => left.number().partial_cmp(&right.number()),
+            };
+            Value::Bool(match (op, ordering) {
+                (_, None) => false,
+                ("<", Some(o)) => o.is_lt(),
+                (">", Some(o)) => o.is_gt(),
+                ("<=", Some(o)) => o.is_le(),
+                (_, Some(o)) => o.is_ge(),
+            })
+        }
+    }
+}
+
+fn string_method(target: &str, name: &str, args: &[Value]) -> Result<Value> {
+    let chars: Vec<char> = target.chars().collect();
+    let index = |i: usize| {
+        let n = args.get(i).map_or(f64::NAN, Value::number);
+        if n.is_nan() { None } else { Some((n.max(0.0) as usize).min(chars.len())) }
+    };
+    Ok(match name {
+        "toLowerCase" => Value::Str(target.to_lowercase()),
+        "toUpperCase" => Value::Str(target.to_uppercase()),
+        "indexOf" => {
+            let needle = args.first().map(Value::to_string).unwrap_or_default();
+            Value::Num(target.find(&needle).map_or(-1.0, |i| target[..i].chars().count() as f64))
+        }
+        "substring" => {
+            let start = index(0).unwrap_or(0);
+            let end = index(1).unwrap_or(chars.len());
+            Value::Str(chars[start.min(end)..start.max(end)].iter().collect())
+        }
+        _ => return Err(Error::proxy(format!("Unsupported method '{}' in PAC script", name))),
+    })
+}
+
+/// The standard PAC helper functions
+fn builtin(name: &str, args: &[Value], dns: &DnsCache) -> Result<Value> {
+    let arg = |i: usize| args.get(i).map(Value::to_string).unwrap_or_default();
+    Ok(match name {
+        "isPlainHostName" => Value::Bool(!arg(0).contains('.')),
+        "dnsDomainIs" => Value::Bool(arg(0).to_ascii_lowercase().ends_with(&arg(1).to_ascii_lowercase())),
+        "localHostOrDomainIs" => {
+            let (host, full) = (arg(0).to_ascii_lowercase(), arg(1).to_ascii_lowercase());
+            Value::Bool(host == full || (!host.contains('.') && full.starts_with(&format!("{}.", host))))
+        }
+        "dnsDomainLevels" => Value::Num(arg(0).matches('.').count() as f64),
+        "shExpMatch" => Value::Bool(shell_match(&arg(0), &arg(1))),
+        "isResolvable" => Value::Bool(dns.resolve(&arg(0)).is_some()),
+        "dnsResolve" => dns.resolve(&arg(0)).map_or(Value::Undefined, |ip| Value::Str(ip.to_string())),
+        "myIpAddress" => Value::Str(dns.my_ip_address().to_string()),
+        "isInNet" => {
+            let pattern = arg(1).parse::<Ipv4Addr>();
+            let mask = arg(2).parse::<Ipv4Addr>();
+            match (dns.resolve(&arg(0)), pattern, mask) {
+                (Some(ip), Ok(pattern), Ok(mask)) => {
+                    let mask = u32::from(mask);
+                    Value::Bool(u32::from(ip) & mask == u32::from(pattern) & mask)
+                }
+                _ => Value::Bool(false),
+            }
+        }
+        "alert" => Value::Undefined,
+        _ => return Err(Error::proxy(format!("Unknown function '{}' in PAC script", name))),
+    })
+}
+
+/// Answers for the DNS helpers, kept across runs of a script
+///
+/// The script runs for every request, inside reqwest's synchronous proxy
+/// callback, so each lookup would otherwise block a runtime worker every
+/// time. Answers, including failed lookups, are reused for
+/// `DNS_CACHE_TTL`.
+#[derive(Debug, Default)]
+struct DnsCache {
+    hosts: Mutex<HashMap<String, (Option<Ipv4Addr>, Instant)>>,
+    my_ip: Mutex<Option<(Ipv4Addr, Instant)>>,
+}
+
+impl DnsCache {
+    /// Resolve a host to an IPv4 address, as PAC's DNS helpers do
+    fn resolve(&self, host: &str) -> Option<Ipv4Addr> {
+        if let Ok(ip) = host.parse() {
+            return Some(ip);
+        }
+        let host = host.to_ascii_lowercase();
+        let fresh = |(_, at): &(Option<Ipv4Addr>, Instant)| at.elapsed() < DNS_CACHE_TTL;
+        let cached = self.hosts.lock().unwrap_or_else(|e| e.into_inner()).get(&host).copied();
+        if let Some((ip, _)) = cached.filter(|entry| fresh(entry)) {
+            return ip;
+        }
+
+        let ip = off_runtime(|| resolve(&host));
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        if hosts.len() >= DNS_CACHE_PRUNE_AT {
+            hosts.retain(|_, entry| fresh(entry));
+        }
+        hosts.insert(host, (ip, Instant::now()));
+        ip
+    }
+
+    /// The address of the interface used for outgoing traffic
+    fn my_ip_address(&self) -> Ipv4Addr {
+        let mut my_ip = self.my_ip.lock().unwrap_or_else(|e| e.into_inner());
+        match *my_ip {
+            Some((ip, at)) if at.elapsed() < DNS_CACHE_TTL => ip,
+            _ => {
+                let ip = off_runtime(my_ip_address);
+                *my_ip = Some((ip, Instant::now()));
+                ip
+            }
+        }
+    }
+}
+
+/// Run blocking work without stalling other tasks on the runtime
+///
+/// The proxy callback can't await, so `spawn_blocking` isn't an option.
+/// On a multi-threaded runtime the worker hands its other tasks to the
+/// rest of the pool first; anywhere else the work just runs.
+fn off_runtime<T>(work: impl FnOnce() -> T) -> T {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(work),
+        _ => work(),
+    }
+}
+
+/// Look up the IPv4 address of a host name
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    (host, 0).to_socket_addrs().ok()?.find_map(|addr| match addr.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    })
+}
+
+/// The address of the interface used for outgoing traffic
+///
+/// Connecting a UDP socket picks a route without sending anything.
+fn my_ip_address() -> Ipv4Addr {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("192.0.2.1:80")?;
+            socket.local_addr()
+        })
+        .ok()
+        .and_then(|addr| match addr.ip() {
+            IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+            _ => None,
+        })
+        .unwrap_or(Ipv4Addr::LOCALHOST)
+}
+
+/// Match a shell glob with `*` and `?`
+fn shell_match(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAC: &str = r#"
+        // Internal hosts go direct, everything else through the proxy
+        var proxy = "PROXY proxy.example.com:8080";
+
+        function isInternal(host) {
+            return isPlainHostName(host) || dnsDomainIs(host, ".corp.example.com")
+                || isInNet(host, "10.0.0.0", "255.0.0.0");
+        }
+
+        function FindProxyForURL(url, host) {
+            if (host == "localhost" || isInternal(host)) {
+                return "DIRECT";
+            } else if (shExpMatch(url, "https://*.example.org/*")) {
+                return "HTTPS secure.example.com:443; DIRECT";
+            }
+            return proxy + "; DIRECT";
+        }
+    "#;
+
+    fn find(script: &Script, url: &str) -> String {
+        script.find_proxy(&url.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_find_proxy() {
+        let script = Script::parse(PAC).unwrap();
+        assert_eq!(find(&script, "http://localhost:3000/"), "DIRECT");
+        assert_eq!(find(&script, "http://intranet/"), "DIRECT");
+        assert_eq!(find(&script, "http://wiki.corp.example.com/"), "DIRECT");
+        assert_eq!(find(&script, "http://10.1.2.3/"), "DIRECT");
+        assert_eq!(find(&script, "https://www.example.org/a"), "HTTPS secure.example.com:443; DIRECT");
+        assert_eq!(find(&script, "http://11.1.2.3/"), "PROXY proxy.example.com:8080; DIRECT");
+
+        assert_eq!(select_proxy("DIRECT"), None);
+        assert_eq!(
+            select_proxy("SOCKS socks.example.com:1080; PROXY proxy.example.com:8080"),
+            Some("http://proxy.example.com:8080".parse().unwrap())
+        );
+        assert_eq!(
+            select_proxy("HTTPS secure.example.com:443"),
+            Some("https://secure.example.com:443".parse().unwrap())
+        );
+
+        assert!(Script::parse("function other() { return 'DIRECT'; }").is_err());
+        assert!(Script::parse("function FindProxyForURL(url, host) { while (true) {} }").is_err());
+        assert!(shell_match("www.example.org", "*.example.???"));
+        assert!(!shell_match("example.org", "*.example.org"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dns_helpers_are_cached() {
+        let script = Script::parse(
+            r#"
+            function FindProxyForURL(url, host) {
+                if (isResolvable(host) && dnsResolve(host) == "127.0.0.1") {
+                    return "DIRECT";
+                }
+                return "PROXY " + myIpAddress() + ":3128";
+            }
+        "#,
+        )
+        .unwrap();
+
+        // Lookups run on a runtime worker without stalling it
+        assert_eq!(find(&script, "http://LocalHost/"), "DIRECT");
+        assert_eq!(find(&script, "http://localhost/"), "DIRECT");
+        assert!(find(&script, "http://unresolvable.invalid/").starts_with("PROXY "));
+        let hosts = script.dns.hosts.lock().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts.get("localhost").unwrap().0, Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(hosts.get("unresolvable.invalid").unwrap().0, None);
+        assert!(script.dns.my_ip.lock().unwrap().is_some());
+    }
+}
@@ -0,0 +1,199 @@
+//! Ordered transforms applied to response bodies as they are read
+
+use std::any::TypeId;
+use std::sync::Arc;
+use http::HeaderMap;
+use http::header::CONTENT_ENCODING;
+
+use crate::compression::{self, Encoding};
+use crate::error::{Error, Result};
+
+/// A stage of a [`BodyPipeline`]
+///
+/// Stages get the whole buffered body and the response's headers, and
+/// return the body the next stage sees.
+pub trait BodyTransform: Send + Sync {
+    /// Transform a response body
+    fn transform(&self, body: Vec<u8>, headers: &HeaderMap) -> Result<Vec<u8>>;
+
+    /// Get the name of this stage
+    fn name(&self) -> &str {
+        "Unknown"
+    }
+}
+
+/// Transforms run, in order, over a response body when it's read
+///
+/// Set on a client with `ClientBuilder::body_pipeline`. `Response::bytes`,
+/// `text` and `json` run the stages over the buffered body; streaming
+/// readers such as `bytes_stream` see the body as received. The default
+/// pipeline only decompresses; add stages after it, e.g. to decrypt:
+///
+/// ```rust
+/// use rusttpx::pipeline::{BodyPipeline, BodyTransform};
+/// use rusttpx::{Client, HeaderMap, Result};
+///
+/// struct Xor(u8);
+///
+/// impl BodyTransform for Xor {
+///     fn transform(&self, body: Vec<u8>, _headers: &HeaderMap) -> Result<Vec<u8>> {
+///         Ok(body.into_iter().map(|b| b ^ self.0).collect())
+///     }
+/// }
+///
+/// let pipeline = BodyPipeline::default().add_stage(Xor(0x5a));
+/// assert_eq!(pipeline.names(), ["Decompress", "Unknown"]);
+/// let client = Client::builder().body_pipeline(pipeline).build();
+/// ```
+#[derive(Clone)]
+pub struct BodyPipeline {
+    stages: Vec<Arc<dyn BodyTransform>>,
+    decompresses: bool,
+}
+
+impl BodyPipeline {
+    /// Create a pipeline with no stages
+    pub fn new() -> Self {
+        Self { stages: Vec::new(), decompresses: false }
+    }
+
+    /// Add a stage after the existing ones
+    pub fn add_stage<T>(mut self, stage: T) -> Self
+    where
+        T: BodyTransform + 'static,
+    {
+        self.decompresses |= TypeId::of::<T>() == TypeId::of::<Decompress>();
+        self.stages.push(Arc::new(stage));
+        self
+    }
+
+    /// Get the names of the stages in the order they run
+    pub fn names(&self) -> Vec<&str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Check whether a stage undoes the `Content-Encoding`
+    pub(crate) fn decompresses(&self) -> bool {
+        self.decompresses
+    }
+
+    /// Run every stage over `body`
+    pub fn apply(&self, mut body: Vec<u8>, headers: &HeaderMap) -> Result<Vec<u8>> {
+        for stage in &self.stages {
+            body = stage.transform(body, headers)?;
+        }
+        Ok(body)
+    }
+}
+
+impl std::fmt::Debug for BodyPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BodyPipeline").field("stages", &self.names()).finish()
+    }
+}
+
+impl Default for BodyPipeline {
+    fn default() -> Self {
+        Self::new().add_stage(Decompress)
+    }
+}
+
+/// Stage undoing the body's `Content-Encoding`
+///
+/// Bodies without one, including those reqwest or
+/// `ClientBuilder::accept_encodings` already decoded, pass through.
+pub struct Decompress;
+
+impl BodyTransform for Decompress {
+    fn transform(&self, mut body: Vec<u8>, headers: &HeaderMap) -> Result<Vec<u8>> {
+        let mut codings = Vec::new();
+        for value in headers.get_all(CONTENT_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| Error::compression("Invalid Content-Encoding header"))?;
+            for token in value.split(',').filter(|t| !t.trim().is_empty()) {
+                let encoding = Encoding::parse(token).ok_or_else(|| {
+                    Error::compression(format!("Unexpected Content-Encoding: {}", token.trim()))
+                })?;
+                codings.push(encoding);
+            }
+        }
+        for encoding in codings.into_iter().rev() {
+            body = compression::decode(encoding, &body)?;
+        }
+        Ok(body)
+    }
+
+    fn name(&self) -> &str {
+        "Decompress"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Reverse;
+
+    impl BodyTransform for Reverse {
+        fn transform(&self, mut body: Vec<u8>, _headers: &HeaderMap) -> Result<Vec<u8>> {
+            body.reverse();
+            Ok(body)
+        }
+
+        fn name(&self) -> &str {
+            "Reverse"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_body_pipeline() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        // A gzip member holding "hello world"
+        let gzipped: Vec<u8> = vec![
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85, 0x11, 0x4a, 0x0d, 0x0b,
+            0x00, 0x00, 0x00,
+        ];
+        let server = MockServer::start().await;
+        Mock::given(path("/plain"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("}1:\"a\"{"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_bytes(gzipped.clone()),
+            )
+            .mount(&server)
+            .await;
+        let url = |p: &str| -> url::Url { format!("{}{}", server.uri(), p).parse().unwrap() };
+
+        let pipeline = BodyPipeline::default().add_stage(Reverse);
+        assert_eq!(pipeline.names(), ["Decompress", "Reverse"]);
+        let client = crate::Client::builder().body_pipeline(pipeline).build();
+
+        let body = client.get(url("/plain")).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(body, b"{\"a\":1}");
+        let text = client.get(url("/plain")).send().await.unwrap().text().await.unwrap();
+        assert_eq!(text, "{\"a\":1}");
+        let json: serde_json::Value = client.get(url("/plain")).send().await.unwrap().json().await.unwrap();
+        assert_eq!(json, serde_json::json!({"a": 1}));
+
+        // Stages run in order: decompressed first, then reversed
+        let text = client.get(url("/gzip")).send().await.unwrap().text().await.unwrap();
+        assert_eq!(text, "dlrow olleh");
+
+        // raw_bytes skips the pipeline and keeps the wire bytes
+        let client = crate::Client::builder().body_pipeline(BodyPipeline::default()).build();
+        let raw = client.get(url("/gzip")).send().await.unwrap().raw_bytes().await.unwrap();
+        assert_eq!(raw, gzipped);
+
+        // Without a pipeline the body is left alone
+        let body = crate::Client::new().get(url("/plain")).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(body, b"}1:\"a\"{");
+    }
+}
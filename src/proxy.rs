@@ -1,8 +1,10 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use reqwest::{ClientBuilder as ReqwestBuilder, Proxy as ReqwestProxy};
 use url::Url;
 
 use crate::error::{Error, Result};
+use crate::pac::{self, Script};
 
 /// Proxy configuration for HTTP requests
 ///
@@ -20,6 +22,8 @@ pub struct ProxyConfig {
     pub bypass: Vec<String>,
     /// Custom proxy for specific hosts
     pub custom_proxies: HashMap<String, Url>,
+    /// Parsed PAC script choosing the proxy per request
+    pac: Option<Arc<Script>>,
 }
 
 /// Proxy authentication
@@ -60,6 +64,7 @@ impl ProxyConfig {
             auth: None,
             bypass: Vec::new(),
             custom_proxies: HashMap::new(),
+            pac: None,
         }
     }
 
@@ -112,6 +117,16 @@ impl ProxyConfig {
         self
     }
 
+    /// Choose proxies with a proxy auto-configuration (PAC) script
+    ///
+    /// The script is parsed once and its `FindProxyForURL(url, host)` is
+    /// called for every request. It takes precedence over the other
+    /// proxies in this configuration, though bypass patterns still apply.
+    pub fn pac_script(mut self, source: &str) -> Result<Self> {
+        self.pac = Some(Arc::new(Script::parse(source)?));
+        Ok(self)
+    }
+
     /// Get the HTTP proxy URL
     pub fn get_http_proxy(&self) -> Option<&Url> {
         self.http_proxy.as_ref()
@@ -142,6 +157,7 @@ impl ProxyConfig {
         self.http_proxy.is_some()
             || self.https_proxy.is_some()
             || !self.custom_proxies.is_empty()
+            || self.pac.is_some()
     }
 
     /// Check if a URL should bypass the proxy
@@ -174,29 +190,51 @@ impl ProxyConfig {
         }
     }
 
-    /// Apply this configuration to a reqwest client builder
-    pub fn apply_to_builder(self, mut builder: ReqwestBuilder) -> ReqwestBuilder {
-        // Apply HTTP proxy
-        if let Some(http_proxy) = self.http_proxy {
-            if let Ok(proxy) = ReqwestProxy::http(http_proxy.as_str()) {
-                builder = builder.proxy(proxy);
-            }
+    /// Get the proxy the PAC script chooses for a URL
+    ///
+    /// Returns `None` without a PAC script, when the script answers
+    /// `DIRECT`, and when evaluating it fails, so a broken script
+    /// connects directly rather than failing every request.
+    pub fn get_pac_proxy_for_url(&self, url: &Url) -> Option<Url> {
+        let result = self.pac.as_ref()?.find_proxy(url);
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &result {
+            tracing::warn!("PAC script failed for {}: {}", url, e);
         }
+        pac::select_proxy(&result.ok()?)
+    }
 
-        // Apply HTTPS proxy
-        if let Some(https_proxy) = self.https_proxy {
-            if let Ok(proxy) = ReqwestProxy::https(https_proxy.as_str()) {
-                builder = builder.proxy(proxy);
-            }
+    /// Get the proxy a request to a URL goes through, if any
+    ///
+    /// Bypassed URLs go direct. Otherwise a PAC script decides when there
+    /// is one, and the custom and scheme-based proxies when there isn't.
+    pub fn select_proxy(&self, url: &Url) -> Option<Url> {
+        if self.should_bypass(url) {
+            return None;
         }
+        if self.pac.is_some() {
+            return self.get_pac_proxy_for_url(url);
+        }
+        self.get_proxy_for_url(url).cloned()
+    }
 
-        // Apply authentication if available
-        if let Some(_auth) = self.auth {
-            // Note: Reqwest handles proxy auth automatically from the URL
-            // This is a placeholder for future implementation
+    /// Apply this configuration to a reqwest client builder
+    ///
+    /// The configuration replaces reqwest's own environment proxy
+    /// detection, so a configuration without proxies disables proxying.
+    /// The proxy is chosen per request with [`ProxyConfig::select_proxy`].
+    pub fn apply_to_builder(self, builder: ReqwestBuilder) -> ReqwestBuilder {
+        if !self.has_proxy() {
+            return builder.no_proxy();
         }
 
-        builder
+        let auth = self.auth.clone();
+        let proxy = ReqwestProxy::custom(move |url| self.select_proxy(url));
+        let proxy = match auth {
+            Some(auth) => proxy.basic_auth(auth.username(), auth.password()),
+            None => proxy,
+        };
+        builder.proxy(proxy)
     }
 }
 
@@ -318,24 +356,29 @@ impl ProxyConfig {
 
     /// Create a proxy configuration from environment variables
     pub fn from_env() -> Self {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// Create a proxy configuration from variables read through `get`
+    pub(crate) fn from_env_with(get: impl Fn(&str) -> Option<String>) -> Self {
         let mut config = Self::new();
 
         // Check for HTTP_PROXY
-        if let Ok(http_proxy) = std::env::var("HTTP_PROXY") {
+        if let Some(http_proxy) = get("HTTP_PROXY") {
             if let Ok(url) = http_proxy.parse::<Url>() {
                 config = config.http_proxy(url);
             }
         }
 
         // Check for HTTPS_PROXY
-        if let Ok(https_proxy) = std::env::var("HTTPS_PROXY") {
+        if let Some(https_proxy) = get("HTTPS_PROXY") {
             if let Ok(url) = https_proxy.parse::<Url>() {
                 config = config.https_proxy(url);
             }
         }
 
         // Check for NO_PROXY
-        if let Ok(no_proxy) = std::env::var("NO_PROXY") {
+        if let Some(no_proxy) = get("NO_PROXY") {
             let bypass_patterns: Vec<String> = no_proxy
                 .split(',')
                 .map(|s| s.trim().to_string())
@@ -347,6 +390,24 @@ impl ProxyConfig {
         config
     }
 
+    /// Create a proxy configuration from a PAC file
+    ///
+    /// `url_or_path` is fetched when it's an `http` or `https` URL and
+    /// read from disk otherwise. See [`ProxyConfig::pac_script`].
+    pub async fn from_pac(url_or_path: &str) -> Result<Self> {
+        let source = match url_or_path.parse::<Url>() {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+                // The PAC file itself is always fetched directly
+                let client = reqwest::Client::builder().no_proxy().build()?;
+                client.get(url).send().await?.error_for_status()?.text().await?
+            }
+            _ => tokio::fs::read_to_string(url_or_path)
+                .await
+                .map_err(|e| Error::proxy(format!("Failed to read PAC file {}: {}", url_or_path, e)))?,
+        };
+        Self::new().pac_script(&source)
+    }
+
     /// Create a proxy configuration for localhost
     pub fn localhost(port: u16) -> Self {
         let url = format!("http://localhost:{}", port)
@@ -423,4 +484,86 @@ mod tests {
         assert_eq!(ProxyType::Socks4.scheme(), "socks4");
         assert_eq!(ProxyType::Socks5.scheme(), "socks5");
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_from_pac() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::{header, method};
+
+        // Stands in for both the origin and the proxy
+        let server = MockServer::start().await;
+        let proxy = server.address();
+        let pac = format!(
+            r#"function FindProxyForURL(url, host) {{
+                if (host == "localhost" || dnsDomainIs(host, ".localhost")) {{
+                    return "DIRECT";
+                }}
+                return "PROXY {}";
+            }}"#,
+            proxy
+        );
+        Mock::given(method("GET"))
+            .and(header("host", "example.test"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("via proxy"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(pac.clone()))
+            .mount(&server)
+            .await;
+
+        let path = std::env::temp_dir().join(format!("rusttpx-{}.pac", std::process::id()));
+        std::fs::write(&path, &pac).unwrap();
+        let from_file = ProxyConfig::from_pac(path.to_str().unwrap()).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let from_url = ProxyConfig::from_pac(&format!("{}/proxy.pac", server.uri())).await.unwrap();
+
+        for config in [from_file, from_url] {
+            assert!(config.has_proxy());
+            let direct = "http://localhost:3000/".parse().unwrap();
+            assert_eq!(config.get_pac_proxy_for_url(&direct), None);
+            let external: Url = "http://example.test/".parse().unwrap();
+            let expected = format!("http://{}", proxy).parse().unwrap();
+            assert_eq!(config.get_pac_proxy_for_url(&external), Some(expected));
+
+            let client = crate::Client::builder().proxy_config(config).build();
+            let response = client.get(external).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "via proxy");
+        }
+
+        assert!(ProxyConfig::from_pac("/nonexistent/proxy.pac").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_per_host_proxy_and_bypass() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        async fn server(body: &str) -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(wiremock::matchers::any())
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&server)
+                .await;
+            server
+        }
+        let origin = server("origin").await;
+        let default_proxy = server("default proxy").await;
+        let custom_proxy = server("custom proxy").await;
+
+        let config = ProxyConfig::new()
+            .proxy(default_proxy.uri().parse().unwrap())
+            .custom_proxy("custom.test", custom_proxy.uri().parse().unwrap())
+            .bypass("127.0.0.1");
+        let client = crate::Client::builder().proxy_config(config).build();
+
+        for (url, expected) in [
+            (origin.uri(), "origin"),
+            ("http://custom.test/".to_string(), "custom proxy"),
+            ("http://other.test/".to_string(), "default proxy"),
+        ] {
+            let response = client.get(url.parse::<Url>().unwrap()).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), expected, "{}", url);
+        }
+        assert_eq!(origin.received_requests().await.unwrap().len(), 1);
+    }
+}
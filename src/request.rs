@@ -1,26 +1,98 @@
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use reqwest::{Request as ReqwestRequest, RequestBuilder as ReqwestBuilder};
-use http::{Method, HeaderMap, HeaderValue};
+use http::{Extensions, Method, HeaderMap, HeaderValue};
+use tokio::sync::{oneshot, Mutex, Semaphore, SemaphorePermit};
 use url::Url;
+use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
 use serde_json::Value;
 
 use crate::error::{Error, Result};
-use crate::response::Response;
+use crate::client::{ConnectorOptions, Connectors};
+use crate::response::{sort_keys, HashAlgo, Hasher, HeaderLimits, Response};
 use crate::cookies::CookieJar;
 use crate::timeout::TimeoutConfig;
+use crate::circuit::{self, CircuitBreaker};
+use crate::trace::{self, TraceContext};
+use crate::cache::ResponseCache;
+use crate::compression::{self, Encoding};
+use crate::middleware::{self, MiddlewareChain};
+use crate::json;
+use crate::multipart;
+use crate::pipeline::BodyPipeline;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::retry::{self, RetryConfig};
+use crate::redirect::{self, RefererPolicy, Redirects};
+use crate::throttle::{self, BandwidthLimit};
+use crate::tls::ClientIdentity;
+#[cfg(feature = "ntlm")]
+use crate::ntlm::{self, NtlmCredentials};
+
+/// How long the body is held back after sending `Expect: 100-continue`
+pub const EXPECT_CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Headers left out of `Request::fingerprint` because they change between
+/// otherwise identical requests or are derived from the body
+pub const FINGERPRINT_EXCLUDED_HEADERS: &[&str] = &[
+    "date",
+    "content-length",
+    "traceparent",
+    "tracestate",
+    "x-request-id",
+];
 
 /// HTTP request representation
 ///
 /// This type represents an HTTP request that can be sent by the client.
 /// It provides methods for accessing request properties and sending the request.
-#[derive(Clone)]
 pub struct Request {
     method: Method,
     url: Url,
     headers: HeaderMap,
     body: Option<RequestBody>,
     timeout_config: TimeoutConfig,
+    options: RequestOptions,
+    extensions: Extensions,
+}
+
+// Note: http::Extensions isn't Clone in this version, so clones start with empty extensions
+impl Clone for Request {
+    fn clone(&self) -> Self {
+        Self {
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            timeout_config: self.timeout_config.clone(),
+            options: self.options.clone(),
+            extensions: Extensions::new(),
+        }
+    }
+}
+
+/// How a single request is sent, as set on its `RequestBuilder`
+///
+/// Carried on the built `Request`, so `Client::send(builder.build()?)`
+/// sends it the same way as `builder.send()`.
+#[derive(Clone, Default)]
+struct RequestOptions {
+    timeout: Option<Duration>,
+    version: Option<http::Version>,
+    expect_continue: bool,
+    chunked: bool,
+    upload_limit: BandwidthLimit,
+    client_identity: Option<ClientIdentity>,
+    connect_timeout: Option<Duration>,
+    absolute_form: bool,
+    close_connection: bool,
+    max_body_size: Option<usize>,
+    no_accept_encoding: bool,
+    no_cookies: bool,
+    /// Runs after the client's middleware
+    middleware: Option<MiddlewareChain>,
+    /// Overrides the client's `error_on_status`
+    error_on_status: Option<bool>,
 }
 
 /// Request body types
@@ -38,6 +110,115 @@ pub enum RequestBody {
     Form(Vec<(String, String)>),
     /// Multipart form data
     Multipart(Vec<(String, MultipartPart)>),
+    /// Streaming body, which can only be sent once
+    Stream(StreamBody),
+}
+
+impl RequestBody {
+    /// Check whether the body can be sent more than once
+    pub fn is_replayable(&self) -> bool {
+        !matches!(self, RequestBody::Stream(_))
+    }
+}
+
+/// Single-use streaming request body
+///
+/// Clones share the same underlying stream, so only the first one sent
+/// carries the body. Use `Request::try_clone` to copy requests safely.
+#[derive(Clone)]
+pub struct StreamBody {
+    inner: Arc<std::sync::Mutex<Option<reqwest::Body>>>,
+}
+
+impl StreamBody {
+    /// Wrap a body, typically one built with `reqwest::Body::wrap_stream`
+    pub fn new(body: reqwest::Body) -> Self {
+        Self {
+            inner: Arc::new(std::sync::Mutex::new(Some(body))),
+        }
+    }
+
+    /// Take the body out, leaving nothing for later sends
+    fn take(&self) -> Option<reqwest::Body> {
+        self.inner.lock().ok().and_then(|mut body| body.take())
+    }
+}
+
+/// Method, URL, headers and body of a request that was sent
+///
+/// Recorded by clients built with `ClientBuilder::record_last_request`.
+/// In-memory bodies are copied; a streaming body is consumed by sending,
+/// so the snapshot only notes that there was one.
+#[derive(Debug, Clone)]
+pub struct RequestSnapshot {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+    streaming_body: bool,
+}
+
+impl RequestSnapshot {
+    pub(crate) fn capture(request: &ReqwestRequest) -> Self {
+        let body = request.body().map(|body| body.as_bytes());
+        Self {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+            body: body.flatten().map(|bytes| bytes.to_vec()),
+            streaming_body: matches!(body, Some(None)),
+        }
+    }
+
+    /// Get the HTTP method
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Get the URL
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the headers set on the request, without the client's defaults
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body, if there was one held in memory
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    /// Check whether the request can be sent again
+    ///
+    /// Requests with streaming bodies can't, since the stream was consumed.
+    pub fn is_replayable(&self) -> bool {
+        !self.streaming_body
+    }
+
+    /// Rebuild the request, failing for a streaming body
+    pub fn to_request(&self) -> Result<Request> {
+        if self.streaming_body {
+            return Err(Error::invalid_request("The request had a streaming body, which can't be replayed"));
+        }
+        let mut request = Request::new(self.method.clone(), self.url.clone());
+        request.headers = self.headers.clone();
+        request.body = Some(match &self.body {
+            Some(body) => RequestBody::Bytes(body.clone()),
+            None => RequestBody::Empty,
+        });
+        Ok(request)
+    }
+}
+
+/// Where a client records the last request it sent
+pub(crate) type LastRequest = Arc<std::sync::Mutex<Option<RequestSnapshot>>>;
+
+pub(crate) fn record_request(last_request: Option<&LastRequest>, request: &ReqwestRequest) {
+    if let Some(Ok(mut last)) = last_request.map(|last| last.lock()) {
+        *last = Some(RequestSnapshot::capture(request));
+    }
 }
 
 /// Multipart form part
@@ -65,6 +246,8 @@ impl Request {
             headers: HeaderMap::new(),
             body: Some(RequestBody::Empty),
             timeout_config: TimeoutConfig::default(),
+            options: RequestOptions::default(),
+            extensions: Extensions::new(),
         }
     }
 
@@ -88,6 +271,54 @@ impl Request {
         &mut self.headers
     }
 
+    /// Get the extensions attached to this request
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get mutable access to the extensions, for attaching caller data
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Move the method, URL, headers and extensions into an `http::Request` for middleware
+    ///
+    /// The body is copied in as bytes. Form, multipart and streaming bodies
+    /// aren't held as bytes, so middleware sees them as empty.
+    pub(crate) fn into_http_request(mut self) -> (http::Request<Vec<u8>>, Self) {
+        let mut request = http::Request::new(self.body_bytes());
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.url.as_str().parse().unwrap_or_default();
+        *request.headers_mut() = std::mem::take(&mut self.headers);
+        *request.extensions_mut() = std::mem::take(&mut self.extensions);
+        (request, self)
+    }
+
+    /// Restore the method, URL, headers and extensions from an HTTP request
+    ///
+    /// A body the middleware changed replaces the request's body as bytes.
+    pub(crate) fn merge_http_request(mut self, request: http::Request<Vec<u8>>) -> Result<Self> {
+        let (parts, body) = request.into_parts();
+        if body != self.body_bytes() {
+            self.body = Some(RequestBody::Bytes(body));
+        }
+        self.url = Url::parse(&parts.uri.to_string())?;
+        self.method = parts.method;
+        self.headers = parts.headers;
+        self.extensions = parts.extensions;
+        Ok(self)
+    }
+
+    /// Copy the body as bytes, or nothing if it isn't held in memory as bytes
+    fn body_bytes(&self) -> Vec<u8> {
+        match &self.body {
+            Some(RequestBody::Text(text)) => text.clone().into_bytes(),
+            Some(RequestBody::Json(value)) => serde_json::to_vec(value).unwrap_or_default(),
+            Some(RequestBody::Bytes(bytes)) => bytes.clone(),
+            _ => Vec::new(),
+        }
+    }
+
     /// Get the body
     pub fn body(&self) -> Option<&RequestBody> {
         self.body.as_ref()
@@ -98,6 +329,103 @@ impl Request {
         &self.timeout_config
     }
 
+    /// Clone the request if its body can be replayed
+    ///
+    /// Returns `None` for streaming bodies, which can only be sent once.
+    /// Retry logic should use this to decide whether a request may be retried.
+    pub fn try_clone(&self) -> Option<Request> {
+        match &self.body {
+            Some(body) if !body.is_replayable() => None,
+            _ => Some(self.clone()),
+        }
+    }
+
+    /// Compute a stable hash identifying this request
+    ///
+    /// Covers the method, the normalized URL (query parameters sorted, no
+    /// fragment), every header except `FINGERPRINT_EXCLUDED_HEADERS` and the
+    /// body. Returns a SHA-256 digest as lowercase hex, which stays the same
+    /// across processes, so it can key caches and deduplicate requests.
+    pub fn fingerprint(&self) -> String {
+        let names: Vec<&http::header::HeaderName> = self
+            .headers
+            .keys()
+            .filter(|name| !FINGERPRINT_EXCLUDED_HEADERS.contains(&name.as_str()))
+            .collect();
+        self.fingerprint_over(names)
+    }
+
+    /// Compute a fingerprint like `fingerprint`, over only the given headers
+    pub fn fingerprint_with(&self, headers: &[http::header::HeaderName]) -> String {
+        self.fingerprint_over(headers.iter().collect())
+    }
+
+    fn fingerprint_over(&self, mut names: Vec<&http::header::HeaderName>) -> String {
+        // Every field is length-prefixed so adjacent fields can't run together
+        let mut hasher = Hasher::new(HashAlgo::Sha256);
+        let mut field = |data: &[u8]| {
+            hasher.update(&(data.len() as u64).to_be_bytes());
+            hasher.update(data);
+        };
+
+        field(self.method.as_str().as_bytes());
+        let mut url = self.url.clone();
+        url.set_fragment(None);
+        let mut query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        query.sort();
+        if query.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(&query);
+        }
+        field(url.as_str().as_bytes());
+
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names.dedup();
+        for name in names {
+            for value in self.headers.get_all(name) {
+                field(name.as_str().as_bytes());
+                field(value.as_bytes());
+            }
+        }
+
+        match &self.body {
+            None | Some(RequestBody::Empty) => {}
+            // Canonical so key order in the value doesn't matter
+            Some(RequestBody::Json(value)) => field(&canonical_json(value).unwrap_or_default()),
+            Some(RequestBody::Form(pairs)) => {
+                for (key, value) in pairs {
+                    field(key.as_bytes());
+                    field(value.as_bytes());
+                }
+            }
+            Some(RequestBody::Multipart(parts)) => {
+                for (name, part) in parts {
+                    field(name.as_bytes());
+                    field(part.filename.as_deref().unwrap_or_default().as_bytes());
+                    field(part.content_type.as_deref().unwrap_or_default().as_bytes());
+                    match &part.content {
+                        MultipartContent::Text(text) => field(text.as_bytes()),
+                        MultipartContent::File(bytes) => field(bytes),
+                    }
+                }
+            }
+            // A stream can't be read without consuming it
+            Some(RequestBody::Stream(_)) => field(b"stream"),
+            Some(_) => field(&self.body_bytes()),
+        }
+        hasher.finish()
+    }
+
+    /// Turn this request into the next hop of a redirect to `location`
+    pub(crate) fn redirected(mut self, status: http::StatusCode, location: Url, policy: Option<RefererPolicy>) -> Self {
+        if redirect::prepare_hop(status, &mut self.method, &mut self.headers, &self.url, &location, policy) {
+            self.body = None;
+        }
+        self.url = location;
+        self
+    }
+
     /// Set a header
     pub fn header(mut self, name: &str, value: &str) -> Result<Self> {
         let name = name.parse::<http::header::HeaderName>()?;
@@ -164,21 +492,41 @@ impl Request {
         Ok(self)
     }
 
+    /// Set a streaming body
+    pub fn body_stream(mut self, body: StreamBody) -> Self {
+        self.body = Some(RequestBody::Stream(body));
+        self
+    }
+
     /// Set timeout
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout_config = self.timeout_config.timeout(timeout);
+        self.options.timeout = Some(timeout);
         self
     }
 
+    /// Use `timeout` unless the request has a timeout of its own
+    pub(crate) fn default_timeout(&mut self, timeout: Duration) {
+        self.options.timeout.get_or_insert(timeout);
+    }
+
     /// Convert to reqwest request
     pub fn into_reqwest_request(self) -> Result<ReqwestRequest> {
-        let mut builder = ReqwestRequest::new(self.method, self.url.into());
+        let mut url = self.url;
+        let credentials = take_url_credentials(&mut url);
+        let mut builder = ReqwestRequest::new(self.method, url);
+        *builder.timeout_mut() = self.options.timeout;
+        if let Some(version) = self.options.version {
+            *builder.version_mut() = version;
+        }
         
-        // Set headers
-        for (name, value) in self.headers {
-            if let Some(name) = name {
-                builder.headers_mut().insert(name, value);
-            }
+        // Set headers, keeping every value of repeated ones
+        *builder.headers_mut() = self.headers;
+        if let Some(credentials) = credentials {
+            builder
+                .headers_mut()
+                .entry(http::header::AUTHORIZATION)
+                .or_insert(credentials);
         }
 
         // Set body
@@ -206,6 +554,12 @@ impl Request {
                 // Multipart needs special handling in the builder
                 return Err(Error::custom("Multipart requests must be built with RequestBuilder"));
             }
+            Some(RequestBody::Stream(stream)) => {
+                let body = stream
+                    .take()
+                    .ok_or_else(|| Error::stream("Stream body has already been sent"))?;
+                *builder.body_mut() = Some(body);
+            }
             None => {
                 // No body
             }
@@ -221,10 +575,14 @@ impl Request {
 /// configurations, headers, and body types.
 pub struct RequestBuilder {
     reqwest_builder: ReqwestBuilder,
-    cookie_jar: Arc<CookieJar>,
+    /// Client defaults the request hasn't set a header of its own for yet
+    default_headers: HeaderMap,
     method: Method,
     url: Url,
     timeout_config: TimeoutConfig,
+    options: RequestOptions,
+    context: SendContext,
+    extensions: Extensions,
 }
 
 impl RequestBuilder {
@@ -235,19 +593,37 @@ impl RequestBuilder {
         method: Method,
         url: Url,
         timeout_config: TimeoutConfig,
-        _default_headers: HeaderMap,
+        default_headers: HeaderMap,
     ) -> Self {
-        let reqwest_builder = reqwest_client.request(method.clone(), url.as_str());
+        let mut url = url;
+        let credentials = take_url_credentials(&mut url);
+        // URL credentials act as a default that explicit credentials replace
+        let mut default_headers = default_headers;
+        if let Some(credentials) = credentials {
+            default_headers.insert(http::header::AUTHORIZATION, credentials);
+        }
+        let reqwest_builder = reqwest_client
+            .request(method.clone(), url.as_str())
+            .headers(default_headers.clone());
         
         Self {
             reqwest_builder,
-            cookie_jar,
+            default_headers,
             method,
             url,
             timeout_config,
+            options: RequestOptions::default(),
+            context: SendContext::new(reqwest_client, cookie_jar),
+            extensions: Extensions::new(),
         }
     }
 
+    /// Send the request with a client's settings
+    pub(crate) fn context(mut self, context: SendContext) -> Self {
+        self.context = context;
+        self
+    }
+
     /// Get the HTTP method
     pub fn method(&self) -> &Method {
         &self.method
@@ -258,20 +634,39 @@ impl RequestBuilder {
         &self.url
     }
 
+    /// Get mutable access to the extensions carried into the built request
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Set a header
-    pub fn header(mut self, name: &str, value: &str) -> Result<Self> {
+    ///
+    /// Values are appended, so a header can be sent more than once. The
+    /// first value set for a header the client has a default for replaces
+    /// that default.
+    pub fn header(self, name: &str, value: &str) -> Result<Self> {
         let name = name.parse::<http::header::HeaderName>()?;
         let value = value.parse::<HeaderValue>()?;
-        self.reqwest_builder = self.reqwest_builder.header(name, value);
-        Ok(self)
+        Ok(self.append_header(name, value))
     }
 
     /// Set multiple headers
+    ///
+    /// Values are appended as with [`RequestBuilder::header`].
     pub fn headers(mut self, headers: HeaderMap) -> Self {
-        for (name, value) in headers {
-            if let Some(name) = name {
-                self.reqwest_builder = self.reqwest_builder.header(name, value);
-            }
+        for (name, value) in headers.iter() {
+            self = self.append_header(name.clone(), value.clone());
+        }
+        self
+    }
+
+    fn append_header(mut self, name: http::header::HeaderName, value: HeaderValue) -> Self {
+        if self.default_headers.remove(&name).is_some() {
+            let mut replacement = HeaderMap::new();
+            replacement.insert(name, value);
+            self.reqwest_builder = self.reqwest_builder.headers(replacement);
+        } else {
+            self.reqwest_builder = self.reqwest_builder.header(name, value);
         }
         self
     }
@@ -291,10 +686,11 @@ impl RequestBuilder {
         self.header("Authorization", auth)
     }
 
-    /// Set basic authentication
-    pub fn basic_auth(mut self, username: &str, password: Option<&str>) -> Self {
-        self.reqwest_builder = self.reqwest_builder.basic_auth(username, password);
-        self
+    /// Set basic authentication, replacing credentials from the URL
+    pub fn basic_auth(self, username: &str, password: Option<&str>) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::AUTHORIZATION, basic_auth_header(username, password));
+        self.headers(headers)
     }
 
     /// Set bearer token authentication
@@ -316,18 +712,115 @@ impl RequestBuilder {
         Ok(self)
     }
 
+    /// Set a JSON body in canonical form, for APIs that sign request bodies
+    ///
+    /// Object keys are sorted and there's no whitespace between tokens, as
+    /// produced by [`canonical_json`], so equal values always serialize to
+    /// the same bytes. Those bytes are exactly what middleware such as a
+    /// request signer sees and what goes on the wire.
+    pub fn json_canonical<T>(mut self, body: &T) -> Result<Self>
+    where
+        T: serde::Serialize,
+    {
+        let body = canonical_json(body)?;
+        self = self.content_type("application/json")?;
+        self.reqwest_builder = self.reqwest_builder.body(body);
+        Ok(self)
+    }
+
+    /// Set a JSON Merge Patch (RFC 7386) body
+    ///
+    /// `body` is sent as JSON with `Content-Type: application/merge-patch+json`.
+    /// Members set to `null` ask the server to remove them. See
+    /// [`json::merge_patch`] for applying a patch locally.
+    pub fn merge_patch<T>(mut self, body: &T) -> Result<Self>
+    where
+        T: serde::Serialize,
+    {
+        let body = serde_json::to_vec(body)?;
+        self = self.content_type(json::MERGE_PATCH_CONTENT_TYPE)?;
+        self.reqwest_builder = self.reqwest_builder.body(body);
+        Ok(self)
+    }
+
+    /// Set a JSON Patch (RFC 6902) body
+    ///
+    /// The patch is sent with `Content-Type: application/json-patch+json`.
+    pub fn json_patch(mut self, patch: &json::JsonPatch) -> Result<Self> {
+        let body = serde_json::to_vec(patch)?;
+        self = self.content_type(json::JSON_PATCH_CONTENT_TYPE)?;
+        self.reqwest_builder = self.reqwest_builder.body(body);
+        Ok(self)
+    }
+
     /// Set text body
     pub fn text(mut self, body: &str) -> Result<Self> {
         self.reqwest_builder = self.reqwest_builder.body(body.to_string());
         Ok(self)
     }
 
+    /// Set a text body with a `Content-Type` inferred from its contents
+    ///
+    /// Valid JSON gets `application/json`, HTML documents `text/html`, other
+    /// markup `application/xml` and anything else `text/plain`. A
+    /// `Content-Type` set before this call is kept.
+    pub fn body_auto(self, content: &str) -> Result<Self> {
+        let builder = if self.has_header(http::header::CONTENT_TYPE) {
+            self
+        } else {
+            self.content_type(infer_content_type(content))?
+        };
+        builder.text(content)
+    }
+
+    /// Check whether a header has been set, including client defaults
+    fn has_header(&self, name: http::header::HeaderName) -> bool {
+        self.reqwest_builder
+            .try_clone()
+            .and_then(|builder| builder.build().ok())
+            .map(|request| request.headers().contains_key(name))
+            .unwrap_or(false)
+    }
+
     /// Set bytes body
     pub fn bytes(mut self, body: Vec<u8>) -> Result<Self> {
         self.reqwest_builder = self.reqwest_builder.body(body);
         Ok(self)
     }
 
+    /// Stream the file at `path` as the body
+    ///
+    /// `Content-Length` comes from the file's size and `Content-Type` from
+    /// its extension, falling back to `application/octet-stream`. A
+    /// `Content-Type` set before this call is kept. The body is a stream,
+    /// so it isn't replayed by retries.
+    pub fn file_body(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let open = || -> std::io::Result<(std::fs::File, u64)> {
+            let file = std::fs::File::open(&path)?;
+            let metadata = file.metadata()?;
+            if !metadata.is_file() {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file"));
+            }
+            Ok((file, metadata.len()))
+        };
+        let (file, len) = open().map_err(|e| {
+            Error::invalid_request(format!("Can't read body from {}: {}", path.display(), e))
+        })?;
+
+        let mut builder = if self.has_header(http::header::CONTENT_TYPE) {
+            self
+        } else {
+            let content_type = multipart::utils::get_content_type_for_file(&path);
+            self.content_type(content_type.as_deref().unwrap_or("application/octet-stream"))?
+        };
+        builder = builder.header("Content-Length", &len.to_string())?;
+        let chunks = file_chunks(tokio::fs::File::from_std(file));
+        let body = reqwest::Body::wrap_stream(throttle::throttle(chunks, builder.options.upload_limit.clone()));
+        builder.reqwest_builder = builder.reqwest_builder.body(body);
+        Ok(builder)
+    }
+
     /// Set form data
     pub fn form<T>(mut self, data: &T) -> Result<Self>
     where
@@ -355,15 +848,35 @@ impl RequestBuilder {
     /// Set timeout
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout_config = self.timeout_config.timeout(timeout);
-        self.reqwest_builder = self.reqwest_builder.timeout(timeout);
+        self.options.timeout = Some(timeout);
         self
     }
 
     /// Set connection timeout
+    ///
+    /// reqwest only takes a connect timeout when a client is built, so the
+    /// request is sent through a connector built from the client's
+    /// configuration with this timeout. Connectors are kept per timeout, so
+    /// requests with the same timeout share connections, but not with the
+    /// client's own pool. Only requests created from a
+    /// [`Client`](crate::Client) can set a connect timeout.
     pub fn connect_timeout(mut self, timeout: Duration) -> Self {
         self.timeout_config = self.timeout_config.connect_timeout(timeout);
-        // Note: reqwest::RequestBuilder doesn't have connect_timeout method
-        // self.reqwest_builder = self.reqwest_builder.connect_timeout(timeout);
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Write the request target in absolute-form, as in `GET http://host/path HTTP/1.1`
+    ///
+    /// For servers and proxies that expect every request to name the full
+    /// URL. Only plain `http` requests are affected: `https` requests go
+    /// through TLS, where the target is always origin-form. Requests sent
+    /// through a configured HTTP proxy already use absolute-form, and
+    /// `false` leaves that as it is. Like `connect_timeout`, this uses a
+    /// separate connector, so only requests created from a
+    /// [`Client`](crate::Client) can set it.
+    pub fn absolute_form(mut self, enabled: bool) -> Self {
+        self.options.absolute_form = enabled;
         self
     }
 
@@ -379,43 +892,207 @@ impl RequestBuilder {
         self
     }
 
+    /// Send `Expect: 100-continue` and hold back the body
+    ///
+    /// The headers are sent first and the body is withheld for up to
+    /// [`EXPECT_CONTINUE_TIMEOUT`]. If the server answers with a final status
+    /// in that window (e.g. 401 or 417), that response is returned and the
+    /// body is never sent. The underlying HTTP stack does not surface interim
+    /// `100 Continue` responses, so a server that accepts the expectation
+    /// receives the body once the timeout elapses, as curl does. Requests
+    /// without an in-memory body are sent unchanged.
+    pub fn expect_continue(mut self, enabled: bool) -> Self {
+        self.options.expect_continue = enabled;
+        self
+    }
+
+    /// Send the body with `Transfer-Encoding: chunked` instead of `Content-Length`
+    ///
+    /// In-memory bodies are sent as a single-chunk stream. A chunked body
+    /// is a stream, so it isn't held back by `expect_continue` or replayed
+    /// by retries.
+    pub fn chunked(mut self, enabled: bool) -> Self {
+        self.options.chunked = enabled;
+        self
+    }
+
+    /// Send `Connection: close`, so the connection isn't reused afterward
+    ///
+    /// For servers known to leave connections in a bad state. The server
+    /// is asked to close the connection after responding, and the request
+    /// goes through a connector that keeps no idle connections, so it's
+    /// never pooled whatever the server does. That connector also means the
+    /// request opens a new connection rather than reusing one from the
+    /// client's pool. Requests not created from a [`Client`](crate::Client)
+    /// only send the header.
+    pub fn close_connection(mut self) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::CONNECTION, HeaderValue::from_static("close"));
+        self.options.close_connection = true;
+        self.headers(headers)
+    }
+
+    /// Refuse to send a body larger than `max` bytes
+    ///
+    /// A local guard for servers with a known upload limit: instead of a
+    /// round trip that ends in `413 Payload Too Large`, sending fails with
+    /// `Error::InvalidRequest` before anything goes out. The body is
+    /// measured as serialized, after middleware. Streaming bodies are
+    /// measured by their `Content-Length`, and pass when they have none.
+    pub fn max_body_size(mut self, max: usize) -> Self {
+        self.options.max_body_size = Some(max);
+        self
+    }
+
+    /// Send this request without `Accept-Encoding` and leave its response undecoded
+    ///
+    /// An escape hatch for servers that mangle compressed bodies: any
+    /// `Accept-Encoding` from the client's defaults or set on this request
+    /// is removed, so a well-behaved server answers with the identity
+    /// encoding, and the body comes back exactly as sent even if the server
+    /// compresses it anyway. Other requests from the client are unaffected.
+    pub fn no_accept_encoding(mut self) -> Self {
+        self.options.no_accept_encoding = true;
+        self
+    }
+
+    /// Keep this request and its response away from the client's cookie jar
+    ///
+    /// No cookies are read from the jar for the request, including when
+    /// its response is followed with `Response::follow_redirect`, and the
+    /// response's `Set-Cookie` headers aren't stored. Use it for requests
+    /// to third-party hosts, such as a CDN, that shouldn't see or set
+    /// session cookies. A `Cookie` header set explicitly is still sent.
+    pub fn no_cookies(mut self) -> Self {
+        self.options.no_cookies = true;
+        self
+    }
+
+    /// Return 4xx and 5xx responses for this request even with the client's `error_on_status`
+    pub fn allow_error_status(mut self) -> Self {
+        self.options.error_on_status = Some(false);
+        self
+    }
+
+    /// Throttle the body upload to `bytes_per_sec`
+    ///
+    /// The body is sent in small chunks paced by a token bucket, for
+    /// testing slow clients or staying under an upload cap. In-memory
+    /// bodies and [`file_body`](Self::file_body) are throttled, whichever
+    /// order the calls come in. A throttled body is a stream, so it isn't
+    /// held back by `expect_continue` or replayed by retries. reqwest
+    /// doesn't expose the stream behind a multipart form, so `multipart`
+    /// bodies are sent at full speed. Zero removes the limit.
+    pub fn upload_bandwidth_limit(self, bytes_per_sec: u64) -> Self {
+        self.options.upload_limit.set(bytes_per_sec);
+        self
+    }
+
+    /// Present `identity` as the TLS client certificate for this request
+    ///
+    /// The client keeps one connector per identity, built from its own
+    /// configuration the first time the identity is used. Connectors don't
+    /// share connections, so the first request with each identity makes a
+    /// new TLS handshake, and idle connections are kept per identity. Only
+    /// requests created from a [`Client`](crate::Client) can choose an identity.
+    pub fn client_identity(mut self, identity: ClientIdentity) -> Self {
+        self.options.client_identity = Some(identity);
+        self
+    }
+
+    /// Run `chain` for this request only, after the client's middleware
+    ///
+    /// The client's chain is left as it is, so other requests from the
+    /// same client don't see this middleware. Handy for per-call auth or
+    /// logging overrides.
+    pub fn with_middleware(mut self, chain: MiddlewareChain) -> Self {
+        let chain = match self.options.middleware.take() {
+            Some(earlier) => earlier.then(chain),
+            None => chain,
+        };
+        self.options.middleware = Some(chain);
+        self
+    }
+
+    /// Override the `Host` header without changing where the request connects
+    ///
+    /// Useful for virtual hosts: combined with `ClientBuilder::resolve`, a
+    /// request can reach `127.0.0.1` while sending `Host: api.example.com`.
+    /// TLS SNI still uses the URL's host. An internationalized name is
+    /// sent in its punycode form, and a malformed one fails with
+    /// `Error::Url`.
+    pub fn host(self, host: &str) -> Result<Self> {
+        let host = ascii_host(host)?;
+        self.header("Host", &host)
+    }
+
+    /// Set `X-Forwarded-For` to `ip`, as a proxy in front of the server would
+    ///
+    /// Meant for testing services that trust proxy headers. The address
+    /// must be a well-formed IPv4 or IPv6 address; anything else fails with
+    /// `Error::InvalidRequest`. A later call replaces the header.
+    pub fn forwarded_for(self, ip: &str) -> Result<Self> {
+        let ip: std::net::IpAddr = ip
+            .trim()
+            .parse()
+            .map_err(|_| Error::invalid_request(format!("Invalid IP address for X-Forwarded-For: {}", ip)))?;
+        self.header("X-Forwarded-For", &ip.to_string())
+    }
+
+    /// Set the standard `Forwarded` header (RFC 7239)
+    ///
+    /// `value` is a list of `name=value` pairs such as
+    /// `for=192.0.2.60;proto=https, for="[2001:db8::1]"`. Each value must be
+    /// a token or a quoted string, and `for` and `by` must name an address
+    /// (optionally with a port), `unknown`, or an obfuscated `_identifier`.
+    pub fn forwarded(self, value: &str) -> Result<Self> {
+        validate_forwarded(value)?;
+        self.header("Forwarded", value)
+    }
+
     /// Set version
     pub fn version(mut self, version: http::Version) -> Self {
-        self.reqwest_builder = self.reqwest_builder.version(version);
+        self.options.version = Some(version);
         self
     }
 
     /// Build the request
     pub fn build(self) -> Result<Request> {
-        let reqwest_request = self.reqwest_builder
-            .build()
-            .map_err(Error::Network)?;
+        self.build_split().1
+    }
 
-        let method = reqwest_request.method().clone();
-        let url = reqwest_request.url().clone();
-        let headers = reqwest_request.headers().clone();
-        let body = reqwest_request.body()
-            .and_then(|b| b.as_bytes())
-            .map(|bytes| RequestBody::Bytes(bytes.to_vec()))
-            .unwrap_or(RequestBody::Empty);
+    /// Build the request, keeping the client settings it's sent with
+    fn build_split(self) -> (SendContext, Result<Request>) {
+        let request = self.reqwest_builder.build().map_err(Error::Network).map(|mut reqwest_request| {
+            let method = reqwest_request.method().clone();
+            let url = reqwest_request.url().clone();
+            let headers = reqwest_request.headers().clone();
+            // Multipart forms and other streaming bodies have no bytes to copy
+            let body = match reqwest_request.body_mut().take() {
+                Some(body) => match body.as_bytes() {
+                    Some(bytes) => RequestBody::Bytes(bytes.to_vec()),
+                    None => RequestBody::Stream(StreamBody::new(body)),
+                },
+                None => RequestBody::Empty,
+            };
 
-        Ok(Request {
-            method,
-            url,
-            headers,
-            body: Some(body),
-            timeout_config: self.timeout_config,
-        })
+            Request {
+                method,
+                url,
+                headers,
+                body: Some(body),
+                timeout_config: self.timeout_config,
+                options: self.options,
+                extensions: self.extensions,
+            }
+        });
+        (self.context, request)
     }
 
     /// Send the request and return the response
     pub async fn send(self) -> Result<Response> {
-        let reqwest_response = self.reqwest_builder
-            .send()
-            .await
-            .map_err(Error::Network)?;
-
-        Response::from_reqwest_response(reqwest_response, self.cookie_jar).await
+        let (context, request) = self.build_split();
+        context.send(request?, true).await
     }
 
     /// Send the request and return JSON response
@@ -440,6 +1117,553 @@ impl RequestBuilder {
     }
 }
 
+/// Client settings a request is sent with
+///
+/// `RequestBuilder::send` and `Client::send` both end up in
+/// `SendContext::send`, so a request goes through the same steps whichever
+/// way it's sent.
+#[derive(Clone)]
+pub(crate) struct SendContext {
+    pub(crate) client: Arc<reqwest::Client>,
+    pub(crate) cookie_jar: Arc<CookieJar>,
+    pub(crate) connectors: Option<Arc<Connectors>>,
+    pub(crate) concurrency_limit: Option<Arc<Semaphore>>,
+    pub(crate) circuit_breaker: Option<Arc<CircuitBreaker>>,
+    pub(crate) trace_propagation: bool,
+    pub(crate) response_cache: Option<Arc<ResponseCache>>,
+    pub(crate) accept_encodings: Option<Arc<[Encoding]>>,
+    pub(crate) retry: Option<Arc<RetryConfig>>,
+    pub(crate) rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    pub(crate) middleware: Option<Arc<MiddlewareChain>>,
+    pub(crate) lifecycle_log: Option<log::Level>,
+    pub(crate) redirects: Option<Redirects>,
+    pub(crate) header_limits: HeaderLimits,
+    pub(crate) stream_idle_timeout: Option<Duration>,
+    pub(crate) detect_charset: bool,
+    pub(crate) body_pipeline: Option<Arc<BodyPipeline>>,
+    pub(crate) error_on_status: bool,
+    pub(crate) last_request: Option<LastRequest>,
+    #[cfg(feature = "ntlm")]
+    pub(crate) ntlm: Option<Arc<NtlmCredentials>>,
+}
+
+impl SendContext {
+    /// Send with `client` and `cookie_jar` and nothing else configured
+    pub(crate) fn new(client: Arc<reqwest::Client>, cookie_jar: Arc<CookieJar>) -> Self {
+        Self {
+            client,
+            cookie_jar,
+            connectors: None,
+            concurrency_limit: None,
+            circuit_breaker: None,
+            trace_propagation: false,
+            response_cache: None,
+            accept_encodings: None,
+            retry: None,
+            rate_limiter: None,
+            middleware: None,
+            lifecycle_log: None,
+            redirects: None,
+            header_limits: HeaderLimits::default(),
+            stream_idle_timeout: None,
+            detect_charset: false,
+            body_pipeline: None,
+            error_on_status: false,
+            last_request: None,
+            #[cfg(feature = "ntlm")]
+            ntlm: None,
+        }
+    }
+
+    /// Send `request`, following redirects as configured or returning them
+    pub(crate) async fn send(&self, request: Request, follow_redirects: bool) -> Result<Response> {
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let send = self.send_inner(request, follow_redirects);
+        let send = trace::log_lifecycle(self.lifecycle_log, &method, &url, send);
+        trace::instrument(&method, &url, send).await
+    }
+
+    async fn send_inner(&self, mut request: Request, follow_redirects: bool) -> Result<Response> {
+        let _permit = acquire_permit(self.concurrency_limit.as_ref()).await?;
+        let options = request.options.clone();
+        let mut extensions = std::mem::take(request.extensions_mut());
+        let mut reqwest_request = request.into_reqwest_request()?;
+        if options.no_accept_encoding {
+            reqwest_request.headers_mut().remove(http::header::ACCEPT_ENCODING);
+        }
+        record_request(self.last_request.as_ref(), &reqwest_request);
+        let connector = ConnectorOptions {
+            identity: options.client_identity.clone(),
+            connect_timeout: options.connect_timeout,
+            no_redirects: !follow_redirects,
+            no_pooling: options.close_connection,
+            absolute_form: options.absolute_form,
+        };
+        let client = match &self.connectors {
+            _ if connector == ConnectorOptions::default() => self.client.as_ref().clone(),
+            Some(connectors) => connectors.get(connector)?,
+            None if connector.identity.is_some() => {
+                return Err(Error::tls("Client identities require a request created from a Client"));
+            }
+            None if connector.connect_timeout.is_some() => {
+                return Err(Error::config("Connect timeouts require a request created from a Client"));
+            }
+            None if connector.absolute_form => {
+                return Err(Error::config("Absolute-form targets require a request created from a Client"));
+            }
+            // Only the header asks for the connection to be closed
+            None => self.client.as_ref().clone(),
+        };
+        let redirects = self.redirects.filter(|_| follow_redirects);
+        // Each send without cookies gets a jar of its own, used only to follow its redirects
+        let cookie_jar = match options.no_cookies {
+            true => Arc::new(CookieJar::new()),
+            false => self.cookie_jar.clone(),
+        };
+        // The request's own middleware runs after the client's
+        let chain = match (&self.middleware, options.middleware) {
+            (Some(client_chain), Some(chain)) => Some(Arc::new(client_chain.then(chain))),
+            (None, Some(chain)) => Some(Arc::new(chain)),
+            (client_chain, None) => client_chain.clone(),
+        };
+        if let Some(chain) = &chain {
+            if let Some(response) = middleware::before_send(chain, &mut reqwest_request, &mut extensions).await? {
+                let url = reqwest_request.url().clone();
+                return middleware::short_circuit(response, url, cookie_jar);
+            }
+        }
+        if let Some(max) = options.max_body_size {
+            check_body_size(&reqwest_request, max)?;
+        }
+        let url = reqwest_request.url().clone();
+        if self.trace_propagation {
+            trace::inject(&mut reqwest_request, extensions.get::<TraceContext>());
+        }
+        let cache = self.response_cache.as_deref();
+        let cache_key = cache.and_then(|c| c.key_for(&reqwest_request));
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            if let Some(mut response) = cache.get(key, cookie_jar.clone()) {
+                // Middleware that saw the request sees its response too
+                if let Some(chain) = &chain {
+                    response = middleware::after_receive(chain, response, extensions, cookie_jar).await?;
+                }
+                return Ok(response
+                    .with_detect_charset(self.detect_charset)
+                    .with_body_pipeline(self.body_pipeline.clone()));
+            }
+        }
+        if options.chunked {
+            apply_chunked(&mut reqwest_request, &options.upload_limit);
+        } else if options.upload_limit.get().is_some() {
+            apply_upload_limit(&mut reqwest_request, &options.upload_limit);
+        }
+        let body_gate = if options.expect_continue {
+            apply_expect_continue(&mut reqwest_request)
+        } else {
+            None
+        };
+        let breaker = self.circuit_breaker.as_deref();
+        let execute = |request| {
+            redirect::follow(redirects, request, |request| {
+                circuit::guard(breaker, &url, client.execute(request))
+            })
+        };
+        let send = |request| {
+            retry::send(self.retry.as_deref(), self.rate_limiter.as_deref(), request, &execute)
+        };
+        #[cfg(feature = "ntlm")]
+        let reqwest_response = ntlm::handshake(self.ntlm.as_deref(), reqwest_request, send).await?;
+        #[cfg(not(feature = "ntlm"))]
+        let reqwest_response = send(reqwest_request).await?;
+        // A final response has arrived, so a body still being held back is abandoned
+        drop(body_gate);
+        self.header_limits.check(reqwest_response.headers())?;
+
+        let mut response = Response::from_reqwest_response(reqwest_response, cookie_jar.clone())
+            .await?
+            .with_stream_idle_timeout(self.stream_idle_timeout);
+        if let Some(encodings) = self.accept_encodings.as_ref().filter(|_| !options.no_accept_encoding) {
+            response = compression::decode_response(response, encodings, cookie_jar.clone()).await?;
+        }
+        // Cached as received, so hits go through the response middleware like fresh responses
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            response = cache.store(key, response, cookie_jar.clone()).await?;
+        }
+        if let Some(chain) = &chain {
+            response = middleware::after_receive(chain, response, extensions, cookie_jar).await?;
+        }
+        let response = response.with_detect_charset(self.detect_charset);
+        // The cache keeps bodies as received, so the pipeline runs on every read
+        let response = response.with_body_pipeline(self.body_pipeline.clone());
+        if options.error_on_status.unwrap_or(self.error_on_status) {
+            return response.error_for_status_with_body().await;
+        }
+        Ok(response)
+    }
+}
+
+/// Add `Expect: 100-continue` and delay the body until the expectation window passes
+///
+/// Dropping the returned sender before the window elapses aborts the body.
+fn apply_expect_continue(request: &mut ReqwestRequest) -> Option<oneshot::Sender<()>> {
+    let bytes = match request.body().and_then(|b| b.as_bytes()) {
+        Some(bytes) if !bytes.is_empty() => bytes.to_vec(),
+        _ => return None,
+    };
+
+    let headers = request.headers_mut();
+    headers.insert(http::header::EXPECT, HeaderValue::from_static("100-continue"));
+    headers.insert(http::header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+
+    let (gate, response_received) = oneshot::channel();
+    let delayed = futures::stream::once(async move {
+        tokio::select! {
+            _ = tokio::time::sleep(EXPECT_CONTINUE_TIMEOUT) => Ok(bytes),
+            _ = response_received => Err(std::io::Error::other(
+                "final response received before the body was sent",
+            )),
+        }
+    });
+    *request.body_mut() = Some(reqwest::Body::wrap_stream(delayed));
+    Some(gate)
+}
+
+/// Fail if the request's body is known to be larger than `max` bytes
+fn check_body_size(request: &ReqwestRequest, max: usize) -> Result<()> {
+    let size = match request.body().map(|body| body.as_bytes()) {
+        None => Some(0),
+        Some(Some(bytes)) => Some(bytes.len()),
+        Some(None) => request
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok()),
+    };
+    match size {
+        Some(size) if size > max => Err(Error::invalid_request(format!(
+            "Request body is {} bytes, over the {} byte limit",
+            size, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Turn an in-memory body into a stream so it is sent without a length
+fn apply_chunked(request: &mut ReqwestRequest, limit: &BandwidthLimit) {
+    let bytes = match request.body().and_then(|b| b.as_bytes()) {
+        Some(bytes) => bytes.to_vec(),
+        None => return,
+    };
+
+    request.headers_mut().remove(http::header::CONTENT_LENGTH);
+    let stream = futures::stream::once(async move { Ok::<_, std::io::Error>(bytes) });
+    *request.body_mut() = Some(reqwest::Body::wrap_stream(throttle::throttle(stream, limit.clone())));
+}
+
+/// Turn an in-memory body into a throttled stream, keeping its length
+fn apply_upload_limit(request: &mut ReqwestRequest, limit: &BandwidthLimit) {
+    let bytes = match request.body().and_then(|b| b.as_bytes()) {
+        Some(bytes) => bytes.to_vec(),
+        None => return,
+    };
+
+    request.headers_mut().insert(http::header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+    let stream = futures::stream::iter([Ok::<_, std::io::Error>(bytes)]);
+    *request.body_mut() = Some(reqwest::Body::wrap_stream(throttle::throttle(stream, limit.clone())));
+}
+
+/// Read a file as a stream of chunks
+fn file_chunks(file: tokio::fs::File) -> impl futures::Stream<Item = std::io::Result<Vec<u8>>> {
+    use tokio::io::AsyncReadExt;
+
+    futures::stream::try_unfold(file, |mut file| async move {
+        let mut chunk = vec![0; 64 * 1024];
+        let n = file.read(&mut chunk).await?;
+        chunk.truncate(n);
+        Ok((n > 0).then_some((chunk, file)))
+    })
+}
+
+/// Characters re-encoded when normalizing URL paths and queries
+const NORMALIZE_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'|')
+    .add(b'\\')
+    .add(b'^');
+
+/// Normalize the percent-encoding of a URL's path and query
+///
+/// Unsafe characters are percent-encoded, existing escapes are uppercased
+/// (`%2f` becomes `%2F`) and stray `%` signs are encoded as `%25`.
+pub fn normalize_url(url: &Url) -> Url {
+    let mut normalized = url.clone();
+    if !url.cannot_be_a_base() {
+        normalized.set_path(&normalize_component(url.path()));
+    }
+    if let Some(query) = url.query() {
+        normalized.set_query(Some(&normalize_component(query)));
+    }
+    normalized
+}
+
+/// Check the syntax of a `Forwarded` header value
+fn validate_forwarded(value: &str) -> Result<()> {
+    let invalid = |reason: &str| Error::invalid_request(format!("Invalid Forwarded header '{}': {}", value, reason));
+    let is_token = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+
+    for element in value.split(',') {
+        for pair in element.split(';') {
+            let (name, param) = pair.trim().split_once('=').ok_or_else(|| invalid("expected name=value"))?;
+            if !is_token(name) {
+                return Err(invalid("parameter names must be tokens"));
+            }
+            let param = match param.strip_prefix('"') {
+                Some(quoted) => quoted.strip_suffix('"').ok_or_else(|| invalid("unterminated quoted string"))?,
+                None if is_token(param) => param,
+                None => return Err(invalid("values must be tokens or quoted strings")),
+            };
+            if name.eq_ignore_ascii_case("for") || name.eq_ignore_ascii_case("by") {
+                // IPv6 addresses are bracketed, and any node may carry a port;
+                // both need quoting, which the token check above enforces
+                let node = match param.strip_prefix('[') {
+                    Some(v6) => v6.split_once(']').map(|(addr, _)| addr).ok_or_else(|| invalid("unclosed '['"))?,
+                    None => param.split(':').next().unwrap_or_default(),
+                };
+                let valid = node.parse::<std::net::IpAddr>().is_ok()
+                    || node.eq_ignore_ascii_case("unknown")
+                    || node.starts_with('_');
+                if !valid {
+                    return Err(invalid("'for' and 'by' must name an address, 'unknown' or an '_identifier'"));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serialize `value` as canonical JSON
+///
+/// Object keys are sorted lexicographically by their UTF-8 bytes, at every
+/// level, and tokens are separated without whitespace. Strings and numbers
+/// are written as `serde_json` writes them.
+pub fn canonical_json<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: serde::Serialize,
+{
+    Ok(serde_json::to_vec(&sort_keys(&serde_json::to_value(value)?))?)
+}
+
+/// Convert a host, with an optional port, to its ASCII form
+///
+/// Internationalized names become punycode (`例え.jp` becomes
+/// `xn--r8jz45g.jp`), as `Url` already does for the hosts of parsed URLs.
+/// IP addresses are returned as-is.
+pub(crate) fn ascii_host(host: &str) -> Result<String> {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(']') && port.bytes().all(|b| b.is_ascii_digit()) => (name, Some(port)),
+        _ => (host, None),
+    };
+    if name.starts_with('[') || name.parse::<std::net::IpAddr>().is_ok() {
+        return Ok(host.to_string());
+    }
+    let name = idna::domain_to_ascii(name).map_err(|_| Error::Url(url::ParseError::IdnaError))?;
+    Ok(match port {
+        Some(port) => format!("{}:{}", name, port),
+        None => name,
+    })
+}
+
+/// Format a URL for display, with an internationalized host in Unicode
+///
+/// `Url` only holds hosts in their ASCII form, which is what is sent on
+/// the wire. This turns `https://xn--r8jz45g.jp/` back into
+/// `https://例え.jp/` for logs and user-facing output.
+pub fn display_url(url: &Url) -> String {
+    let Some(host) = url.host_str() else {
+        return url.to_string();
+    };
+    match idna::domain_to_unicode(host) {
+        (unicode, Ok(())) if unicode != host => format!(
+            "{}{}{}",
+            &url[..url::Position::BeforeHost],
+            unicode,
+            &url[url::Position::AfterHost..]
+        ),
+        _ => url.to_string(),
+    }
+}
+
+fn normalize_component(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut pending = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        output.extend(percent_encode(&bytes[pending..i], NORMALIZE_ENCODE_SET));
+        let is_escape = bytes.len() > i + 2
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+        if is_escape {
+            output.push('%');
+            output.push(bytes[i + 1].to_ascii_uppercase() as char);
+            output.push(bytes[i + 2].to_ascii_uppercase() as char);
+            i += 3;
+        } else {
+            output.push_str("%25");
+            i += 1;
+        }
+        pending = i;
+    }
+    output.extend(percent_encode(&bytes[pending..], NORMALIZE_ENCODE_SET));
+    output
+}
+
+/// Request parsed from raw HTTP text
+#[derive(Debug)]
+pub(crate) struct RawRequest {
+    pub(crate) method: Method,
+    pub(crate) url: Url,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: String,
+}
+
+/// Parse a raw HTTP request: request line, headers, blank line, body
+///
+/// The target may be an absolute URL, or a path resolved against the `Host`
+/// header (as `http://`) or else against `base`. Leading blank lines and
+/// `#`/`//` comment lines are skipped, as in `.http` files.
+pub(crate) fn parse_raw_request(raw: &str, base: Option<&Url>) -> Result<RawRequest> {
+    let normalized = raw.replace("\r\n", "\n");
+    let mut lines = normalized.split('\n');
+
+    let request_line = lines
+        .by_ref()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"))
+        .ok_or_else(|| Error::invalid_request("Raw request is empty"))?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts
+        .next()
+        .and_then(|m| Method::from_bytes(m.as_bytes()).ok())
+        .ok_or_else(|| Error::invalid_request(format!("Invalid request line: {}", request_line)))?;
+    let target = parts
+        .next()
+        .ok_or_else(|| Error::invalid_request(format!("Missing request target: {}", request_line)))?;
+    if let Some(version) = parts.next() {
+        if !version.starts_with("HTTP/") || parts.next().is_some() {
+            return Err(Error::invalid_request(format!("Invalid request line: {}", request_line)));
+        }
+    }
+
+    let mut headers = Vec::new();
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| Error::invalid_request(format!("Invalid header line: {}", line)))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let body = lines.collect::<Vec<_>>().join("\n").trim_end_matches('\n').to_string();
+
+    let host = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+        .map(|(_, value)| value.as_str());
+    let url = if target.starts_with("http://") || target.starts_with("https://") {
+        Url::parse(target)?
+    } else if let (Some(host), true) = (host, target.starts_with('/')) {
+        Url::parse(&format!("http://{}{}", host, target))?
+    } else if let Some(base) = base {
+        base.join(target)?
+    } else {
+        return Err(Error::invalid_request(format!(
+            "Cannot resolve request target without a Host header: {}",
+            target
+        )));
+    };
+
+    Ok(RawRequest {
+        method,
+        url,
+        headers,
+        body,
+    })
+}
+
+/// Build a `Basic` authorization header value
+fn basic_auth_header(username: &str, password: Option<&str>) -> HeaderValue {
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+
+    let credentials = format!("{}:{}", username, password.unwrap_or_default());
+    let mut value = HeaderValue::from_str(&format!("Basic {}", BASE64.encode(credentials)))
+        .expect("base64 is a valid header value");
+    value.set_sensitive(true);
+    value
+}
+
+/// Remove userinfo from a URL, returning it as a `Basic` authorization value
+///
+/// This keeps credentials out of logs and traces of the URL.
+fn take_url_credentials(url: &mut Url) -> Option<HeaderValue> {
+    if url.username().is_empty() && url.password().is_none() {
+        return None;
+    }
+    let decode = |s: &str| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned();
+    let username = decode(url.username());
+    let password = url.password().map(decode);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    Some(basic_auth_header(&username, password.as_deref()))
+}
+
+/// Guess a content type from the start of a text body
+fn infer_content_type(content: &str) -> &'static str {
+    let trimmed = content.trim_start();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        if serde_json::from_str::<Value>(content).is_ok() {
+            return "application/json";
+        }
+    } else if trimmed.starts_with('<') {
+        let lower = trimmed.get(..15).unwrap_or(trimmed).to_ascii_lowercase();
+        if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+            return "text/html";
+        }
+        return "application/xml";
+    }
+    "text/plain"
+}
+
+/// Wait for a permit from an optional concurrency limiter
+///
+/// The permit is released when dropped, so holding it across the send
+/// releases it on success, error and timeout alike.
+pub(crate) async fn acquire_permit(limit: Option<&Arc<Semaphore>>) -> Result<Option<SemaphorePermit<'_>>> {
+    match limit {
+        Some(semaphore) => semaphore
+            .acquire()
+            .await
+            .map(Some)
+            .map_err(|_| Error::custom("Concurrency limiter closed")),
+        None => Ok(None),
+    }
+}
+
 impl std::fmt::Debug for Request {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Request")
@@ -460,6 +1684,7 @@ impl std::fmt::Debug for RequestBody {
             RequestBody::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
             RequestBody::Form(data) => write!(f, "Form({} pairs)", data.len()),
             RequestBody::Multipart(parts) => write!(f, "Multipart({} parts)", parts.len()),
+            RequestBody::Stream(_) => write!(f, "Stream"),
         }
     }
 }
@@ -468,6 +1693,91 @@ impl std::fmt::Debug for RequestBody {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_host_override() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::{header, method};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("host", "api.example.com"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let url: Url = server.uri().parse().unwrap();
+        let response = crate::Client::new()
+            .get(url)
+            .host("api.example.com")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(response.is_success());
+
+        // With a resolve override the URL can name the virtual host directly
+        let client = crate::Client::builder()
+            .resolve("api.example.com", *server.address())
+            .build();
+        let url: Url = format!("http://api.example.com:{}/", server.address().port()).parse().unwrap();
+        let response = client.get(url).host("api.example.com").unwrap().send().await.unwrap();
+        assert!(response.is_success());
+    }
+
+    #[test]
+    fn test_parse_raw_get() {
+        let raw = "# fetch a user\nGET /users/1 HTTP/1.1\nHost: api.example.com\nAccept: application/json\n";
+        let request = parse_raw_request(raw, None).unwrap();
+        assert_eq!(request.method, Method::GET);
+        assert_eq!(request.url.as_str(), "http://api.example.com/users/1");
+        assert_eq!(request.headers.len(), 2);
+        assert_eq!(request.headers[1], ("Accept".to_string(), "application/json".to_string()));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn test_parse_raw_post_with_body() {
+        let raw = "POST https://example.com/items\r\nContent-Type: application/json\r\n\r\n{\"name\": \"widget\"}\r\n";
+        let request = parse_raw_request(raw, None).unwrap();
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.url.as_str(), "https://example.com/items");
+        assert_eq!(request.body, "{\"name\": \"widget\"}");
+
+        assert!(parse_raw_request("", None).is_err());
+        assert!(parse_raw_request("GET /relative", None).is_err());
+        assert!(parse_raw_request("GET / HTTP/1.1\nno colon here\n", None).is_err());
+    }
+
+    #[test]
+    fn test_request_try_clone() {
+        let url = Url::parse("http://example.com/").unwrap();
+
+        let request = Request::new(Method::POST, url.clone())
+            .json(&serde_json::json!({"id": 1}))
+            .unwrap();
+        let cloned = request.try_clone().unwrap();
+        assert!(matches!(cloned.body(), Some(RequestBody::Json(_))));
+
+        let chunks = futures::stream::iter(vec![Ok::<_, std::io::Error>(b"chunk".to_vec())]);
+        let request = Request::new(Method::POST, url)
+            .body_stream(StreamBody::new(reqwest::Body::wrap_stream(chunks)));
+        assert!(request.try_clone().is_none());
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        let url = Url::parse("http://example.com/a b/c?q=x y").unwrap();
+        let normalized = normalize_url(&url);
+        assert_eq!(normalized.path(), "/a%20b/c");
+        assert_eq!(normalized.query(), Some("q=x%20y"));
+
+        let url = Url::parse("http://example.com/files/a%2fb?path=%2fetc%zz").unwrap();
+        let normalized = normalize_url(&url);
+        assert_eq!(normalized.path(), "/files/a%2Fb");
+        assert_eq!(normalized.query(), Some("path=%2Fetc%25zz"));
+    }
+
     #[test]
     fn test_request_creation() {
         let url = "https://httpbin.org/get".parse().unwrap();
@@ -492,4 +1802,670 @@ mod tests {
         
         assert_eq!(builder.method(), &Method::GET);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_body_auto_content_type() {
+        let client = crate::Client::new();
+        let url: Url = "http://example.com/".parse().unwrap();
+        let content_type = |builder: RequestBuilder| {
+            let request = builder.build().unwrap();
+            request.headers().get("content-type").unwrap().to_str().unwrap().to_string()
+        };
+
+        let json = client.post(url.clone()).body_auto(" {\"name\": \"ada\"}").unwrap();
+        assert_eq!(content_type(json), "application/json");
+        let xml = client.post(url.clone()).body_auto("<?xml version=\"1.0\"?><note/>").unwrap();
+        assert_eq!(content_type(xml), "application/xml");
+        let html = client.post(url.clone()).body_auto("<!DOCTYPE html><p>hi</p>").unwrap();
+        assert_eq!(content_type(html), "text/html");
+        let text = client.post(url.clone()).body_auto("[not json").unwrap();
+        assert_eq!(content_type(text), "text/plain");
+
+        // An explicit content type wins
+        let explicit = client
+            .post(url)
+            .content_type("application/vnd.api+json")
+            .unwrap()
+            .body_auto("{}")
+            .unwrap();
+        assert_eq!(content_type(explicit), "application/vnd.api+json");
+    }
+
+    /// Read from the socket until the end of the request head
+    async fn read_head(socket: &mut tokio::net::TcpStream) -> String {
+        use tokio::io::AsyncReadExt;
+
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            if socket.read(&mut buf).await.unwrap() == 0 {
+                break;
+            }
+            head.push(buf[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_expect_continue_rejected_skips_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let head = read_head(&mut socket).await.to_lowercase();
+            socket
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            // Give the client a chance to (wrongly) send the body
+            let mut body = Vec::new();
+            let _ = tokio::time::timeout(
+                EXPECT_CONTINUE_TIMEOUT * 2,
+                socket.read_to_end(&mut body),
+            )
+            .await;
+            (head, body)
+        });
+
+        let client = crate::Client::new();
+        let url: Url = format!("http://{}/upload", addr).parse().unwrap();
+        let response = client
+            .put(url)
+            .bytes(vec![b'x'; 64 * 1024])
+            .unwrap()
+            .expect_continue(true)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+        drop(response);
+        drop(client);
+
+        let (head, body) = server.await.unwrap();
+        assert!(head.contains("expect: 100-continue"));
+        assert!(body.is_empty(), "body was sent: {} bytes", body.len());
+    }
+
+    #[tokio::test]
+    async fn test_expect_continue_accepted_sends_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            read_head(&mut socket).await;
+            socket.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await.unwrap();
+            let mut body = vec![0u8; 5];
+            socket.read_exact(&mut body).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                String::from_utf8(body).unwrap()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let client = crate::Client::new();
+        let url: Url = format!("http://{}/upload", addr).parse().unwrap();
+        let body = client
+            .post(url)
+            .text("hello")
+            .unwrap()
+            .expect_continue(true)
+            .send_text()
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_chunked_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for _ in 0..3 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let head = read_head(&mut socket).await.to_ascii_lowercase();
+                let framing = if head.contains("transfer-encoding: chunked\r\n") {
+                    let mut body = Vec::new();
+                    while !body.ends_with(b"0\r\n\r\n") {
+                        body.push(socket.read_u8().await.unwrap());
+                    }
+                    "chunked"
+                } else {
+                    let mut body = [0u8; 5];
+                    socket.read_exact(&mut body).await.unwrap();
+                    "length"
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    framing.len(),
+                    framing
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = crate::Client::new();
+        let url: Url = format!("http://{}/upload", addr).parse().unwrap();
+        let send = |chunked: bool| {
+            client.post(url.clone()).text("hello").unwrap().chunked(chunked).send_text()
+        };
+
+        assert_eq!(send(true).await.unwrap(), "chunked");
+        assert_eq!(send(false).await.unwrap(), "length");
+
+        // The option travels with a built request to Client::send
+        let request = client.post(url.clone()).text("hello").unwrap().chunked(true).build().unwrap();
+        assert_eq!(client.send(request).await.unwrap().text().await.unwrap(), "chunked");
+    }
+
+    #[tokio::test]
+    async fn test_max_body_size() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+        let body = serde_json::json!({"data": "x".repeat(100)});
+
+        let result = client.post(url.clone()).json(&body).unwrap().max_body_size(64).send().await;
+        assert!(matches!(result, Err(Error::InvalidRequest(_))), "{:?}", result.err());
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        // A built request keeps the limit when sent through the client
+        let request = client.post(url.clone()).json(&body).unwrap().max_body_size(64).build().unwrap();
+        let result = client.send(request).await;
+        assert!(matches!(result, Err(Error::InvalidRequest(_))), "{:?}", result.err());
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        let response = client.post(url).json(&body).unwrap().max_body_size(1024).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_absolute_form() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        // A strict proxy: only absolute-form targets are accepted
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let targets = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = targets.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let seen = seen.clone();
+                tokio::spawn(async move {
+                    let mut socket = BufReader::new(socket);
+                    let mut request_line = String::new();
+                    socket.read_line(&mut request_line).await.unwrap();
+                    let mut line = String::new();
+                    while line != "\r\n" {
+                        line.clear();
+                        socket.read_line(&mut line).await.unwrap();
+                    }
+                    let target = request_line.split(' ').nth(1).unwrap().to_string();
+                    let status = if target.starts_with("http://") { "200 OK" } else { "400 Bad Request" };
+                    seen.lock().unwrap().push(target);
+                    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                    let _ = socket.get_mut().write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        let url: Url = format!("http://{}/status?q=1", addr).parse().unwrap();
+
+        let client = crate::Client::new();
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        let response = client.get(url.clone()).absolute_form(true).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        // Through a configured proxy, targets are absolute-form anyway
+        let proxy: Url = format!("http://{}", addr).parse().unwrap();
+        let client = crate::Client::builder()
+            .proxy_config(crate::proxy::ProxyConfig::new().http_proxy(proxy))
+            .build();
+        let response = client.get("http://origin.test/page".parse::<Url>().unwrap()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let targets = targets.lock().unwrap().clone();
+        assert_eq!(
+            targets,
+            vec![
+                "/status?q=1".to_string(),
+                format!("http://{}/status?q=1", addr),
+                "http://origin.test/page".to_string(),
+            ]
+        );
+
+        let request = RequestBuilder::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(CookieJar::new()),
+            Method::GET,
+            url,
+            TimeoutConfig::default(),
+            HeaderMap::new(),
+        );
+        let result = request.absolute_form(true).send().await;
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_no_accept_encoding() {
+        use crate::compression::Encoding;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        // Claims gzip but sends something else, like a misbehaving server
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_bytes(b"not really gzip".to_vec()),
+            )
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let client = crate::Client::builder().accept_encodings(&[Encoding::Gzip]).build();
+
+        assert!(client.get(url.clone()).send().await.is_err());
+
+        let response = client.get(url).no_accept_encoding().send().await.unwrap();
+        assert_eq!(response.header("content-encoding").unwrap(), "gzip");
+        assert_eq!(response.bytes().await.unwrap(), b"not really gzip");
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[0].headers.contains_key(&"accept-encoding".into()));
+        assert!(!requests[1].headers.contains_key(&"accept-encoding".into()));
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_headers() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+
+        client
+            .get(url.clone())
+            .forwarded_for("203.0.113.7")
+            .unwrap()
+            .forwarded(r#"for="[2001:db8::1]:4711";proto=https, for=unknown;by=_proxy"#)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let requests = server.received_requests().await.unwrap();
+        // wiremock splits header values on commas
+        let header = |name: &str| {
+            let values = requests[0].headers.get(&name.into())?;
+            Some(values.iter().map(|v| v.as_str()).collect::<Vec<_>>().join(", "))
+        };
+        assert_eq!(header("x-forwarded-for").as_deref(), Some("203.0.113.7"));
+        assert_eq!(
+            header("forwarded").as_deref(),
+            Some(r#"for="[2001:db8::1]:4711";proto=https, for=unknown;by=_proxy"#)
+        );
+
+        assert!(client.get(url.clone()).forwarded_for("::1").is_ok());
+        for ip in ["203.0.113", "example.com", "1.2.3.4, 5.6.7.8", ""] {
+            let result = client.get(url.clone()).forwarded_for(ip);
+            assert!(matches!(result, Err(Error::InvalidRequest(_))), "{}", ip);
+        }
+        for value in ["for=example.com", "for=[::1]", "for=\"2001:db8::1\"", "for", "for=1.2.3.4; =x", "for=\"1.2.3.4"] {
+            let result = client.get(url.clone()).forwarded(value);
+            assert!(matches!(result, Err(Error::InvalidRequest(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let request = |url: &str, body: &str| {
+            Request::new(Method::POST, url.parse().unwrap())
+                .header("Content-Type", "application/json")
+                .unwrap()
+                .json(&serde_json::from_str::<Value>(body).unwrap())
+                .unwrap()
+        };
+        let a = request("https://Example.com:443/search?b=2&a=1#top", r#"{"x": 1, "y": 2}"#);
+        let b = request("https://example.com/search?a=1&b=2", r#"{"y": 2, "x": 1}"#)
+            .header("Date", "Tue, 15 Nov 1994 08:12:31 GMT")
+            .unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().len(), 64);
+
+        // The body, method and headers all count
+        let c = request("https://example.com/search?a=1&b=2", r#"{"x": 1, "y": 3}"#);
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        let mut d = a.clone();
+        d.method = Method::PUT;
+        assert_ne!(a.fingerprint(), d.fingerprint());
+        let e = a.clone().header("Accept", "text/plain").unwrap();
+        assert_ne!(a.fingerprint(), e.fingerprint());
+
+        // Unless the caller leaves them out
+        let names = [http::header::CONTENT_TYPE];
+        assert_eq!(a.fingerprint_with(&names), e.fingerprint_with(&names));
+        assert_ne!(a.fingerprint_with(&[]), a.fingerprint());
+    }
+
+    #[tokio::test]
+    async fn test_json_canonical() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        #[derive(serde::Serialize)]
+        struct Order {
+            zebra: u32,
+            apple: Vec<u32>,
+            nested: std::collections::HashMap<&'static str, &'static str>,
+        }
+
+        let nested = |pairs: &[(&'static str, &'static str)]| pairs.iter().copied().collect();
+        let first = Order {
+            zebra: 1,
+            apple: vec![2, 3],
+            nested: nested(&[("b", "x y"), ("a", "\"q\"")]),
+        };
+        let second = serde_json::json!({
+            "nested": {"a": "\"q\"", "b": "x y"},
+            "apple": [2, 3],
+            "zebra": 1,
+        });
+        let expected = br#"{"apple":[2,3],"nested":{"a":"\"q\"","b":"x y"},"zebra":1}"#;
+        assert_eq!(canonical_json(&first).unwrap(), expected);
+        assert_eq!(canonical_json(&second).unwrap(), expected);
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+        client.post(url).json_canonical(&first).unwrap().send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].body, expected);
+        let content_type = requests[0].headers.get(&"content-type".into()).map(|v| v.as_str().to_string());
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn test_no_cookies() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(path("/start"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/next"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/next"))
+            .respond_with(ResponseTemplate::new(200).insert_header("set-cookie", "tracker=cdn; Path=/"))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let start = url.join("/start").unwrap();
+
+        let jar = CookieJar::new();
+        jar.add_cookie_from_response("session=secret; Path=/", &url);
+        let client = crate::Client::builder().cookie_jar(jar).build();
+        assert_eq!(client.cookie_jar().cookies_for_url(&url).len(), 1);
+
+        // Jar cookies are read when a redirect is followed, so step through one
+        let follow = |request: Request| {
+            let client = client.clone();
+            async move {
+                let first = client.send_no_redirect_follow(request).await.unwrap();
+                first.follow_redirect(&client).await.unwrap().unwrap();
+            }
+        };
+        follow(client.get(start.clone()).no_cookies().build().unwrap()).await;
+        assert!(!client.cookie_jar().has_cookie("tracker"));
+        // The same hop without no_cookies carries the session cookie
+        follow(client.get(start).build().unwrap()).await;
+        assert!(client.cookie_jar().has_cookie("tracker"));
+
+        let requests = server.received_requests().await.unwrap();
+        let cookie = |i: usize| requests[i].headers.get(&"cookie".into()).map(|v| v.as_str().to_string());
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[1].url.path(), "/next");
+        assert_eq!(cookie(1), None);
+        assert_eq!(requests[3].url.path(), "/next");
+        assert_eq!(cookie(3).as_deref(), Some("session=secret"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let patch = serde_json::json!({"title": "Hello!", "author": {"familyName": null}});
+        let client = crate::Client::new();
+        client.patch(url).merge_patch(&patch).unwrap().send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body, patch);
+        let content_type = requests[0].headers.get(&"content-type".into()).map(|v| v.as_str().to_string());
+        assert_eq!(content_type.as_deref(), Some("application/merge-patch+json"));
+    }
+
+    #[tokio::test]
+    async fn test_json_patch() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let patch = json::JsonPatch::new()
+            .test("/version", 3)
+            .and_then(|p| p.replace("/title", "Hello!"))
+            .and_then(|p| p.remove("/draft"))
+            .unwrap();
+        let client = crate::Client::new();
+        client.patch(url).json_patch(&patch).unwrap().send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body, serde_json::json!([
+            {"op": "test", "path": "/version", "value": 3},
+            {"op": "replace", "path": "/title", "value": "Hello!"},
+            {"op": "remove", "path": "/draft"}
+        ]));
+        let content_type = requests[0].headers.get(&"content-type".into()).map(|v| v.as_str().to_string());
+        assert_eq!(content_type.as_deref(), Some("application/json-patch+json"));
+    }
+
+    #[tokio::test]
+    async fn test_close_connection() {
+        use tokio::io::AsyncWriteExt;
+
+        // Keeps connections open, answering with the connection's number
+        // and the `Connection` header it got
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for connection in 1.. {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    loop {
+                        let head = read_head(&mut socket).await.to_ascii_lowercase();
+                        if head.is_empty() {
+                            break;
+                        }
+                        let close = head.contains("connection: close\r\n");
+                        let body = format!("{} {}", connection, if close { "close" } else { "keep-alive" });
+                        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        let client = crate::Client::new();
+        let url: Url = format!("http://{}/", addr).parse().unwrap();
+        let send = |close: bool| {
+            let request = client.get(url.clone());
+            match close {
+                true => request.close_connection().send_text(),
+                false => request.send_text(),
+            }
+        };
+        assert_eq!(send(false).await.unwrap(), "1 keep-alive");
+        assert_eq!(send(false).await.unwrap(), "1 keep-alive");
+        assert_eq!(send(true).await.unwrap(), "2 close");
+        // The closing request's connection was never pooled for reuse
+        assert_eq!(send(true).await.unwrap(), "3 close");
+        assert_eq!(send(false).await.unwrap(), "1 keep-alive");
+    }
+
+    #[tokio::test]
+    async fn test_url_credentials() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let mut url: Url = format!("{}/private", server.uri()).parse().unwrap();
+        url.set_username("ada").unwrap();
+        url.set_password(Some("p@ss word")).unwrap();
+
+        let client = crate::Client::new();
+        let builder = client.get(url.clone());
+        assert_eq!(builder.url().username(), "");
+        assert_eq!(builder.url().password(), None);
+        builder.send().await.unwrap();
+        client.send(Request::new(Method::GET, url.clone())).await.unwrap();
+        // Explicit credentials win over the URL's
+        client.get(url).basic_auth("grace", Some("hopper")).send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let authorization = |i: usize| {
+            let values = requests[i].headers.get(&"authorization".into()).unwrap();
+            assert_eq!(values.iter().count(), 1);
+            values.as_str().to_string()
+        };
+        // base64 of "ada:p@ss word"
+        assert_eq!(authorization(0), "Basic YWRhOnBAc3Mgd29yZA==");
+        assert_eq!(authorization(1), "Basic YWRhOnBAc3Mgd29yZA==");
+        assert_eq!(authorization(2), "Basic Z3JhY2U6aG9wcGVy");
+        for request in &requests {
+            assert_eq!(request.url.username(), "");
+            assert_eq!(request.url.password(), None);
+            assert_eq!(request.url.path(), "/private");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_body() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let contents = br#"{"uploaded": true}"#;
+        let path = std::env::temp_dir().join(format!("rusttpx-file-body-{}.json", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+
+        let client = crate::Client::new();
+        let response = client.put(url.clone()).file_body(&path).unwrap().send().await.unwrap();
+        assert_eq!(response.status(), 204);
+        std::fs::remove_file(&path).unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let header = |name: &str| requests[0].headers.get(&name.into()).map(|v| v.as_str().to_string());
+        assert_eq!(requests[0].body, contents);
+        assert_eq!(header("content-type").as_deref(), Some("application/json"));
+        assert_eq!(header("content-length"), Some(contents.len().to_string()));
+
+        let result = client.put(url).file_body(&path);
+        assert!(matches!(result, Err(Error::InvalidRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_upload_bandwidth_limit() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+        let contents = vec![b'x'; 3000];
+        let path = std::env::temp_dir().join(format!("rusttpx-upload-limit-{}.bin", std::process::id()));
+        std::fs::write(&path, &contents).unwrap();
+
+        // 3000 bytes at 10000 bytes per second take at least 300ms
+        let client = crate::Client::new();
+        let minimum = Duration::from_millis(300);
+        let started = std::time::Instant::now();
+        let response = client
+            .put(url.clone())
+            .file_body(&path)
+            .unwrap()
+            .upload_bandwidth_limit(10_000)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert!(started.elapsed() >= minimum);
+        std::fs::remove_file(&path).unwrap();
+
+        let started = std::time::Instant::now();
+        let response = client
+            .put(url)
+            .upload_bandwidth_limit(10_000)
+            .bytes(contents.clone())
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert!(started.elapsed() >= minimum);
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.body == contents));
+        let length = requests[1].headers.get(&"content-length".into()).map(|v| v.as_str().to_string());
+        assert_eq!(length, Some("3000".to_string()));
+    }
+}
@@ -1,11 +1,127 @@
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use cookie::Cookie;
 use futures::Stream;
 use reqwest::{Response as ReqwestResponse, StatusCode};
 use http::{HeaderMap, HeaderValue};
+use http::header::{CONTENT_ENCODING, CONTENT_LENGTH};
 use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 
 use crate::error::{Error, Result, StatusError};
+use crate::client::Client;
 use crate::cookies::CookieJar;
+use crate::compression::StreamDecoder;
+use crate::multipart::{self, Part};
+use crate::pipeline::BodyPipeline;
+use crate::redirect;
+use crate::streaming::{GrpcFrame, IdleTimeout, StreamingReader};
+use crate::throttle::{self, BandwidthLimit};
+use crate::tls::TlsInfo;
+
+/// Maximum number of body characters included in JSON parse errors
+pub const JSON_ERROR_SNIPPET_LEN: usize = 200;
+
+/// Describe the start of a body for an error message
+fn body_snippet(body: &str) -> String {
+    let snippet: String = body.chars().take(JSON_ERROR_SNIPPET_LEN).collect();
+    format!("body starts with: {:?}", snippet)
+}
+
+/// Digest algorithm for checking downloads against a published checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    /// SHA-256
+    Sha256,
+    /// SHA-512
+    Sha512,
+    /// MD5, only for checksums published that way, as it's broken for security
+    Md5,
+}
+
+/// Running digest in one of the `HashAlgo`s
+pub(crate) enum Hasher {
+    Sha(ring::digest::Context),
+    Md5(md5::Md5),
+}
+
+impl Hasher {
+    pub(crate) fn new(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Sha256 => Hasher::Sha(ring::digest::Context::new(&ring::digest::SHA256)),
+            HashAlgo::Sha512 => Hasher::Sha(ring::digest::Context::new(&ring::digest::SHA512)),
+            HashAlgo::Md5 => Hasher::Md5(md5::Digest::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha(context) => context.update(data),
+            Hasher::Md5(md5) => md5::Digest::update(md5, data),
+        }
+    }
+
+    /// Finish the digest as lowercase hex
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Sha(context) => hex::encode(context.finish()),
+            Hasher::Md5(md5) => hex::encode(md5::Digest::finalize(md5)),
+        }
+    }
+}
+
+/// Decode text of unknown charset
+///
+/// A byte order mark wins, then valid UTF-8, then chardetng's best guess.
+fn decode_detected(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true).decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Limits on the headers of a received response
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct HeaderLimits {
+    /// Maximum total bytes of header names and values
+    pub(crate) max_size: Option<usize>,
+    /// Maximum number of header fields, counting repeated names
+    pub(crate) max_count: Option<usize>,
+}
+
+impl HeaderLimits {
+    /// Check received headers against the limits
+    pub(crate) fn check(&self, headers: &HeaderMap) -> Result<()> {
+        if let Some(max) = self.max_count {
+            if headers.len() > max {
+                return Err(Error::response_parse(format!(
+                    "Response has {} headers, more than the limit of {}",
+                    headers.len(),
+                    max
+                )));
+            }
+        }
+        if let Some(max) = self.max_size {
+            let size: usize = headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            if size > max {
+                return Err(Error::response_parse(format!(
+                    "Response headers are {} bytes, more than the limit of {}",
+                    size, max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
 
 /// HTTP response representation
 ///
@@ -19,6 +135,11 @@ pub struct Response {
     version: http::Version,
     inner: ReqwestResponse,
     cookie_jar: Arc<CookieJar>,
+    tls_info: Option<TlsInfo>,
+    stream_idle_timeout: Option<Duration>,
+    detect_charset: bool,
+    raw_body: Option<Vec<u8>>,
+    body_pipeline: Option<Arc<BodyPipeline>>,
 }
 
 impl Response {
@@ -27,10 +148,10 @@ impl Response {
         reqwest_response: ReqwestResponse,
         cookie_jar: Arc<CookieJar>,
     ) -> Result<Self> {
-        // Extract cookies from response headers
-        if let Some(cookie_header) = reqwest_response.headers().get("set-cookie") {
+        // Extract cookies from response headers (a response may set several)
+        for cookie_header in reqwest_response.headers().get_all("set-cookie") {
             if let Ok(cookie_str) = cookie_header.to_str() {
-                cookie_jar.add_cookie_from_response(cookie_str, &reqwest_response.url());
+                cookie_jar.add_cookie_from_response(cookie_str, reqwest_response.url());
             }
         }
 
@@ -38,6 +159,10 @@ impl Response {
         let headers = reqwest_response.headers().clone();
         let url = reqwest_response.url().clone();
         let version = reqwest_response.version();
+        let tls_info = reqwest_response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .map(TlsInfo::from_reqwest);
 
         Ok(Self {
             status,
@@ -46,9 +171,94 @@ impl Response {
             version,
             inner: reqwest_response,
             cookie_jar,
+            tls_info,
+            stream_idle_timeout: None,
+            detect_charset: false,
+            raw_body: None,
+            body_pipeline: None,
+        })
+    }
+
+    /// Create a response from already-buffered parts
+    ///
+    /// Cookies in the headers are not added to the jar again.
+    pub(crate) fn from_parts(
+        status: StatusCode,
+        version: http::Version,
+        headers: HeaderMap,
+        url: url::Url,
+        body: Vec<u8>,
+        cookie_jar: Arc<CookieJar>,
+    ) -> Result<Self> {
+        let mut http_response = http::Response::new(body);
+        *http_response.status_mut() = status;
+        *http_response.version_mut() = version;
+        *http_response.headers_mut() = headers.clone();
+
+        Ok(Self {
+            status,
+            headers,
+            url,
+            version,
+            inner: ReqwestResponse::from(http_response),
+            cookie_jar,
+            tls_info: None,
+            stream_idle_timeout: None,
+            detect_charset: false,
+            raw_body: None,
+            body_pipeline: None,
         })
     }
 
+    /// Keep the TLS details of the response this one was rebuilt from
+    pub(crate) fn with_tls_info(mut self, tls_info: Option<TlsInfo>) -> Self {
+        self.tls_info = tls_info;
+        self
+    }
+
+    /// Fail body streams that go `timeout` without data
+    pub(crate) fn with_stream_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.stream_idle_timeout = timeout;
+        self
+    }
+
+    /// Guess the charset in `text` when the response doesn't declare one
+    pub(crate) fn with_detect_charset(mut self, enabled: bool) -> Self {
+        self.detect_charset = enabled;
+        self
+    }
+
+    /// Keep the body as it was received, before content decoding
+    pub(crate) fn with_raw_body(mut self, raw_body: Option<Vec<u8>>) -> Self {
+        self.raw_body = raw_body;
+        self
+    }
+
+    /// Run `pipeline` over the body when it's read
+    pub(crate) fn with_body_pipeline(mut self, pipeline: Option<Arc<BodyPipeline>>) -> Self {
+        self.body_pipeline = pipeline;
+        self
+    }
+
+    /// Buffer the body and run the body pipeline over it, if there is one
+    async fn run_body_pipeline(mut self) -> Result<Self> {
+        let Some(pipeline) = self.body_pipeline.take() else {
+            return Ok(self);
+        };
+        let body = self.inner.bytes().await.map_err(Error::Network)?;
+        let body = pipeline.apply(body.to_vec(), &self.headers)?;
+        if pipeline.decompresses() {
+            self.headers.remove(CONTENT_ENCODING);
+            self.headers.remove(CONTENT_LENGTH);
+        }
+        let mut http_response = http::Response::new(body);
+        *http_response.status_mut() = self.status;
+        *http_response.version_mut() = self.version;
+        *http_response.headers_mut() = self.headers.clone();
+        self.inner = ReqwestResponse::from(http_response);
+        Ok(self)
+    }
+
     /// Get the HTTP status code
     pub fn status(&self) -> StatusCode {
         self.status
@@ -76,7 +286,39 @@ impl Response {
             .and_then(|v| v.to_str().ok())
     }
 
-    /// Get the content length
+    /// Parse the `Content-Type` header into a MIME type
+    pub fn mime_type(&self) -> Option<mime::Mime> {
+        self.content_type().and_then(|ct| ct.parse().ok())
+    }
+
+    /// Check if the content type is JSON (including `+json` suffixes)
+    pub fn is_json(&self) -> bool {
+        self.mime_type().map(|m| is_json_mime(&m)).unwrap_or(false)
+    }
+
+    /// Check if the content type is XML (including `+xml` suffixes)
+    pub fn is_xml(&self) -> bool {
+        self.mime_type().map(|m| is_xml_mime(&m)).unwrap_or(false)
+    }
+
+    /// Check if the content type is HTML
+    pub fn is_html(&self) -> bool {
+        self.mime_type()
+            .map(|m| m.type_() == mime::TEXT && m.subtype() == mime::HTML)
+            .unwrap_or(false)
+    }
+
+    /// Check if the content type is textual (`text/*`)
+    pub fn is_text(&self) -> bool {
+        self.mime_type()
+            .map(|m| m.type_() == mime::TEXT)
+            .unwrap_or(false)
+    }
+
+    /// Get the content length as sent on the wire
+    ///
+    /// For a response with a `Content-Encoding` this is the encoded size,
+    /// not the size of the decoded body. See `decoded_content_length`.
     pub fn content_length(&self) -> Option<u64> {
         self.headers
             .get("content-length")
@@ -84,11 +326,59 @@ impl Response {
             .and_then(|s| s.parse().ok())
     }
 
+    /// Get the length of the decoded body, if it is known up front
+    ///
+    /// Equal to `content_length` when the body has no content coding.
+    /// Returns `None` for encoded bodies, whose decoded size is only known
+    /// after reading them.
+    pub fn decoded_content_length(&self) -> Option<u64> {
+        let encoded = self
+            .headers
+            .get_all("content-encoding")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|coding| !coding.trim().eq_ignore_ascii_case("identity"));
+        if encoded {
+            None
+        } else {
+            self.content_length()
+        }
+    }
+
+    /// Get the cookies set by this response
+    ///
+    /// Parses every `Set-Cookie` header, keeping each cookie's attributes.
+    /// Headers that fail to parse are skipped.
+    pub fn cookies(&self) -> Vec<Cookie<'static>> {
+        self.headers
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|s| Cookie::parse(s).ok())
+            .map(|c| c.into_owned())
+            .collect()
+    }
+
     /// Get the URL that was requested
     pub fn url(&self) -> &url::Url {
         &self.url
     }
 
+    /// Get the URL for display, with an internationalized host in Unicode
+    /// rather than the punycode it was requested with
+    pub fn display_url(&self) -> String {
+        crate::request::display_url(&self.url)
+    }
+
+    /// Get the TLS details of the connection the response arrived on
+    ///
+    /// `None` for plaintext connections, cached or middleware-made
+    /// responses, and clients built without `ClientBuilder::tls_info`.
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_ref()
+    }
+
     /// Check if the response is successful (2xx status code)
     pub fn is_success(&self) -> bool {
         self.status.is_success()
@@ -109,6 +399,53 @@ impl Response {
         self.status.is_redirection()
     }
 
+    /// Send the next request of a redirect chain, one hop at a time
+    ///
+    /// Returns `None` when this response isn't a redirect, or is one
+    /// without a usable `Location`. Otherwise the request behind this
+    /// response is sent, without following redirects, to the `Location`
+    /// resolved against this response's URL. As when the client follows
+    /// redirects, 301 and 302 turn a `POST` into a `GET`, 303 turns anything
+    /// but `HEAD` into a `GET`, and credentials and cookies are dropped when
+    /// the origin changes. Cookies the jar holds for the new URL, including
+    /// ones set by this response, are added. The `Referer` follows the
+    /// client's `referer_policy`, and is left out without one.
+    ///
+    /// Only responses from `Client::send_no_redirect_follow`, or from an
+    /// earlier `follow_redirect`, can be followed. A streaming body can't be
+    /// sent again, so a 307 or 308 after one fails.
+    pub async fn follow_redirect(&self, client: &Client) -> Result<Option<Response>> {
+        let Some(location) = redirect::location(self.status, &self.headers, &self.url) else {
+            return Ok(None);
+        };
+        let sent = self.extensions().get::<redirect::Sent>().ok_or_else(|| {
+            Error::custom("Only responses from Client::send_no_redirect_follow can be followed")
+        })?;
+        let mut next = sent.0.clone().redirected(self.status, location, client.referer_policy());
+
+        // Carried cookies come first, then any others the jar has for the new URL
+        let is_navigation = next.method() == http::Method::GET;
+        let jar = self.cookie_jar.cookies_for_request(next.url(), is_navigation, Some(&self.url));
+        let headers = next.headers_mut();
+        let mut pairs: Vec<String> = headers
+            .get(http::header::COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split("; ").map(String::from).collect())
+            .unwrap_or_default();
+        for cookie in jar {
+            if !pairs.iter().any(|pair| pair.split('=').next() == Some(cookie.name())) {
+                pairs.push(format!("{}={}", cookie.name(), cookie.value()));
+            }
+        }
+        if let Ok(value) = HeaderValue::from_str(&pairs.join("; ")) {
+            if !pairs.is_empty() {
+                headers.insert(http::header::COOKIE, value);
+            }
+        }
+
+        client.send_no_redirect_follow(next).await.map(Some)
+    }
+
     /// Check if the response indicates an informational status
     pub fn is_informational(&self) -> bool {
         self.status.is_informational()
@@ -131,6 +468,25 @@ impl Response {
         Ok(self)
     }
 
+    /// Like `error_for_status`, with the start of the body in the error message
+    pub(crate) async fn error_for_status_with_body(self) -> Result<Self> {
+        let status = self.status;
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(self);
+        }
+        let kind = if status.is_client_error() { "Client" } else { "Server" };
+        let mut message = format!("{} error: {}", kind, status);
+        let body = self.bytes().await.unwrap_or_default();
+        if !body.is_empty() {
+            message.push_str(&format!(" ({})", body_snippet(&String::from_utf8_lossy(&body))));
+        }
+        Err(Error::from(if status.is_client_error() {
+            StatusError::client(status, message)
+        } else {
+            StatusError::server(status, message)
+        }))
+    }
+
     /// Raise an error for bad status codes (consumes self)
     pub fn error_for_status_ref(&self) -> Result<&Self> {
         if self.status.is_client_error() {
@@ -150,7 +506,12 @@ impl Response {
 
     /// Get the response body as text
     pub async fn text(self) -> Result<String> {
-        self.inner
+        let response = self.run_body_pipeline().await?;
+        let declared = response.mime_type().is_some_and(|mime| mime.get_param(mime::CHARSET).is_some());
+        if response.detect_charset && !declared {
+            return Ok(decode_detected(&response.bytes().await?));
+        }
+        response.inner
             .text()
             .await
             .map_err(Error::Network)
@@ -158,30 +519,135 @@ impl Response {
 
     /// Get the response body as bytes
     pub async fn bytes(self) -> Result<Vec<u8>> {
-        self.inner
+        self.run_body_pipeline()
+            .await?
+            .inner
             .bytes()
             .await
             .map_err(Error::Network)
             .map(|b| b.to_vec())
     }
 
+    /// Get the response body exactly as the server encoded it
+    ///
+    /// Unlike `bytes`, this skips the decompression configured with
+    /// `ClientBuilder::accept_encodings` and the body pipeline, so a gzip
+    /// response comes back still gzipped. Useful for verifying signatures over the wire bytes. Only
+    /// content codings are kept; the transfer framing, such as chunked
+    /// encoding, is always removed.
+    pub async fn raw_bytes(self) -> Result<Vec<u8>> {
+        match self.raw_body {
+            Some(raw_body) => Ok(raw_body),
+            None => self.inner.bytes().await.map_err(Error::Network).map(|b| b.to_vec()),
+        }
+    }
+
     /// Get the response body as JSON
     pub async fn json<T>(self) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
     {
-        self.inner
+        self.run_body_pipeline()
+            .await?
+            .inner
             .json()
             .await
             .map_err(Error::Network)
     }
 
+    /// Get the response body as JSON, including a snippet of the raw body on failure
+    ///
+    /// The body is buffered before parsing so that, when deserialization fails,
+    /// the error carries both the serde error and the first
+    /// [`JSON_ERROR_SNIPPET_LEN`] characters of what the server actually sent.
+    pub async fn json_or_raw<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let bytes = self.bytes().await?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            Error::response_parse(format!("{} ({})", e, body_snippet(&String::from_utf8_lossy(&bytes))))
+        })
+    }
+
+    /// Get the response body as JSON whatever its declared content type
+    ///
+    /// For endpoints that serve JSON as `text/plain` or `text/html`. A JSON
+    /// body under a non-JSON content type is decoded with a warning logged.
+    /// A body that doesn't parse fails with `Error::ResponseParse` naming
+    /// the declared content type, with a snippet of the body.
+    pub async fn json_lenient<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let declared = self.content_type().unwrap_or("no content type").to_string();
+        let declared_json = self.is_json();
+        let url = self.url.clone();
+        let bytes = self.bytes().await?;
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => {
+                if !declared_json {
+                    log::warn!("Response from {} is JSON but declared as {}", url, declared);
+                }
+                Ok(value)
+            }
+            Err(e) => Err(Error::response_parse(format!(
+                "Expected JSON in response declared as {}: {} ({})",
+                declared,
+                e,
+                body_snippet(&String::from_utf8_lossy(&bytes))
+            ))),
+        }
+    }
+
+    /// Validate the response body against a JSON Schema, then deserialize it
+    ///
+    /// Validation failures are reported together in a single
+    /// `Error::ResponseParse`, one `path: message` entry per failure.
+    #[cfg(feature = "schema")]
+    pub async fn json_validated<T>(self, schema: &crate::schema::JsonSchema) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let bytes = self.bytes().await?;
+        let value: Value = serde_json::from_slice(&bytes)
+            .map_err(|e| Error::response_parse(e.to_string()))?;
+        schema.validate(&value)?;
+        serde_json::from_value(value).map_err(|e| Error::response_parse(e.to_string()))
+    }
+
+    /// Get the response body as pretty-printed JSON with sorted keys
+    ///
+    /// Uses two-space indentation, matching the CLI output. Bodies that
+    /// aren't JSON fail with `Error::ResponseParse`.
+    pub async fn json_pretty(self) -> Result<String> {
+        let bytes = self.bytes().await?;
+        let value: Value = serde_json::from_slice(&bytes)
+            .map_err(|e| Error::response_parse(e.to_string()))?;
+        Ok(pretty_json(&value))
+    }
+
     /// Get the response body as a stream of bytes
+    ///
+    /// With the client's `stream_idle_timeout`, the stream fails with
+    /// `Error::Timeout` when the server sends nothing for that long.
     pub fn bytes_stream(self) -> impl Stream<Item = Result<Vec<u8>>> {
         use futures::StreamExt;
-        self.inner
+        let stream = self.inner
             .bytes_stream()
-            .map(|chunk| chunk.map(|b| b.to_vec()).map_err(|e| Error::Network(e)))
+            .map(|chunk| chunk.map(|b| b.to_vec()).map_err(Error::Network));
+        IdleTimeout::new(stream, self.stream_idle_timeout)
+    }
+
+    /// Get the response body as a stream of bytes with its content codings undone
+    ///
+    /// `gzip`, `deflate` and `br` bodies are decompressed as chunks arrive,
+    /// rather than after the whole body is read. An unsupported
+    /// `Content-Encoding`, or a body that ends partway through, yields an
+    /// `Error::Compression`. Bodies the client already decoded pass through.
+    pub fn decoded_bytes_stream(self) -> impl Stream<Item = Result<Vec<u8>>> {
+        let decoder = StreamDecoder::for_headers(&self.headers);
+        crate::streaming::decoded_chunks(self.bytes_stream(), decoder)
     }
 
     /// Get the response body as a stream of text chunks
@@ -213,11 +679,177 @@ impl Response {
         Ok(bytes.len() as u64)
     }
 
-    // Note: reqwest::Response doesn't implement AsyncRead in this version
-    // pub fn reader(self) -> impl AsyncRead {
-    //     self.inner
+    // Note: reqwest's body types discard trailers in this version (both the plain
+    // and decoding bodies return `Ok(None)` from `poll_trailers`), so trailing
+    // headers such as `grpc-status` never reach us. Once reqwest exposes them:
+    // pub async fn trailers(self) -> Result<Option<HeaderMap>> {
+    //     let mut body = self.inner.into_body();
+    //     while let Some(chunk) = body.data().await {
+    //         chunk.map_err(Error::Network)?;
+    //     }
+    //     body.trailers().await.map_err(Error::Network)
     // }
 
+    /// Read the response body through `tokio::io::AsyncRead`
+    ///
+    /// reqwest::Response doesn't implement AsyncRead in this version, so the
+    /// byte stream is adapted with `StreamingReader`.
+    pub fn into_async_read(self) -> impl tokio::io::AsyncRead + Unpin {
+        StreamingReader::new(Box::pin(self.bytes_stream()))
+    }
+
+    /// Read the body on a spawned task into a bounded channel
+    ///
+    /// The task waits whenever `capacity` chunks are queued, so a slow
+    /// consumer holds back the download instead of buffering it. The task
+    /// ends after the last chunk or the first error, or as soon as the
+    /// receiver is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or if called outside a Tokio runtime.
+    pub fn into_channel(self, capacity: usize) -> (mpsc::Receiver<Result<Vec<u8>>>, JoinHandle<()>) {
+        use futures::StreamExt;
+
+        let (sender, receiver) = mpsc::channel(capacity);
+        let handle = tokio::spawn(async move {
+            let mut stream = Box::pin(self.bytes_stream());
+            loop {
+                let chunk = tokio::select! {
+                    chunk = stream.next() => chunk,
+                    _ = sender.closed() => break,
+                };
+                let Some(chunk) = chunk else { break };
+                let failed = chunk.is_err();
+                if sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        (receiver, handle)
+    }
+
+    /// Get the body with its digest in `algo`, as lowercase hex
+    ///
+    /// The digest is computed while the body streams in, for comparing
+    /// against a published checksum.
+    pub async fn bytes_with_hash(self, algo: HashAlgo) -> Result<(Vec<u8>, String)> {
+        use futures::StreamExt;
+
+        let mut hasher = Hasher::new(algo);
+        let mut body = Vec::new();
+        let mut stream = Box::pin(self.bytes_stream());
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            body.extend(chunk);
+        }
+        Ok((body, hasher.finish()))
+    }
+
+    /// Decode a top-level JSON array element by element as the body arrives
+    ///
+    /// For APIs that return one huge `[{...}, {...}]` rather than JSON
+    /// lines. Each element is yielded as soon as its last byte is read, so
+    /// only one element is held in memory at a time. A body that isn't a
+    /// well-formed array yields an `Error::ResponseParse`, after any elements
+    /// decoded before the problem, and ends the stream.
+    pub fn json_array_stream<T>(self) -> impl Stream<Item = Result<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        crate::streaming::json_array_elements(self.bytes_stream())
+    }
+
+    /// Decode a stream of JSON events, such as JSON lines or server-sent events
+    ///
+    /// Each event is decoded into `T`, typically an enum tagged by a `type`
+    /// field (`#[serde(tag = "type")]`). With `skip_unknown`, events whose
+    /// tag matches no variant are dropped instead of yielding an error, so
+    /// new event types on the server don't break older clients. Other
+    /// decoding errors are yielded and the stream carries on.
+    pub fn event_json_stream<T>(self, skip_unknown: bool) -> impl Stream<Item = Result<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        use futures::StreamExt;
+
+        crate::streaming::json_events(self.bytes_stream()).filter_map(move |event| {
+            let decoded = event.and_then(|bytes| match serde_json::from_slice::<T>(&bytes) {
+                Ok(value) => Ok(Some(value)),
+                Err(e) if skip_unknown && e.is_data() && e.to_string().starts_with("unknown variant") => Ok(None),
+                Err(e) => Err(Error::from(e)),
+            });
+            std::future::ready(decoded.transpose())
+        })
+    }
+
+    /// Parse a gRPC-Web body into its message frames as it arrives
+    ///
+    /// Data frames hold the serialized messages for a protobuf decoder, and
+    /// the final trailer frame holds the call's status (see
+    /// [`GrpcFrame::trailers`]). Only the binary `application/grpc-web`
+    /// format is understood, not base64 `application/grpc-web-text`.
+    pub fn grpc_web_frames(self) -> impl Stream<Item = Result<GrpcFrame>> {
+        crate::streaming::grpc_web_frames(self.bytes_stream())
+    }
+
+    /// Get the body as a stream of bytes, copying each chunk to `sink` first
+    ///
+    /// For logging or saving a body while still processing it, without
+    /// buffering it twice. A chunk is yielded only after it has been written
+    /// to the sink, and the sink is flushed when the body ends. A failed
+    /// write yields an `Error::Stream` and ends the stream.
+    pub fn tee<W>(self, sink: W) -> impl Stream<Item = Result<Vec<u8>>>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use futures::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let state = (Box::pin(self.bytes_stream()), sink, false);
+        futures::stream::unfold(state, |(mut stream, mut sink, done)| async move {
+            if done {
+                return None;
+            }
+            let written = match stream.next().await {
+                Some(Ok(chunk)) => sink.write_all(&chunk).await.map(|_| Some(chunk)),
+                Some(Err(e)) => return Some((Err(e), (stream, sink, true))),
+                None => sink.flush().await.map(|_| None),
+            };
+            match written {
+                Ok(Some(chunk)) => Some((Ok(chunk), (stream, sink, false))),
+                Ok(None) => None,
+                Err(e) => Some((Err(Error::stream(format!("Tee write failed: {}", e))), (stream, sink, true))),
+            }
+        })
+    }
+
+    /// Split a `multipart/*` body into its parts as it streams in
+    ///
+    /// For batch APIs that answer with `multipart/mixed` and the like. The
+    /// boundary comes from the `Content-Type`; a response without one yields
+    /// a single `Error::ResponseParse`. Each part is yielded with its
+    /// headers once it has been read in full, and the body must end with
+    /// the closing `--boundary--`.
+    pub fn multipart_parts(self) -> impl Stream<Item = Result<Part>> {
+        let boundary = self
+            .mime_type()
+            .filter(|mime| mime.type_() == mime::MULTIPART)
+            .and_then(|mime| mime.get_param(mime::BOUNDARY).map(|b| b.to_string()));
+        multipart::response_parts(self.bytes_stream(), boundary)
+    }
+
+    /// Get the response body as a stream of bytes read at most `bytes_per_sec`
+    ///
+    /// A token bucket paces the reads, so the server sees a slow client
+    /// rather than one that reads quickly and then idles. Chunks are split
+    /// to carry at most a tenth of a second's worth of bytes each. Zero
+    /// reads at full speed.
+    pub fn throttled_bytes_stream(self, bytes_per_sec: u64) -> impl Stream<Item = Result<Vec<u8>>> {
+        throttle::throttle(self.bytes_stream(), BandwidthLimit::new(bytes_per_sec))
+    }
+
     /// Get the underlying reqwest response
     pub fn into_inner(self) -> ReqwestResponse {
         self.inner
@@ -248,6 +880,15 @@ impl Response {
         self.inner.remote_addr()
     }
 
+    /// Parse the server's rate-limit headers
+    ///
+    /// Reads the standardized `RateLimit-Limit`, `RateLimit-Remaining` and
+    /// `RateLimit-Reset` headers, falling back to the `X-RateLimit-*`
+    /// headers GitHub and others send. Returns `None` when none are present.
+    pub fn rate_limit_info(&self) -> Option<RateLimitInfo> {
+        RateLimitInfo::from_headers(&self.headers, SystemTime::now())
+    }
+
     /// Get the response extensions
     pub fn extensions(&self) -> &http::Extensions {
         self.inner.extensions()
@@ -259,6 +900,80 @@ impl Response {
     }
 }
 
+/// Pretty-print a JSON value with two-space indentation and sorted object keys
+pub fn pretty_json(value: &Value) -> String {
+    serde_json::to_string_pretty(&sort_keys(value)).unwrap_or_default()
+}
+
+/// Copy a JSON value with object keys sorted by their UTF-8 bytes, at every level
+pub(crate) fn sort_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+            Value::Object(entries.into_iter().map(|(k, v)| (k.clone(), sort_keys(v))).collect())
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sort_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Check if a MIME type is JSON, e.g. `application/json` or `application/vnd.api+json`
+pub fn is_json_mime(mime: &mime::Mime) -> bool {
+    mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+}
+
+/// Check if a MIME type is XML, e.g. `application/xml` or `application/atom+xml`
+pub fn is_xml_mime(mime: &mime::Mime) -> bool {
+    mime.subtype() == mime::XML || mime.suffix() == Some(mime::XML)
+}
+
+/// Rate-limit state reported in response headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Requests allowed in the current window
+    pub limit: Option<u64>,
+    /// Requests left in the current window
+    pub remaining: Option<u64>,
+    /// When the window resets
+    pub reset: Option<SystemTime>,
+}
+
+impl RateLimitInfo {
+    /// Parse rate-limit headers, resolving relative resets against `now`
+    ///
+    /// `RateLimit-Reset` is seconds until the reset. `X-RateLimit-Reset` is
+    /// a Unix timestamp for most APIs and seconds until the reset for some,
+    /// so values too small to be a recent timestamp are taken as seconds.
+    pub(crate) fn from_headers(headers: &HeaderMap, now: SystemTime) -> Option<Self> {
+        let number = |name: &str| {
+            // Draft policies may follow the number, as in `100, 100;w=60`
+            let value = headers.get(name)?.to_str().ok()?;
+            value.split([',', ';']).next()?.trim().parse::<u64>().ok()
+        };
+        let either = |field: &str| {
+            number(&format!("ratelimit-{}", field))
+                .map(|n| (n, true))
+                .or_else(|| number(&format!("x-ratelimit-{}", field)).map(|n| (n, false)))
+        };
+
+        let limit = either("limit").map(|(n, _)| n);
+        let remaining = either("remaining").map(|(n, _)| n);
+        let reset = either("reset").map(|(n, standard)| {
+            // 2001-09-09, long before any server would send a timestamp
+            if standard || n < 1_000_000_000 {
+                now + Duration::from_secs(n)
+            } else {
+                UNIX_EPOCH + Duration::from_secs(n)
+            }
+        });
+        if limit.is_none() && remaining.is_none() && reset.is_none() {
+            return None;
+        }
+        Some(Self { limit, remaining, reset })
+    }
+}
+
 impl Clone for Response {
     fn clone(&self) -> Self {
         // Note: reqwest::Response doesn't support cloning in this version
@@ -391,6 +1106,48 @@ impl Response {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rate_limit_info() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+
+        // GitHub style, with the reset as a Unix timestamp
+        let github = headers(&[
+            ("x-ratelimit-limit", "5000"),
+            ("x-ratelimit-remaining", "4987"),
+            ("x-ratelimit-reset", "1700000600"),
+        ]);
+        let info = RateLimitInfo::from_headers(&github, now).unwrap();
+        assert_eq!(info.limit, Some(5000));
+        assert_eq!(info.remaining, Some(4987));
+        assert_eq!(info.reset, Some(now + Duration::from_secs(600)));
+
+        // The standardized headers count the reset in seconds, and win
+        let standard = headers(&[
+            ("ratelimit-limit", "100, 100;w=60"),
+            ("ratelimit-remaining", "0"),
+            ("ratelimit-reset", "30"),
+            ("x-ratelimit-remaining", "99"),
+        ]);
+        let info = RateLimitInfo::from_headers(&standard, now).unwrap();
+        assert_eq!(info.limit, Some(100));
+        assert_eq!(info.remaining, Some(0));
+        assert_eq!(info.reset, Some(now + Duration::from_secs(30)));
+
+        let relative = headers(&[("x-ratelimit-reset", "45")]);
+        let info = RateLimitInfo::from_headers(&relative, now).unwrap();
+        assert_eq!((info.limit, info.reset), (None, Some(now + Duration::from_secs(45))));
+
+        assert!(RateLimitInfo::from_headers(&headers(&[("x-ratelimit-limit", "lots")]), now).is_none());
+        assert!(RateLimitInfo::from_headers(&HeaderMap::new(), now).is_none());
+    }
+
     #[tokio::test]
     async fn test_response_creation() {
         let builder = ResponseBuilder::new(StatusCode::OK)
@@ -421,4 +1178,652 @@ mod tests {
         assert!(!response.is_client_error());
         assert!(response.is_server_error());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_mime_classification() {
+        let vnd: mime::Mime = "application/vnd.api+json".parse().unwrap();
+        assert!(is_json_mime(&vnd));
+        assert!(!is_xml_mime(&vnd));
+
+        let html: mime::Mime = "text/html; charset=utf-8".parse().unwrap();
+        assert_eq!(html.type_(), mime::TEXT);
+        assert_eq!(html.subtype(), mime::HTML);
+        assert!(!is_json_mime(&html));
+
+        let atom: mime::Mime = "application/atom+xml".parse().unwrap();
+        assert!(is_xml_mime(&atom));
+
+        let unknown: mime::Mime = "application/x-unknown".parse().unwrap();
+        assert!(!is_json_mime(&unknown));
+        assert!(!is_xml_mime(&unknown));
+    }
+
+    #[tokio::test]
+    async fn test_into_async_read() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let body: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let url: url::Url = server.uri().parse().unwrap();
+
+        let expected = client.get(url.clone()).send().await.unwrap().bytes().await.unwrap();
+
+        let response = client.get(url).send().await.unwrap();
+        let mut reader = response.into_async_read();
+        let mut copied = Vec::new();
+        tokio::io::copy(&mut reader, &mut copied).await.unwrap();
+
+        assert_eq!(copied, expected);
+        assert_eq!(copied, body);
+    }
+
+    #[tokio::test]
+    async fn test_into_channel() {
+        use std::time::Duration;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let body: Vec<u8> = (0..4_000_000u32).map(|i| (i % 251) as u8).collect();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&server)
+            .await;
+        let client = crate::Client::new();
+        let url: url::Url = server.uri().parse().unwrap();
+
+        // Nothing is consumed yet, so the reader stops once two chunks are queued
+        let (mut receiver, handle) = client.get(url.clone()).send().await.unwrap().into_channel(2);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(receiver.len(), 2);
+        assert!(!handle.is_finished());
+
+        let mut received = Vec::new();
+        while let Some(chunk) = receiver.recv().await {
+            received.extend(chunk.unwrap());
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(received, body);
+        handle.await.unwrap();
+
+        // Dropping the receiver stops the reader
+        let (receiver, handle) = client.get(url).send().await.unwrap().into_channel(1);
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(1), handle).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_throttled_bytes_stream() {
+        use std::time::{Duration, Instant};
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let body = vec![b'x'; 3000];
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&server)
+            .await;
+        let url: url::Url = server.uri().parse().unwrap();
+        let response = crate::Client::new().get(url).send().await.unwrap();
+
+        // 3000 bytes at 10000 bytes per second take at least 300ms
+        let started = Instant::now();
+        let mut stream = Box::pin(response.throttled_bytes_stream(10_000));
+        let mut received = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.unwrap();
+            assert!(chunk.len() <= 1000);
+            received.extend(chunk);
+        }
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(received, body);
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_hash() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+            .mount(&server)
+            .await;
+        let url: url::Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+
+        let digests = [
+            (HashAlgo::Sha256, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"),
+            (
+                HashAlgo::Sha512,
+                "309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f\
+                 989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f",
+            ),
+            (HashAlgo::Md5, "5eb63bbbe01eeed093cb22bb8f5acdc3"),
+        ];
+        for (algo, expected) in digests {
+            let response = client.get(url.clone()).send().await.unwrap();
+            let (body, digest) = response.bytes_with_hash(algo).await.unwrap();
+            assert_eq!(body, b"hello world");
+            assert_eq!(digest, expected, "{:?}", algo);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_array_stream() {
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Item {
+            id: u32,
+            name: String,
+            tags: Vec<String>,
+        }
+
+        // Large enough to arrive in many chunks, with brackets inside strings
+        let items: Vec<Value> = (0..20_000)
+            .map(|id| serde_json::json!({"id": id, "name": format!("item [{}] {{", id), "tags": ["a]", "\"b\""]}))
+            .collect();
+        let body = serde_json::to_vec_pretty(&items).unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&server)
+            .await;
+        let url: url::Url = server.uri().parse().unwrap();
+
+        let response = crate::Client::new().get(url).send().await.unwrap();
+        let mut stream = Box::pin(response.json_array_stream::<Item>());
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            let item = item.unwrap();
+            assert_eq!(item.id, count);
+            assert_eq!(item.name, format!("item [{}] {{", count));
+            assert_eq!(item.tags, ["a]", "\"b\""]);
+            count += 1;
+        }
+        assert_eq!(count, 20_000);
+    }
+
+    #[tokio::test]
+    async fn test_event_json_stream() {
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        #[serde(tag = "type", rename_all = "snake_case")]
+        enum Event {
+            Created { id: u32 },
+            Deleted { id: u32 },
+        }
+
+        let body = concat!(
+            "data: {\"type\": \"created\", \"id\": 1}\n\n",
+            "data: {\"type\": \"renamed\", \"id\": 1, \"name\": \"x\"}\n\n",
+            "data: {\"type\": \"deleted\", \"id\": 1}\n\n",
+        );
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        let url: url::Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+
+        let response = client.get(url.clone()).send().await.unwrap();
+        let events: Vec<Event> = response.event_json_stream(true).map(|e| e.unwrap()).collect().await;
+        assert_eq!(events, [Event::Created { id: 1 }, Event::Deleted { id: 1 }]);
+
+        // Without skipping, the unknown event is an error in its place
+        let response = client.get(url).send().await.unwrap();
+        let events: Vec<Result<Event>> = response.event_json_stream(false).collect().await;
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[1], Err(Error::Json(_))));
+        assert_eq!(events[2].as_ref().unwrap(), &Event::Deleted { id: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_tee() {
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&server)
+            .await;
+        let url: url::Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+
+        let mut sink = Vec::new();
+        let response = client.get(url.clone()).send().await.unwrap();
+        let received: Vec<u8> = response
+            .tee(&mut sink)
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(received, body);
+        assert_eq!(sink, body);
+
+        // A failing sink ends the stream with its error
+        let (writer, reader) = tokio::io::duplex(64);
+        drop(reader);
+        let response = client.get(url).send().await.unwrap();
+        let results: Vec<_> = response.tee(writer).collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(Error::Stream(_))), "{:?}", results[0]);
+    }
+
+    #[tokio::test]
+    async fn test_detect_charset() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("héllo wörld".encode_utf16().flat_map(|unit| unit.to_le_bytes()))
+            .collect();
+        let windows_1252 = encoding_rs::WINDOWS_1252
+            .encode("Prix : 25 € — déjà payé, merci à vous et à bientôt")
+            .0
+            .into_owned();
+        let server = MockServer::start().await;
+        Mock::given(path("/utf16"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(utf16, "text/plain"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/windows-1252"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(windows_1252, "text/plain"))
+            .mount(&server)
+            .await;
+        let base: url::Url = server.uri().parse().unwrap();
+
+        let client = crate::Client::builder().detect_charset(true).build();
+        let text = |path: &str| client.get(base.join(path).unwrap()).send_text();
+        assert_eq!(text("/utf16").await.unwrap(), "héllo wörld");
+        assert_eq!(
+            text("/windows-1252").await.unwrap(),
+            "Prix : 25 € — déjà payé, merci à vous et à bientôt"
+        );
+
+        // Without detection, invalid UTF-8 is replaced
+        let response = crate::Client::new().get(base.join("/windows-1252").unwrap()).send().await.unwrap();
+        assert!(response.text().await.unwrap().contains('\u{FFFD}'));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_parts() {
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        // The second part is large enough to span many chunks
+        let large = "x".repeat(256 * 1024);
+        let body = format!(
+            "--b\r\nContent-Type: application/json\r\n\r\n{{\"ok\":true}}\r\n--b\r\nContent-Type: text/plain\r\nContent-ID: 2\r\n\r\n{}\r\n--b--\r\n",
+            large
+        );
+        let server = MockServer::start().await;
+        Mock::given(path("/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "multipart/mixed; boundary=\"b\""))
+            .mount(&server)
+            .await;
+        Mock::given(path("/plain"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not multipart"))
+            .mount(&server)
+            .await;
+        let base: url::Url = server.uri().parse().unwrap();
+        let client = crate::Client::new();
+
+        let response = client.get(base.join("/batch").unwrap()).send().await.unwrap();
+        let parts: Vec<_> = response.multipart_parts().collect().await;
+        assert_eq!(parts.len(), 2);
+        let first = parts[0].as_ref().unwrap();
+        assert_eq!(first.content_type(), Some("application/json"));
+        assert_eq!(first.body(), b"{\"ok\":true}");
+        let second = parts[1].as_ref().unwrap();
+        assert_eq!(second.content_type(), Some("text/plain"));
+        assert_eq!(second.header("content-id"), Some("2"));
+        assert_eq!(second.text().unwrap(), large);
+
+        let response = client.get(base.join("/plain").unwrap()).send().await.unwrap();
+        let parts: Vec<_> = response.multipart_parts().collect().await;
+        assert!(matches!(parts.as_slice(), [Err(Error::ResponseParse(_))]));
+    }
+
+    #[tokio::test]
+    async fn test_decoded_content_length() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        // A gzip member holding "hello world"
+        let gzipped: Vec<u8> = vec![
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85, 0x11, 0x4a, 0x0d, 0x0b,
+            0x00, 0x00, 0x00,
+        ];
+
+        let server = MockServer::start().await;
+        Mock::given(path("/gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_bytes(gzipped.clone()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/plain"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let get = |route: &str| {
+            client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send()
+        };
+
+        let response = get("/gzip").await.unwrap();
+        assert_eq!(response.content_length(), Some(gzipped.len() as u64));
+        assert_eq!(response.decoded_content_length(), None);
+        assert_ne!(response.content_length(), Some("hello world".len() as u64));
+
+        let response = get("/plain").await.unwrap();
+        assert_eq!(response.content_length(), Some(11));
+        assert_eq!(response.decoded_content_length(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_json_pretty() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(path("/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"b":1,"a":{"d":[true,null],"c":"x"}}"#))
+            .mount(&server)
+            .await;
+        Mock::given(path("/text"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let get = |route: &str| {
+            client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send()
+        };
+
+        let pretty = get("/json").await.unwrap().json_pretty().await.unwrap();
+        let expected = "{\n  \"a\": {\n    \"c\": \"x\",\n    \"d\": [\n      true,\n      null\n    ]\n  },\n  \"b\": 1\n}";
+        assert_eq!(pretty, expected);
+
+        let err = get("/text").await.unwrap().json_pretty().await.unwrap_err();
+        assert!(matches!(err, Error::ResponseParse(_)));
+    }
+
+    #[tokio::test]
+    async fn test_response_content_type_helpers() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        for (route, content_type) in [
+            ("/vnd", "application/vnd.api+json"),
+            ("/html", "text/html; charset=utf-8"),
+            ("/unknown", "application/x-unknown"),
+        ] {
+            Mock::given(path(route))
+                .respond_with(ResponseTemplate::new(200).insert_header("content-type", content_type))
+                .mount(&server)
+                .await;
+        }
+
+        let client = crate::Client::new();
+        let get = |route: &str| {
+            client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send()
+        };
+
+        let response = get("/vnd").await.unwrap();
+        assert!(response.is_json());
+        assert!(!response.is_html());
+
+        let response = get("/html").await.unwrap();
+        assert!(response.is_html());
+        assert!(response.is_text());
+        assert!(!response.is_json());
+
+        let response = get("/unknown").await.unwrap();
+        assert_eq!(response.mime_type().unwrap().essence_str(), "application/x-unknown");
+        assert!(!response.is_json());
+        assert!(!response.is_html());
+        assert!(!response.is_text());
+    }
+
+    #[tokio::test]
+    async fn test_response_cookies() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .append_header("set-cookie", "session=abc123; Path=/; HttpOnly")
+                    .append_header("set-cookie", "theme=dark; Max-Age=3600"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let response = client.get(server.uri().parse::<url::Url>().unwrap()).send().await.unwrap();
+
+        let cookies = response.cookies();
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].name(), "session");
+        assert_eq!(cookies[0].path(), Some("/"));
+        assert_eq!(cookies[0].http_only(), Some(true));
+        assert_eq!(cookies[1].name(), "theme");
+        assert_eq!(cookies[1].max_age(), Some(cookie::time::Duration::seconds(3600)));
+
+        assert!(client.cookie_jar().has_cookie("session"));
+        assert!(client.cookie_jar().has_cookie("theme"));
+    }
+
+    #[tokio::test]
+    async fn test_multiple_set_cookie_headers_stored_in_jar() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::path("/login"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .append_header("set-cookie", "session=s3cr3t; Path=/")
+                    .append_header("set-cookie", "csrf=t0k3n; Path=/"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let url = format!("{}/login", server.uri()).parse::<url::Url>().unwrap();
+        client.post(url).send().await.unwrap();
+
+        let jar = client.cookie_jar();
+        assert_eq!(jar.len(), 2);
+        assert_eq!(jar.get_cookie("session").unwrap().value(), "s3cr3t");
+        assert_eq!(jar.get_cookie("csrf").unwrap().value(), "t0k3n");
+    }
+
+    #[tokio::test]
+    async fn test_json_or_raw_includes_body_snippet() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::{method, path};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("<html><body>Service Unavailable</body></html>"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let url = format!("{}/json", server.uri()).parse::<url::Url>().unwrap();
+        let response = client.get(url).send().await.unwrap();
+
+        let err = response.json_or_raw::<Value>().await.unwrap_err();
+        assert!(matches!(err, Error::ResponseParse(_)));
+        assert!(err.to_string().contains("<html"));
+    }
+
+    #[tokio::test]
+    async fn test_json_lenient() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(path("/plain"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(r#"{"id": 7}"#, "text/plain"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/html"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("<html><body>Sign in</body></html>", "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let get = |route: &str| client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send();
+
+        let value: Value = get("/plain").await.unwrap().json_lenient().await.unwrap();
+        assert_eq!(value["id"], 7);
+
+        let err = get("/html").await.unwrap().json_lenient::<Value>().await.unwrap_err();
+        assert!(matches!(err, Error::ResponseParse(_)));
+        let message = err.to_string();
+        assert!(message.contains("declared as text/html"), "{}", message);
+        assert!(message.contains("<html><body>Sign in"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_decoded_bytes_stream() {
+        use std::io::Write;
+        use futures::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let original: Vec<u8> = (0..20_000).flat_map(|i| format!("line {} of the body\n", i * 7919 % 10007).into_bytes()).collect();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&original).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        // Sends the gzip body in small chunks, or cut short for "/cut"
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = gzipped.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0u8; 4096];
+                    let n = tokio::io::AsyncReadExt::read(&mut socket, &mut request).await.unwrap();
+                    let cut = String::from_utf8_lossy(&request[..n]).starts_with("GET /cut");
+                    let body = if cut { &body[..body.len() / 2] } else { &body[..] };
+                    let head = "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n";
+                    socket.write_all(head.as_bytes()).await.unwrap();
+                    for chunk in body.chunks(1000) {
+                        socket.write_all(format!("{:x}\r\n", chunk.len()).as_bytes()).await.unwrap();
+                        socket.write_all(chunk).await.unwrap();
+                        socket.write_all(b"\r\n").await.unwrap();
+                        socket.flush().await.unwrap();
+                        tokio::task::yield_now().await;
+                    }
+                    socket.write_all(b"0\r\n\r\n").await.unwrap();
+                });
+            }
+        });
+        let client = crate::Client::new();
+        let get = |route: &str| client.get(format!("http://{}{}", addr, route).parse::<url::Url>().unwrap()).send();
+
+        let chunks: Vec<Vec<u8>> = get("/full")
+            .await
+            .unwrap()
+            .decoded_bytes_stream()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), original);
+
+        let raw: Vec<Vec<u8>> = get("/full").await.unwrap().bytes_stream().map(|c| c.unwrap()).collect().await;
+        assert_eq!(raw.concat(), gzipped);
+
+        let results: Vec<Result<Vec<u8>>> = get("/cut").await.unwrap().decoded_bytes_stream().collect().await;
+        let err = results.last().unwrap().as_ref().unwrap_err();
+        assert!(matches!(err, Error::Compression(_)), "{:?}", err);
+
+        // Bodies without a content coding pass through
+        let url: url::Url = "http://localhost/".parse().unwrap();
+        let jar = Arc::new(CookieJar::new());
+        let response = Response::from_parts(StatusCode::OK, http::Version::HTTP_11, HeaderMap::new(), url, b"plain".to_vec(), jar).unwrap();
+        let chunks: Vec<Vec<u8>> = response.decoded_bytes_stream().map(|c| c.unwrap()).collect().await;
+        assert_eq!(chunks.concat(), b"plain");
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_frames() {
+        use futures::StreamExt;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let trailer = b"grpc-status: 0\r\ngrpc-message: OK\r\n";
+        let mut body = vec![0x00, 0, 0, 0, 3, 0x08, 0x96, 0x01];
+        body.push(0x80);
+        body.extend((trailer.len() as u32).to_be_bytes());
+        body.extend_from_slice(trailer);
+
+        let server = MockServer::start().await;
+        Mock::given(path("/full"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body.clone(), "application/grpc-web+proto"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/cut"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body[..6].to_vec(), "application/grpc-web+proto"))
+            .mount(&server)
+            .await;
+        let client = crate::Client::new();
+        let get = |route: &str| client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send();
+
+        let frames: Vec<GrpcFrame> = get("/full")
+            .await
+            .unwrap()
+            .grpc_web_frames()
+            .map(|frame| frame.unwrap())
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_data() && !frames[0].is_compressed());
+        assert_eq!(frames[0].payload(), [0x08, 0x96, 0x01]);
+        assert!(frames[0].trailers().is_err());
+        assert!(frames[1].is_trailer());
+        let trailers = frames[1].trailers().unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(trailers["grpc-message"], "OK");
+
+        let frames: Vec<_> = get("/cut").await.unwrap().grpc_web_frames().collect().await;
+        assert!(matches!(frames.as_slice(), [Err(Error::ResponseParse(_))]));
+    }
+}
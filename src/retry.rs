@@ -0,0 +1,365 @@
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime};
+use http::{HeaderMap, StatusCode};
+use reqwest::{Request as ReqwestRequest, Response as ReqwestResponse};
+use tokio::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::middleware::rate_limit::RateLimiter;
+use crate::response::RateLimitInfo;
+
+/// Configuration for automatically retrying failed requests
+///
+/// A request is retried when the response status is in `retry_on` or the
+/// connection fails or times out. Requests with streaming bodies can't be
+/// replayed and are sent only once.
+///
+/// With a total timeout, every attempt is limited to the time left in the
+/// budget, so retries never run past it.
+///
+/// With `respect_rate_limits`, a `429 Too Many Requests` is retried after
+/// the wait the server asks for instead of the fixed delay.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay between attempts
+    pub delay: Duration,
+    /// Response statuses that trigger a retry
+    pub retry_on: Vec<StatusCode>,
+    /// Time limit for each attempt, defaulting to the request's timeout
+    pub attempt_timeout: Option<Duration>,
+    /// Time limit for all attempts and the delays between them
+    pub total_timeout: Option<Duration>,
+    /// Wait for the server's rate-limit reset before retrying a 429
+    pub respect_rate_limits: bool,
+    /// Longest wait for a rate-limit reset
+    pub max_rate_limit_wait: Duration,
+}
+
+impl RetryConfig {
+    /// Create a new retry configuration
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Set the maximum number of retries
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay between attempts
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Set the response statuses that trigger a retry
+    pub fn retry_on(mut self, statuses: Vec<StatusCode>) -> Self {
+        self.retry_on = statuses;
+        self
+    }
+
+    /// Limit each attempt to `timeout`
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit all attempts together to `timeout`
+    ///
+    /// Each attempt gets the smaller of its own timeout and the time left.
+    /// Once the budget is spent, sending fails with a timeout error.
+    pub fn total_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry `429 Too Many Requests` once the server says the limit resets
+    ///
+    /// The wait comes from `Retry-After`, in seconds or as a date, or else
+    /// `RateLimit-Reset` or `X-RateLimit-Reset`. Without any of them, the
+    /// usual delay applies. A 429 is retried even when it's not in
+    /// `retry_on`.
+    pub fn respect_rate_limits(mut self, enabled: bool) -> Self {
+        self.respect_rate_limits = enabled;
+        self
+    }
+
+    /// Wait at most `max` for a rate-limit reset, retrying early if it's later
+    pub fn max_rate_limit_wait(mut self, max: Duration) -> Self {
+        self.max_rate_limit_wait = max;
+        self
+    }
+
+    fn should_retry(&self, result: &Result<ReqwestResponse>) -> bool {
+        match result {
+            Ok(response) if self.respect_rate_limits && response.status() == StatusCode::TOO_MANY_REQUESTS => true,
+            Ok(response) => self.retry_on.contains(&response.status()),
+            Err(Error::Network(e)) => e.is_connect() || e.is_timeout(),
+            Err(_) => false,
+        }
+    }
+
+    /// Get the delay before retrying after `result`
+    fn delay_after(&self, result: &Result<ReqwestResponse>) -> Duration {
+        match result {
+            Ok(response) if self.respect_rate_limits && response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                rate_limit_wait(response.headers(), SystemTime::now())
+                    .map_or(self.delay, |wait| wait.min(self.max_rate_limit_wait))
+            }
+            _ => self.delay,
+        }
+    }
+}
+
+/// Read how long a rate-limited client should wait from response headers
+fn rate_limit_wait(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let retry_after = headers
+        .get(http::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim);
+    let reset = match retry_after {
+        Some(value) => match value.parse::<u64>() {
+            Ok(seconds) => return Some(Duration::from_secs(seconds)),
+            Err(_) => httpdate::parse_http_date(value).ok(),
+        },
+        None => None,
+    };
+    let reset = reset.or_else(|| RateLimitInfo::from_headers(headers, now)?.reset)?;
+    Some(reset.duration_since(now).unwrap_or_default())
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            delay: Duration::from_secs(1),
+            retry_on: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            attempt_timeout: None,
+            total_timeout: None,
+            respect_rate_limits: false,
+            max_rate_limit_wait: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Send a request, retrying per `config` and waiting on the rate limiter
+/// before every attempt
+pub(crate) async fn send<F, Fut>(
+    config: Option<&RetryConfig>,
+    rate_limiter: Option<&Mutex<RateLimiter>>,
+    mut request: ReqwestRequest,
+    send: F,
+) -> Result<ReqwestResponse>
+where
+    F: Fn(ReqwestRequest) -> Fut,
+    Fut: Future<Output = Result<ReqwestResponse>>,
+{
+    let started = Instant::now();
+    let budget = config.and_then(|c| c.total_timeout);
+    let remaining = || budget.map(|budget| budget.saturating_sub(started.elapsed()));
+    let attempt_timeout = config.and_then(|c| c.attempt_timeout).or(request.timeout().copied());
+
+    let mut attempt = 0;
+    loop {
+        if let Some(limiter) = rate_limiter {
+            limiter.lock().await.wait().await;
+        }
+
+        if let Some(remaining) = remaining() {
+            if remaining.is_zero() {
+                return Err(Error::timeout(budget.unwrap_or_default()));
+            }
+            *request.timeout_mut() = Some(attempt_timeout.map_or(remaining, |t| t.min(remaining)));
+        } else if let Some(timeout) = attempt_timeout {
+            *request.timeout_mut() = Some(timeout);
+        }
+
+        let retry = config
+            .filter(|c| attempt < c.max_retries)
+            .and_then(|c| request.try_clone().map(|next| (c, next)));
+        let result = send(request).await;
+
+        // An attempt cut short by the budget means the budget is spent
+        if let (Some(budget), Err(Error::Network(e))) = (budget, &result) {
+            if e.is_timeout() && remaining().is_some_and(|r| r.is_zero()) {
+                return Err(Error::timeout(budget));
+            }
+        }
+
+        match retry {
+            Some((config, next)) if config.should_retry(&result) => {
+                attempt += 1;
+                let delay = config.delay_after(&result);
+                let delay = remaining().map_or(delay, |r| delay.min(r));
+                tokio::time::sleep(delay).await;
+                request = next;
+            }
+            _ => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use url::Url;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::method;
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let client = crate::Client::builder()
+            .retry(RetryConfig::new(3).delay(Duration::from_millis(10)))
+            .build();
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        // Statuses outside `retry_on` are returned as-is
+        let client = crate::Client::builder()
+            .retry(RetryConfig::new(3).retry_on(vec![StatusCode::BAD_GATEWAY]))
+            .build();
+        server.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_total_timeout_budget() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503).set_delay(Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let client = crate::Client::builder()
+            .retry(
+                RetryConfig::new(5)
+                    .delay(Duration::from_millis(10))
+                    .total_timeout(Duration::from_millis(300)),
+            )
+            .build();
+        let started = Instant::now();
+        let result = client.get(url).send().await;
+
+        // The first attempt takes 200ms, the retry is cut off at the 300ms budget
+        assert!(matches!(result, Err(Error::Timeout { .. })), "{:?}", result);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(server.received_requests().await.unwrap().len() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_spaces_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let client = crate::Client::builder().rate_limit(20.0).build();
+        let started = Instant::now();
+        for _ in 0..3 {
+            client.get(url.clone()).send().await.unwrap();
+        }
+        // The first request goes straight out, the next two wait 50ms each
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let wait = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            rate_limit_wait(&headers, now)
+        };
+
+        assert_eq!(wait(&[("retry-after", "120")]), Some(Duration::from_secs(120)));
+        let date = httpdate::fmt_http_date(now + Duration::from_secs(30));
+        assert_eq!(wait(&[("retry-after", &date)]), Some(Duration::from_secs(30)));
+        assert_eq!(wait(&[("x-ratelimit-reset", "1700000010")]), Some(Duration::from_secs(10)));
+        assert_eq!(wait(&[("ratelimit-reset", "5"), ("x-ratelimit-reset", "1700000010")]), Some(Duration::from_secs(5)));
+        // A reset in the past means go now
+        assert_eq!(wait(&[("x-ratelimit-reset", "1699999990")]), Some(Duration::ZERO));
+        assert_eq!(wait(&[("x-ratelimit-remaining", "0")]), None);
+    }
+
+    #[tokio::test]
+    async fn test_respect_rate_limits() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("RateLimit-Reset", "1"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        // 429 isn't in `retry_on`, and the fixed delay is much shorter than the reset
+        let client = crate::Client::builder()
+            .respect_rate_limits(true)
+            .retry(RetryConfig::new(2).delay(Duration::from_millis(10)).retry_on(vec![]))
+            .build();
+        let started = Instant::now();
+        let response = client.get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // The wait is capped
+        server.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+            .mount(&server)
+            .await;
+        let client = crate::Client::builder()
+            .retry(
+                RetryConfig::new(1)
+                    .respect_rate_limits(true)
+                    .max_rate_limit_wait(Duration::from_millis(50)),
+            )
+            .build();
+        let started = Instant::now();
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+}
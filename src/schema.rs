@@ -0,0 +1,104 @@
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Compiled JSON Schema for validating response bodies
+pub struct JsonSchema {
+    compiled: JSONSchema,
+}
+
+impl JsonSchema {
+    /// Compile a JSON Schema document
+    pub fn new(schema: &Value) -> Result<Self> {
+        let compiled = JSONSchema::compile(schema)
+            .map_err(|e| Error::config(format!("Invalid JSON schema: {}", e)))?;
+        Ok(Self { compiled })
+    }
+
+    /// Validate a JSON value, listing every failure in the error
+    pub fn validate(&self, instance: &Value) -> Result<()> {
+        self.compiled.validate(instance).map_err(|errors| {
+            let failures: Vec<String> = errors
+                .map(|e| {
+                    let path = e.instance_path.to_string();
+                    let path = if path.is_empty() { "/".to_string() } else { path };
+                    format!("{}: {}", path, e)
+                })
+                .collect();
+            Error::response_parse(format!(
+                "Schema validation failed: {}",
+                failures.join("; ")
+            ))
+        })
+    }
+}
+
+impl std::fmt::Debug for JsonSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonSchema").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        id: u64,
+        name: String,
+    }
+
+    fn user_schema() -> JsonSchema {
+        JsonSchema::new(&serde_json::json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": { "type": "integer" },
+                "name": { "type": "string" }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_validated() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(path("/valid"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"id": 1, "name": "ada"})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/missing"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"id": 1})))
+            .mount(&server)
+            .await;
+
+        let client = crate::Client::new();
+        let schema = user_schema();
+        let get = |route: &str| {
+            client.get(format!("{}{}", server.uri(), route).parse::<url::Url>().unwrap()).send()
+        };
+
+        let user: User = get("/valid").await.unwrap().json_validated(&schema).await.unwrap();
+        assert_eq!(user, User { id: 1, name: "ada".to_string() });
+
+        let err = get("/missing")
+            .await
+            .unwrap()
+            .json_validated::<User>(&schema)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ResponseParse(_)));
+        assert!(err.to_string().contains("\"name\" is a required property"));
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        assert!(JsonSchema::new(&serde_json::json!({"type": 12})).is_err());
+    }
+}
@@ -1,11 +1,15 @@
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use futures::{Stream, StreamExt};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncReadExt, AsyncWriteExt};
 use serde_json::Value;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::error::{Error, Result};
+use crate::compression::StreamDecoder;
+use crate::response::{HashAlgo, Hasher};
 
 /// Streaming response handler
 ///
@@ -40,6 +44,26 @@ where
     }
 }
 
+/// Streaming utilities for any stream of results, text, bytes or JSON alike
+impl<T, I> StreamingResponse<T>
+where
+    T: Stream<Item = Result<I>> + Unpin,
+{
+    /// Fold all items into a single value, starting from `init`
+    ///
+    /// Stops at the first error, whether from the stream or from `f`.
+    pub async fn fold<A, F>(mut self, init: A, mut f: F) -> Result<A>
+    where
+        F: FnMut(A, I) -> Result<A>,
+    {
+        let mut acc = init;
+        while let Some(item) = self.stream.next().await {
+            acc = f(acc, item?)?;
+        }
+        Ok(acc)
+    }
+}
+
 // Note: impl Trait in type aliases is unstable, so we'll use generic types instead
 // pub type TextStream = StreamingResponse<impl Stream<Item = Result<String>>>;
 // pub type BytesStream = StreamingResponse<impl Stream<Item = Result<Vec<u8>>>>;
@@ -127,6 +151,31 @@ where
         Ok(result)
     }
 
+    /// Collect all bytes, failing past `max_total` bytes or a stalled chunk
+    ///
+    /// Guards against bodies that are too large or trickle in slowly: each
+    /// chunk must arrive within `per_chunk_timeout`, or collecting fails with
+    /// a timeout error. Exceeding `max_total` fails with a stream error as
+    /// soon as the chunk that crosses it arrives.
+    pub async fn collect_bytes_bounded(self, max_total: usize, per_chunk_timeout: Duration) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut stream = self.stream;
+
+        loop {
+            let chunk = tokio::time::timeout(per_chunk_timeout, stream.next())
+                .await
+                .map_err(|_| Error::timeout(per_chunk_timeout))?;
+            let Some(chunk) = chunk else { break };
+            let chunk = chunk?;
+            if result.len() + chunk.len() > max_total {
+                return Err(Error::stream(format!("Body exceeds {} bytes", max_total)));
+            }
+            result.extend(chunk);
+        }
+
+        Ok(result)
+    }
+
     /// Process bytes chunks with a callback function
     pub async fn for_each_bytes<F>(mut self, mut callback: F) -> Result<()>
     where
@@ -348,6 +397,423 @@ where
     }
 }
 
+/// Stream that fails with `Error::Timeout` when its inner stream stalls
+///
+/// The timer starts when the consumer asks for an item and is reset once
+/// one arrives, so time the consumer spends between polls doesn't count.
+/// After the timeout fires the stream ends.
+pub(crate) struct IdleTimeout<S> {
+    stream: Pin<Box<S>>,
+    timeout: Option<Duration>,
+    timer: Option<Pin<Box<tokio::time::Sleep>>>,
+    waiting: bool,
+    done: bool,
+}
+
+impl<S> IdleTimeout<S> {
+    /// Wrap `stream`; `None` never times out
+    pub(crate) fn new(stream: S, timeout: Option<Duration>) -> Self {
+        Self {
+            stream: Box::pin(stream),
+            timeout,
+            timer: None,
+            waiting: false,
+            done: false,
+        }
+    }
+}
+
+impl<S> Stream for IdleTimeout<S>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if let (Some(timeout), false) = (self.timeout, self.waiting) {
+            let deadline = tokio::time::Instant::now() + timeout;
+            match &mut self.timer {
+                Some(timer) => timer.as_mut().reset(deadline),
+                None => self.timer = Some(Box::pin(tokio::time::sleep_until(deadline))),
+            }
+            self.waiting = true;
+        }
+
+        if let Poll::Ready(item) = self.stream.as_mut().poll_next(cx) {
+            self.waiting = false;
+            self.done = item.is_none();
+            return Poll::Ready(item);
+        }
+        let expired = self.timer.as_mut().is_some_and(|timer| timer.as_mut().poll(cx).is_ready());
+        match self.timeout {
+            Some(timeout) if expired => {
+                self.done = true;
+                Poll::Ready(Some(Err(Error::timeout(timeout))))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// Incremental splitter for a top-level JSON array
+///
+/// Bytes are fed in chunks of any size, and the raw bytes of each element
+/// come out as soon as the element ends, so the array is never buffered
+/// whole. Only the structure is tracked (nesting and strings, including
+/// escaped quotes and brackets inside strings); the elements themselves are
+/// left for a JSON parser.
+#[derive(Debug, Default)]
+pub(crate) struct JsonArraySplitter {
+    state: ArrayState,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    after_comma: bool,
+    element: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum ArrayState {
+    #[default]
+    Start,
+    Elements,
+    Done,
+}
+
+impl JsonArraySplitter {
+    /// Feed the next chunk, pushing every element it completes to `out`
+    pub(crate) fn feed(&mut self, chunk: &[u8], out: &mut Vec<Vec<u8>>) -> Result<()> {
+        for &byte in chunk {
+            match self.state {
+                ArrayState::Start if byte.is_ascii_whitespace() => {}
+                ArrayState::Start if byte == b'[' => self.state = ArrayState::Elements,
+                ArrayState::Start => return Err(Error::response_parse("Expected a JSON array")),
+                ArrayState::Done if byte.is_ascii_whitespace() => {}
+                ArrayState::Done => return Err(Error::response_parse("Trailing data after JSON array")),
+                ArrayState::Elements => self.element_byte(byte, out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the array was closed once the input has ended
+    pub(crate) fn finish(&self) -> Result<()> {
+        match self.state {
+            ArrayState::Done => Ok(()),
+            _ => Err(Error::response_parse("JSON array ended early")),
+        }
+    }
+
+    fn element_byte(&mut self, byte: u8, out: &mut Vec<Vec<u8>>) -> Result<()> {
+        if self.in_string {
+            self.element.push(byte);
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return Ok(());
+        }
+        match byte {
+            b',' | b']' if self.depth == 0 => {
+                if self.element.is_empty() {
+                    // Only `[]` may close without an element
+                    if byte == b',' || self.after_comma {
+                        return Err(Error::response_parse("Missing element in JSON array"));
+                    }
+                } else {
+                    out.push(std::mem::take(&mut self.element));
+                }
+                self.after_comma = byte == b',';
+                if byte == b']' {
+                    self.state = ArrayState::Done;
+                }
+            }
+            b'}' if self.depth == 0 => return Err(Error::response_parse("Unbalanced `}` in JSON array")),
+            _ if byte.is_ascii_whitespace() && self.depth == 0 => {}
+            _ => {
+                match byte {
+                    b'"' => self.in_string = true,
+                    b'{' | b'[' => self.depth += 1,
+                    b'}' | b']' => self.depth -= 1,
+                    _ => {}
+                }
+                self.element.push(byte);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode the elements of a top-level JSON array as its bytes stream in
+pub(crate) fn json_array_elements<S, T>(stream: S) -> impl Stream<Item = Result<T>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+    T: serde::de::DeserializeOwned,
+{
+    let state = (Box::pin(stream), JsonArraySplitter::default(), std::collections::VecDeque::<Vec<u8>>::new(), None, false);
+    futures::stream::unfold(state, |(mut stream, mut splitter, mut ready, mut failed, mut ended)| async move {
+        loop {
+            // Elements completed before an error still come first
+            if let Some(element) = ready.pop_front() {
+                let item = serde_json::from_slice::<T>(&element).map_err(Error::from);
+                return Some((item, (stream, splitter, ready, failed, ended)));
+            }
+            if let Some(e) = failed.take() {
+                return Some((Err(e), (stream, splitter, ready, None, true)));
+            }
+            if ended {
+                return None;
+            }
+            let mut elements = Vec::new();
+            let fed = match stream.next().await {
+                Some(Ok(chunk)) => splitter.feed(&chunk, &mut elements),
+                Some(Err(e)) => Err(e),
+                None => {
+                    ended = true;
+                    splitter.finish()
+                }
+            };
+            ready.extend(elements);
+            if let Err(e) = fed {
+                failed = Some(e);
+                ended = true;
+            }
+        }
+    })
+}
+
+/// Split a byte stream into lines, without their `\n` or `\r\n` endings
+///
+/// A last line without a line ending is yielded when the stream ends.
+pub(crate) fn lines<S>(stream: S) -> impl Stream<Item = Result<Vec<u8>>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    let state = (Box::pin(stream), Vec::new(), std::collections::VecDeque::<Vec<u8>>::new(), false);
+    futures::stream::unfold(state, |(mut stream, mut partial, mut ready, mut ended)| async move {
+        loop {
+            if let Some(line) = ready.pop_front() {
+                return Some((Ok(line), (stream, partial, ready, ended)));
+            }
+            if ended {
+                return None;
+            }
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    let mut rest = &chunk[..];
+                    while let Some(at) = rest.iter().position(|b| *b == b'\n') {
+                        partial.extend_from_slice(&rest[..at]);
+                        if partial.last() == Some(&b'\r') {
+                            partial.pop();
+                        }
+                        ready.push_back(std::mem::take(&mut partial));
+                        rest = &rest[at + 1..];
+                    }
+                    partial.extend_from_slice(rest);
+                }
+                Some(Err(e)) => return Some((Err(e), (stream, partial, ready, true))),
+                None => {
+                    ended = true;
+                    if !partial.is_empty() {
+                        ready.push_back(std::mem::take(&mut partial));
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Split a JSON lines or server-sent events body into its JSON payloads
+///
+/// Plain lines are payloads on their own. SSE `data:` lines are joined
+/// until the blank line ending the event, and other SSE fields (`event:`,
+/// `id:`, `retry:`) and comments are skipped. Blank lines are ignored.
+pub(crate) fn json_events<S>(stream: S) -> impl Stream<Item = Result<Vec<u8>>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    let state = (Box::pin(lines(stream).fuse()), None::<Vec<u8>>);
+    futures::stream::unfold(state, |(mut lines, mut data)| async move {
+        loop {
+            let line = match lines.next().await {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some((Err(e), (lines, data))),
+                None => return data.take().map(|event| (Ok(event), (lines, None))),
+            };
+            if line.iter().all(u8::is_ascii_whitespace) {
+                match data.take() {
+                    Some(event) => return Some((Ok(event), (lines, None))),
+                    None => continue,
+                }
+            }
+            if let Some(payload) = line.strip_prefix(b"data:") {
+                let payload = payload.strip_prefix(b" ").unwrap_or(payload);
+                match &mut data {
+                    Some(event) => {
+                        event.push(b'\n');
+                        event.extend_from_slice(payload);
+                    }
+                    None => data = Some(payload.to_vec()),
+                }
+                continue;
+            }
+            let sse_field = [&b":"[..], b"event:", b"id:", b"retry:"].iter().any(|field| line.starts_with(field));
+            if !sse_field {
+                return Some((Ok(line), (lines, data)));
+            }
+        }
+    })
+}
+
+/// A gRPC-Web message frame
+///
+/// The body of a gRPC-Web response is a sequence of frames: a flags byte,
+/// a 4-byte big-endian length and the payload. Data frames carry protobuf
+/// messages; the final trailer frame, flagged by the high bit, carries the
+/// `grpc-status` and other trailers as HTTP/1 header lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcFrame {
+    flags: u8,
+    payload: Vec<u8>,
+}
+
+impl GrpcFrame {
+    /// Flag bit marking a trailer frame
+    const TRAILER: u8 = 0x80;
+    /// Flag bit marking a compressed payload
+    const COMPRESSED: u8 = 0x01;
+
+    /// Check if this is the trailer frame
+    pub fn is_trailer(&self) -> bool {
+        self.flags & Self::TRAILER != 0
+    }
+
+    /// Check if this is a data frame
+    pub fn is_data(&self) -> bool {
+        !self.is_trailer()
+    }
+
+    /// Check if the payload is compressed with the response's `grpc-encoding`
+    pub fn is_compressed(&self) -> bool {
+        self.flags & Self::COMPRESSED != 0
+    }
+
+    /// Get the flags byte
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Get the payload
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Take the payload
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+
+    /// Parse a trailer frame's payload into headers
+    ///
+    /// Fails with `Error::ResponseParse` for data frames and malformed lines.
+    pub fn trailers(&self) -> Result<http::HeaderMap> {
+        if !self.is_trailer() {
+            return Err(Error::response_parse("Not a gRPC-Web trailer frame"));
+        }
+        let text = std::str::from_utf8(&self.payload)
+            .map_err(|_| Error::response_parse("gRPC-Web trailers aren't UTF-8"))?;
+        let mut trailers = http::HeaderMap::new();
+        for line in text.split("\r\n").filter(|line| !line.is_empty()) {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| Error::response_parse(format!("Invalid gRPC-Web trailer: {}", line)))?;
+            let name = http::HeaderName::from_bytes(name.trim().to_ascii_lowercase().as_bytes())
+                .map_err(|_| Error::response_parse(format!("Invalid gRPC-Web trailer name: {}", name)))?;
+            let value = http::HeaderValue::from_str(value.trim())
+                .map_err(|_| Error::response_parse(format!("Invalid gRPC-Web trailer value: {}", value)))?;
+            trailers.append(name, value);
+        }
+        Ok(trailers)
+    }
+}
+
+/// Split a gRPC-Web body into frames
+///
+/// A body ending partway through a frame yields a final
+/// `Error::ResponseParse`.
+pub(crate) fn grpc_web_frames<S>(stream: S) -> impl Stream<Item = Result<GrpcFrame>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    let state = (Box::pin(stream.fuse()), Vec::new());
+    futures::stream::unfold(state, |(mut stream, mut buf)| async move {
+        loop {
+            if buf.len() >= 5 {
+                let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+                if buf.len() >= 5 + len {
+                    let rest = buf.split_off(5 + len);
+                    let frame = GrpcFrame {
+                        flags: buf[0],
+                        payload: buf[5..].to_vec(),
+                    };
+                    return Some((Ok(frame), (stream, rest)));
+                }
+            }
+            match stream.next().await {
+                Some(Ok(chunk)) => buf.extend(chunk),
+                Some(Err(e)) => return Some((Err(e), (stream, Vec::new()))),
+                None if buf.is_empty() => return None,
+                None => {
+                    let err = Error::response_parse(format!(
+                        "gRPC-Web body ended partway through a frame ({} bytes left over)",
+                        buf.len()
+                    ));
+                    return Some((Err(err), (stream, Vec::new())));
+                }
+            }
+        }
+    })
+}
+
+/// Undo a body's content codings as its chunks arrive
+///
+/// Empty decoded chunks are skipped. The stream ends after the first error.
+pub(crate) fn decoded_chunks<S>(stream: S, decoder: Result<StreamDecoder>) -> impl Stream<Item = Result<Vec<u8>>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    let state = (Box::pin(stream.fuse()), decoder.map(Some));
+    futures::stream::unfold(state, |(mut stream, decoder)| async move {
+        let mut decoder = match decoder {
+            Ok(Some(decoder)) => decoder,
+            Ok(None) => return None,
+            Err(e) => return Some((Err(e), (stream, Ok(None)))),
+        };
+        loop {
+            let decoded = match stream.next().await {
+                Some(Ok(chunk)) => decoder.push(&chunk),
+                Some(Err(e)) => Err(e),
+                None => {
+                    return match decoder.finish() {
+                        Ok(rest) if rest.is_empty() => None,
+                        result => Some((result, (stream, Ok(None)))),
+                    };
+                }
+            };
+            match decoded {
+                Ok(chunk) if chunk.is_empty() => continue,
+                Ok(chunk) => return Some((Ok(chunk), (stream, Ok(Some(decoder))))),
+                Err(e) => return Some((Err(e), (stream, Ok(None)))),
+            }
+        }
+    })
+}
+
 /// Streaming utilities
 pub mod utils {
     use super::*;
@@ -504,6 +970,30 @@ impl DownloadManager {
 
     /// Download a file from a URL
     pub async fn download_file(&self, url: &str, filename: Option<&str>) -> Result<PathBuf> {
+        let (path, _) = self.download(url, filename, None).await?;
+        Ok(path)
+    }
+
+    /// Download a file from a URL and check it against a SHA-256 checksum
+    ///
+    /// `expected_sha256` is hex, in either case. The digest is computed as
+    /// the file is written. On a mismatch the file is deleted and an error
+    /// returned, so a corrupt or tampered download is never left behind.
+    pub async fn download_verified(&self, url: &str, filename: Option<&str>, expected_sha256: &str) -> Result<PathBuf> {
+        let (path, digest) = self.download(url, filename, Some(HashAlgo::Sha256)).await?;
+        let digest = digest.unwrap_or_default();
+        if !digest.eq_ignore_ascii_case(expected_sha256.trim()) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(Error::custom(format!(
+                "Checksum mismatch for {}: expected SHA-256 {}, got {}",
+                url, expected_sha256, digest
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Download a file, hashing it in `algo` along the way
+    async fn download(&self, url: &str, filename: Option<&str>, algo: Option<HashAlgo>) -> Result<(PathBuf, Option<String>)> {
         use tokio::time::timeout;
         
         let client = crate::Client::new();
@@ -521,15 +1011,20 @@ impl DownloadManager {
             let mut file = tokio::fs::File::create(&file_path).await.map_err(|e| Error::Custom(format!("IO error: {}", e)))?;
             
             let _total_bytes = 0u64;
+            let mut hasher = algo.map(Hasher::new);
             tokio::pin!(bytes_stream);
             
             while let Some(chunk) = bytes_stream.next().await {
                 let bytes = chunk?;
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(&bytes);
+                }
                 file.write_all(&bytes).await.map_err(|e| Error::Custom(format!("IO error: {}", e)))?;
                 // total_bytes += bytes.len() as u64; // This line was removed as per the edit hint
             }
+            file.flush().await.map_err(|e| Error::Custom(format!("IO error: {}", e)))?;
             
-            Ok(file_path)
+            Ok((file_path, hasher.map(Hasher::finish)))
         };
         
         timeout(self.timeout, download_future)
@@ -593,6 +1088,111 @@ mod tests {
         assert_eq!(collected, vec![1, 2, 3, 4, 5, 6]);
     }
 
+    #[tokio::test]
+    async fn test_collect_bytes_bounded() {
+        let chunks = || stream::iter(vec![Ok(vec![1, 2, 3]), Ok(vec![4, 5, 6])]);
+        let timeout = Duration::from_secs(1);
+
+        let collected = StreamingResponse::new(chunks()).collect_bytes_bounded(6, timeout).await.unwrap();
+        assert_eq!(collected, vec![1, 2, 3, 4, 5, 6]);
+        let result = StreamingResponse::new(chunks()).collect_bytes_bounded(5, timeout).await;
+        assert!(matches!(result, Err(Error::Stream(_))));
+    }
+
+    #[tokio::test]
+    async fn test_collect_bytes_bounded_stalled() {
+        // One chunk, then nothing ever again
+        let stalled = stream::iter(vec![Ok(vec![1, 2, 3])]).chain(stream::pending());
+        let started = std::time::Instant::now();
+        let result = StreamingResponse::new(stalled)
+            .collect_bytes_bounded(1024, Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_fold_json() {
+        let data = vec![Ok(serde_json::json!(1)), Ok(serde_json::json!(2.5)), Ok(serde_json::json!(3))];
+        let sum = StreamingResponse::new(stream::iter(data))
+            .fold(0.0, |sum, value| Ok(sum + value.as_f64().unwrap_or_default()))
+            .await
+            .unwrap();
+        assert_eq!(sum, 6.5);
+
+        // The first error ends the fold, later items are never seen
+        let data = vec![Ok(serde_json::json!(1)), Err(Error::stream("broken")), Ok(serde_json::json!(2))];
+        let mut seen = 0;
+        let result = StreamingResponse::new(stream::iter(data))
+            .fold(0, |count, _| {
+                seen += 1;
+                Ok(count + 1)
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Stream(_))));
+        assert_eq!(seen, 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_verified() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("hello world"))
+            .mount(&server)
+            .await;
+        let dir = std::env::temp_dir().join(format!("rusttpx-verified-{}", std::process::id()));
+        let manager = DownloadManager::new(dir.to_str().unwrap()).await.unwrap();
+        let url = format!("{}/hello.txt", server.uri());
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        let path = manager.download_verified(&url, None, &sha256.to_uppercase()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+
+        // A mismatch fails and leaves no file behind
+        let wrong = "0".repeat(64);
+        let result = manager.download_verified(&url, Some("bad.txt"), &wrong).await;
+        assert!(matches!(result, Err(Error::Custom(_))));
+        assert!(!dir.join("bad.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_json_events() {
+        let body = "{\"a\":1}\r\n\n: comment\nevent: update\nid: 7\ndata: {\"b\":\ndata: 2}\n\ndata: {\"c\":3}";
+        // Byte by byte, so lines straddle chunks
+        let chunks = body.bytes().map(|b| Ok(vec![b]));
+        let events: Vec<String> = json_events(futures::stream::iter(chunks))
+            .map(|event| String::from_utf8(event.unwrap()).unwrap())
+            .collect()
+            .await;
+        assert_eq!(events, ["{\"a\":1}", "{\"b\":\n2}", "{\"c\":3}"]);
+    }
+
+    #[test]
+    fn test_json_array_splitter() {
+        let input = br#" [ {"a": "]}, [\"x"}, [1, [2]], "s\\", 3 , null] "#;
+        // Byte by byte, so every boundary falls inside a chunk somewhere
+        let mut splitter = JsonArraySplitter::default();
+        let mut out = Vec::new();
+        for byte in input.iter() {
+            splitter.feed(std::slice::from_ref(byte), &mut out).unwrap();
+        }
+        splitter.finish().unwrap();
+        let elements: Vec<_> = out.iter().map(|e| String::from_utf8_lossy(e).into_owned()).collect();
+        assert_eq!(elements, [r#"{"a": "]}, [\"x"}"#, "[1, [2]]", r#""s\\""#, "3", "null"]);
+
+        for bad in [&b"{}"[..], b"[1,,2]", b"[1,]", b"[1] 2"] {
+            let mut splitter = JsonArraySplitter::default();
+            assert!(splitter.feed(bad, &mut Vec::new()).is_err(), "{:?}", bad);
+        }
+        let mut splitter = JsonArraySplitter::default();
+        splitter.feed(b"[1, 2", &mut Vec::new()).unwrap();
+        assert!(splitter.finish().is_err());
+    }
+
     #[test]
     fn test_utils() {
         assert_eq!(utils::format_bytes(1024), "1.0 KB");
@@ -0,0 +1,377 @@
+//! A small local HTTP server for tests
+//!
+//! `TestServer` answers HTTP/1.1 requests from a list of canned routes,
+//! counting hits per path. It needs nothing beyond tokio, so it works as a
+//! fixture for this crate's tests and for users' own.
+//!
+//! ```rust,no_run
+//! use rusttpx::testkit::{Route, TestServer};
+//!
+//! # async fn example() -> rusttpx::Result<()> {
+//! let server = TestServer::start(vec![
+//!     Route::new("/hello").body("hi"),
+//!     Route::redirect("/old", "/hello"),
+//! ]).await;
+//! let client = rusttpx::Client::new();
+//! let text = client.get(server.url_for("/old")).send().await?.text().await?;
+//! assert_eq!(text, "hi");
+//! assert_eq!(server.hit_count("/hello"), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use http::{Method, StatusCode};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// Largest request head the server reads before giving up on a connection
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// A canned response for requests to one path
+#[derive(Debug, Clone)]
+pub struct Route {
+    method: Option<Method>,
+    path: String,
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Option<Duration>,
+    ranges: bool,
+}
+
+impl Route {
+    /// Answer requests to `path` with any method, `200 OK` and an empty body
+    pub fn new(path: &str) -> Self {
+        Self {
+            method: None,
+            path: path.to_string(),
+            status: StatusCode::OK,
+            headers: Vec::new(),
+            body: Vec::new(),
+            delay: None,
+            ranges: false,
+        }
+    }
+
+    /// Redirect requests to `path` to `location` with `302 Found`
+    pub fn redirect(path: &str, location: &str) -> Self {
+        Self::new(path).status(302).header("Location", location)
+    }
+
+    /// Only answer requests with `method`
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Set the response status
+    ///
+    /// # Panics
+    ///
+    /// Panics if `status` isn't a valid status code.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = StatusCode::from_u16(status).expect("invalid status code");
+        self
+    }
+
+    /// Add a response header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the response body
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Wait `delay` before sending the response
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Serve single byte ranges with `206 Partial Content`
+    ///
+    /// Requests without a `Range` header, or asking for several ranges, get
+    /// the whole body. A range past the end gets `416 Range Not Satisfiable`.
+    pub fn ranges(mut self) -> Self {
+        self.ranges = true;
+        self
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.path == path && self.method.as_ref().is_none_or(|m| m == method)
+    }
+}
+
+/// A local HTTP/1.1 server answering from canned routes
+///
+/// Requests that match no route get `404 Not Found`. Request bodies are
+/// read by `Content-Length` and discarded. The server stops when dropped.
+#[derive(Debug)]
+pub struct TestServer {
+    addr: SocketAddr,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Start a server on a free localhost port
+    ///
+    /// # Panics
+    ///
+    /// Panics if no port can be bound.
+    pub async fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind test server");
+        let addr = listener.local_addr().expect("test server has no local address");
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let routes = Arc::new(routes);
+        let task = tokio::spawn({
+            let hits = hits.clone();
+            async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    tokio::spawn(serve(socket, routes.clone(), hits.clone()));
+                }
+            }
+        });
+        Self { addr, hits, task }
+    }
+
+    /// Get the server's base URL, such as `http://127.0.0.1:4321/`
+    pub fn url(&self) -> Url {
+        format!("http://{}/", self.addr).parse().expect("test server URL is valid")
+    }
+
+    /// Get the URL of `path` on the server
+    pub fn url_for(&self, path: &str) -> Url {
+        self.url().join(path).expect("test server path is valid")
+    }
+
+    /// Get the server's address
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Count the requests made to `path`, whether or not a route matched
+    pub fn hit_count(&self, path: &str) -> usize {
+        self.hits.lock().unwrap().get(path).copied().unwrap_or(0)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A parsed request head
+struct RequestHead {
+    method: Method,
+    path: String,
+    headers: HashMap<String, String>,
+}
+
+impl RequestHead {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Answer requests on one connection until the client closes it
+async fn serve(socket: TcpStream, routes: Arc<Vec<Route>>, hits: Arc<Mutex<HashMap<String, usize>>>) {
+    let mut socket = BufReader::new(socket);
+    while let Some(head) = read_head(&mut socket).await {
+        let length = head.header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; length];
+        if socket.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        *hits.lock().unwrap().entry(head.path.clone()).or_insert(0) += 1;
+
+        let route = routes.iter().find(|r| r.matches(&head.method, &head.path));
+        if let Some(delay) = route.and_then(|r| r.delay) {
+            tokio::time::sleep(delay).await;
+        }
+        let response = match route {
+            Some(route) => respond(route, &head),
+            None => encode(StatusCode::NOT_FOUND, &[], b"", head.method == Method::HEAD),
+        };
+        if socket.get_mut().write_all(&response).await.is_err() {
+            return;
+        }
+        if head.header("connection").is_some_and(|v| v.eq_ignore_ascii_case("close")) {
+            return;
+        }
+    }
+}
+
+/// Read a request line and headers, or `None` once the connection is done
+async fn read_head(socket: &mut BufReader<TcpStream>) -> Option<RequestHead> {
+    let mut lines = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let read = socket.read_line(&mut line).await.ok()?;
+        size += read;
+        if read == 0 || size > MAX_HEAD_SIZE {
+            return None;
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines.first()?.split(' ');
+    let method = request_line.next()?.parse().ok()?;
+    let target = request_line.next()?;
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(RequestHead { method, path, headers })
+}
+
+/// Build the response for a matched route
+fn respond(route: &Route, head: &RequestHead) -> Vec<u8> {
+    let head_only = head.method == Method::HEAD;
+    let mut headers = route.headers.clone();
+    if !route.ranges {
+        return encode(route.status, &headers, &route.body, head_only);
+    }
+
+    headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
+    let len = route.body.len();
+    match head.header("range").and_then(|range| parse_range(range, len)) {
+        Some(Some((start, end))) => {
+            headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end, len)));
+            encode(StatusCode::PARTIAL_CONTENT, &headers, &route.body[start..=end], head_only)
+        }
+        Some(None) => {
+            headers.push(("Content-Range".to_string(), format!("bytes */{}", len)));
+            encode(StatusCode::RANGE_NOT_SATISFIABLE, &headers, b"", head_only)
+        }
+        None => encode(route.status, &headers, &route.body, head_only),
+    }
+}
+
+/// Parse a single `bytes=` range against a body of `len` bytes
+///
+/// Returns `None` for headers to ignore, `Some(None)` for a range that
+/// can't be satisfied, and otherwise the inclusive start and end.
+fn parse_range(range: &str, len: usize) -> Option<Option<(usize, usize)>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len.checked_sub(1))
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)),
+        (start, end) => {
+            let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, len.checked_sub(1).map(|last| end.min(last)))
+        }
+    };
+    Some(end.filter(|end| start <= *end).map(|end| (start, end)))
+}
+
+/// Serialize a response with a `Content-Length`
+fn encode(status: StatusCode, headers: &[(String, String)], body: &[u8], head_only: bool) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("")
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    let mut response = response.into_bytes();
+    if !head_only {
+        response.extend_from_slice(body);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-3", 10), Some(Some((0, 3))));
+        assert_eq!(parse_range("bytes=4-", 10), Some(Some((4, 9))));
+        assert_eq!(parse_range("bytes=-3", 10), Some(Some((7, 9))));
+        assert_eq!(parse_range("bytes=5-100", 10), Some(Some((5, 9))));
+        assert_eq!(parse_range("bytes=10-", 10), Some(None));
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+    }
+
+    #[tokio::test]
+    async fn test_server_routes() {
+        let server = TestServer::start(vec![
+            Route::new("/json")
+                .method(Method::GET)
+                .header("Content-Type", "application/json")
+                .body(r#"{"ok":true}"#),
+            Route::new("/created").status(201).body("made"),
+            Route::redirect("/old", "/json"),
+            Route::new("/slow").delay(Duration::from_millis(100)),
+            Route::new("/file").body("0123456789").ranges(),
+        ])
+        .await;
+        let client = crate::Client::new();
+
+        let response = client.get(server.url_for("/old?x=1")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.header("content-type").unwrap(), "application/json");
+        assert_eq!(response.text().await.unwrap(), r#"{"ok":true}"#);
+        assert_eq!(server.hit_count("/old"), 1);
+        assert_eq!(server.hit_count("/json"), 1);
+
+        let response = client.post(server.url_for("/created")).text("payload").unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.text().await.unwrap(), "made");
+
+        // The method filter and unknown paths both fall through to 404
+        let response = client.post(server.url_for("/json")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = client.get(server.url_for("/missing")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(server.hit_count("/missing"), 1);
+
+        let started = Instant::now();
+        client.get(server.url_for("/slow")).send().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let response = client.get(server.url_for("/file")).header("Range", "bytes=2-5").unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.header("content-range").unwrap(), "bytes 2-5/10");
+        assert_eq!(response.text().await.unwrap(), "2345");
+        let response = client.get(server.url_for("/file")).header("Range", "bytes=20-").unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        let response = client.get(server.url_for("/file")).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "0123456789");
+        assert_eq!(server.hit_count("/file"), 3);
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{Stream, StreamExt};
+
+/// Bandwidth limit shared between whoever sets it and a throttled stream
+///
+/// Streams created before the limit is set read it when they are polled,
+/// so the order of builder calls doesn't matter. Zero means unlimited.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BandwidthLimit(Arc<AtomicU64>);
+
+impl BandwidthLimit {
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        let limit = Self::default();
+        limit.set(bytes_per_sec);
+        limit
+    }
+
+    pub(crate) fn set(&self, bytes_per_sec: u64) {
+        self.0.store(bytes_per_sec, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> Option<u64> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+}
+
+/// Token bucket refilled at a fixed rate, starting empty
+///
+/// The bucket holds at most a tenth of a second's worth of tokens, so
+/// bursts after an idle period stay short.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Self {
+            tokens: 0.0,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Wait until `n` bytes may be sent at `rate` bytes per second
+    async fn take(&mut self, n: usize, rate: u64) {
+        let rate = rate as f64;
+        let capacity = (rate / 10.0).max(n as f64);
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * rate).min(capacity);
+        self.refilled = now;
+        if self.tokens < n as f64 {
+            tokio::time::sleep(Duration::from_secs_f64((n as f64 - self.tokens) / rate)).await;
+            self.tokens = n as f64;
+            self.refilled = Instant::now();
+        }
+        self.tokens -= n as f64;
+    }
+}
+
+/// Throttle a body stream to `limit`, splitting chunks so each one
+/// carries at most a tenth of a second's worth of bytes
+pub(crate) fn throttle<S, E>(stream: S, limit: BandwidthLimit) -> impl Stream<Item = Result<Vec<u8>, E>>
+where
+    S: Stream<Item = Result<Vec<u8>, E>>,
+{
+    let state = (Box::pin(stream), Vec::new(), Bucket::new(), limit);
+    futures::stream::unfold(state, |(mut stream, mut pending, mut bucket, limit)| async move {
+        if pending.is_empty() {
+            pending = match stream.next().await? {
+                Ok(chunk) => chunk,
+                Err(e) => return Some((Err(e), (stream, pending, bucket, limit))),
+            };
+        }
+        let chunk = match limit.get() {
+            Some(rate) => {
+                let n = pending.len().min((rate as usize / 10).max(1));
+                bucket.take(n, rate).await;
+                let rest = pending.split_off(n);
+                std::mem::replace(&mut pending, rest)
+            }
+            None => std::mem::take(&mut pending),
+        };
+        Some((Ok(chunk), (stream, pending, bucket, limit)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_throttle_splits_and_paces() {
+        let limit = BandwidthLimit::new(1000);
+        let body = futures::stream::iter(vec![Ok::<_, std::io::Error>(vec![0u8; 250])]);
+        let started = Instant::now();
+        let chunks: Vec<_> = throttle(body, limit).map(|chunk| chunk.unwrap().len()).collect().await;
+
+        // 250 bytes at 1000 bytes per second, in chunks of 100
+        assert_eq!(chunks, vec![100, 100, 50]);
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+}
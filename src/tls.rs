@@ -1,4 +1,7 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::sync::Arc;
 use reqwest::{ClientBuilder as ReqwestBuilder, Certificate, Identity};
 use rustls::{ClientConfig, RootCertStore, Certificate as RustlsCertificate};
 use rustls_native_certs::load_native_certs;
@@ -27,6 +30,31 @@ pub struct TlsConfig {
     pub tls_version: TlsVersion,
     /// Cipher suites
     pub cipher_suites: Vec<String>,
+    /// Whether servers must staple an OCSP response showing their certificate is good
+    pub require_ocsp_stapling: bool,
+}
+
+/// HTTP versions offered through ALPN when rusttpx sets up TLS itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) enum Alpn {
+    /// HTTP/2, falling back to HTTP/1.1, as reqwest offers by default
+    #[default]
+    Http2OrHttp1,
+    /// Only HTTP/1.1, for `ClientBuilder::http1_only`
+    Http1,
+    /// Only HTTP/2, for `ClientBuilder::http2_prior_knowledge`
+    Http2,
+}
+
+impl Alpn {
+    /// The protocol IDs to offer, most preferred first
+    pub(crate) fn protocols(self) -> Vec<Vec<u8>> {
+        match self {
+            Alpn::Http2OrHttp1 => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            Alpn::Http1 => vec![b"http/1.1".to_vec()],
+            Alpn::Http2 => vec![b"h2".to_vec()],
+        }
+    }
 }
 
 /// TLS version configuration
@@ -90,6 +118,7 @@ impl TlsConfig {
             client_key_path: None,
             tls_version: TlsVersion::default(),
             cipher_suites: Vec::new(),
+            require_ocsp_stapling: false,
         }
     }
 
@@ -153,6 +182,27 @@ impl TlsConfig {
         self
     }
 
+    /// Require servers to staple a valid OCSP response to the handshake
+    ///
+    /// The response must be signed by the certificate's issuer or a
+    /// responder it delegated to, be within its validity period, and report
+    /// the certificate as good. Servers that don't staple are rejected.
+    ///
+    /// Stapling adds no round trips, but many servers don't staple, or only
+    /// do once they've cached a response after a restart, so expect
+    /// connection failures with servers outside your control. Connections
+    /// use rustls and offer the same HTTP versions as the client otherwise
+    /// would. Only `ca_cert_path` and the system roots can be trusted, and
+    /// client certificates must come from `client_cert_path`/`client_key_path`:
+    /// with roots from `add_root_cert` or a certificate from `client_cert`
+    /// (including a per-request `client_identity`), every connection fails
+    /// with an error naming the conflict. Has no effect when verification
+    /// is disabled.
+    pub fn require_ocsp_stapling(mut self, required: bool) -> Self {
+        self.require_ocsp_stapling = required;
+        self
+    }
+
     /// Get root certificates
     pub fn get_root_certs(&self) -> &[Certificate] {
         &self.root_certs
@@ -194,7 +244,16 @@ impl TlsConfig {
     }
 
     /// Apply this configuration to a reqwest client builder
-    pub fn apply_to_builder(self, mut builder: ReqwestBuilder) -> ReqwestBuilder {
+    pub fn apply_to_builder(self, builder: ReqwestBuilder) -> ReqwestBuilder {
+        self.apply_with_alpn(builder, Alpn::default())
+    }
+
+    /// Apply this configuration, offering `alpn` if rusttpx sets up TLS itself
+    pub(crate) fn apply_with_alpn(self, mut builder: ReqwestBuilder, alpn: Alpn) -> ReqwestBuilder {
+        if self.require_ocsp_stapling && self.verify {
+            return builder.use_preconfigured_tls(crate::ocsp::client_config(&self, alpn));
+        }
+
         // Load native certificates if no custom ones are provided
         if self.root_certs.is_empty() && self.ca_cert_path.is_none() {
             if let Ok(certs) = load_native_certs() {
@@ -223,16 +282,15 @@ impl TlsConfig {
         }
 
         // Load client certificate if specified
-        if let Some(_client_cert) = self.client_cert {
-            // Note: identity method is not available in this version of reqwest
-            // builder = builder.identity(client_cert);
-        } else if let (Some(cert_path), Some(key_path)) = (self.client_cert_path, self.client_key_path) {
-            if let (Ok(_cert_data), Ok(_key_data)) = (std::fs::read(&cert_path), std::fs::read(&key_path)) {
-                // Note: Identity::from_pkcs8_pem is not available in this version
-                // if let Ok(identity) = Identity::from_pkcs8_pem(&cert_data, &key_data) {
-                //     builder = builder.identity(identity);
-                // }
-            }
+        let identity = match (self.client_cert, self.client_cert_path, self.client_key_path) {
+            (Some(client_cert), _, _) => Some(client_cert),
+            (None, Some(cert_path), Some(key_path)) => utils::load_identity(&cert_path, &key_path).ok(),
+            _ => None,
+        };
+        if let Some(identity) = identity {
+            // Note: PEM identities are rustls identities, which the default
+            // native-tls backend rejects
+            builder = builder.use_rustls_tls().identity(identity);
         }
 
         // Disable certificate verification if requested
@@ -297,6 +355,83 @@ impl Default for TlsConfig {
     }
 }
 
+/// A client certificate that can be chosen per request
+///
+/// Unlike reqwest's `Identity`, this keeps the PEM it was loaded from, so
+/// clients can tell identities apart and reuse a connector for each one.
+#[derive(Clone)]
+pub struct ClientIdentity {
+    pem: Arc<[u8]>,
+    identity: Identity,
+}
+
+impl ClientIdentity {
+    /// Create an identity from a PEM bundle holding a private key and certificate chain
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let identity = Identity::from_pem(pem)
+            .map_err(|e| Error::tls(format!("Invalid client certificate or key: {}", e)))?;
+        Ok(Self {
+            pem: Arc::from(pem),
+            identity,
+        })
+    }
+
+    /// Load an identity from PEM certificate and private key files
+    pub fn from_pem_files(cert_path: &PathBuf, key_path: &PathBuf) -> Result<Self> {
+        Self::from_pem(&utils::read_identity_pem(cert_path, key_path)?)
+    }
+
+    /// Get the underlying reqwest identity
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl PartialEq for ClientIdentity {
+    fn eq(&self, other: &Self) -> bool {
+        self.pem == other.pem
+    }
+}
+
+impl Eq for ClientIdentity {}
+
+impl Hash for ClientIdentity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pem.hash(state);
+    }
+}
+
+// The PEM holds a private key, so it is left out of debug output
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity").finish_non_exhaustive()
+    }
+}
+
+/// Details of the TLS connection a response arrived on
+///
+/// Only recorded when the client was built with `ClientBuilder::tls_info`.
+/// reqwest hands over just the server's leaf certificate; the negotiated
+/// protocol version, cipher suite, ALPN protocol and the rest of the chain
+/// stay inside its connector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    peer_certificate: Option<Vec<u8>>,
+}
+
+impl TlsInfo {
+    pub(crate) fn from_reqwest(info: &reqwest::tls::TlsInfo) -> Self {
+        Self {
+            peer_certificate: info.peer_certificate().map(<[u8]>::to_vec),
+        }
+    }
+
+    /// Get the server's DER-encoded leaf certificate
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificate.as_deref()
+    }
+}
+
 /// TLS configuration builder
 pub struct TlsBuilder {
     config: TlsConfig,
@@ -456,6 +591,23 @@ pub mod utils {
         }
     }
 
+    /// Load a client identity from PEM certificate and private key files
+    pub fn load_identity(cert_path: &PathBuf, key_path: &PathBuf) -> Result<Identity> {
+        ClientIdentity::from_pem_files(cert_path, key_path).map(|identity| identity.identity)
+    }
+
+    /// Read a private key and certificate into a single PEM bundle
+    pub(crate) fn read_identity_pem(cert_path: &PathBuf, key_path: &PathBuf) -> Result<Vec<u8>> {
+        let read = |path: &PathBuf| {
+            std::fs::read(path)
+                .map_err(|e| Error::tls(format!("Failed to read {}: {}", path.display(), e)))
+        };
+        let mut pem = read(key_path)?;
+        pem.push(b'\n');
+        pem.extend(read(cert_path)?);
+        Ok(pem)
+    }
+
     /// Get the default cipher suites
     pub fn default_cipher_suites() -> Vec<String> {
         vec![
@@ -526,4 +678,21 @@ mod tests {
         assert!(!cipher_suites.is_empty());
         assert!(cipher_suites.contains(&"TLS_AES_256_GCM_SHA384".to_string()));
     }
+
+    #[test]
+    fn test_load_identity() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let cert = fixtures.join("server.crt");
+        let key = fixtures.join("server.key");
+
+        let identity = utils::load_identity(&cert, &key).unwrap();
+        // Building a client with the identity must not panic
+        crate::Client::builder()
+            .tls_config(TlsConfig::new().client_cert(identity))
+            .build();
+
+        // A certificate alone has no private key
+        assert!(utils::load_identity(&cert, &cert).is_err());
+        assert!(utils::load_identity(&cert, &fixtures.join("missing.key")).is_err());
+    }
 } 
\ No newline at end of file
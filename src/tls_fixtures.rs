@@ -0,0 +1,114 @@
+//! HTTPS servers for unit tests, using the certificates in `tests/fixtures`
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use rustls::server::AllowAnyAuthenticatedClient;
+use url::Url;
+
+/// Path of a file in `tests/fixtures`
+pub(crate) fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// DER contents of the single PEM block in a fixture
+pub(crate) fn pem_der(name: &str) -> Vec<u8> {
+    use base64::Engine as _;
+
+    let pem = std::fs::read_to_string(fixture(name)).unwrap();
+    let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+    base64::engine::general_purpose::STANDARD.decode(body).unwrap()
+}
+
+/// A rustls server for `localhost`, answering one request per connection
+pub(crate) struct TlsServer {
+    cert: String,
+    key: String,
+    client_ca: Option<String>,
+    ocsp: Option<Vec<u8>>,
+}
+
+impl TlsServer {
+    /// A server presenting the `cert` and `key` fixtures
+    pub(crate) fn new(cert: &str, key: &str) -> Self {
+        Self {
+            cert: cert.to_string(),
+            key: key.to_string(),
+            client_ca: None,
+            ocsp: None,
+        }
+    }
+
+    /// Require a client certificate issued by the `ca` fixture
+    pub(crate) fn client_ca(mut self, ca: &str) -> Self {
+        self.client_ca = Some(ca.to_string());
+        self
+    }
+
+    /// Staple `ocsp` to the handshake, when given
+    pub(crate) fn ocsp(mut self, ocsp: Option<Vec<u8>>) -> Self {
+        self.ocsp = ocsp;
+        self
+    }
+
+    /// Start serving, returning the server's base URL
+    ///
+    /// `respond` gets the connection once the request head is read and
+    /// returns the raw HTTP response, or `None` to close without one.
+    /// Handshakes the server rejects also close without a response.
+    pub(crate) fn serve<F>(self, respond: F) -> Url
+    where
+        F: Fn(&ServerConnection) -> Option<String> + Send + 'static,
+    {
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match self.client_ca {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                roots.add(&Certificate(pem_der(&ca))).unwrap();
+                builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let chain = vec![Certificate(pem_der(&self.cert))];
+        let key = PrivateKey(pem_der(&self.key));
+        let config = match self.ocsp {
+            Some(ocsp) => builder.with_single_cert_with_ocsp_and_sct(chain, key, ocsp, Vec::new()),
+            None => builder.with_single_cert(chain, key),
+        };
+        let config = Arc::new(config.unwrap());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://localhost:{}/", listener.local_addr().unwrap().port());
+        std::thread::spawn(move || {
+            for socket in listener.incoming() {
+                let connection = ServerConnection::new(config.clone()).unwrap();
+                let mut stream = StreamOwned::new(connection, socket.unwrap());
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    if stream.read_exact(&mut byte).is_err() {
+                        break;
+                    }
+                    head.push(byte[0]);
+                }
+                if !head.ends_with(b"\r\n\r\n") {
+                    continue;
+                }
+                if let Some(response) = respond(&stream.conn) {
+                    let _ = stream.write_all(response.as_bytes());
+                    let _ = stream.flush();
+                }
+            }
+        });
+        url.parse().unwrap()
+    }
+}
+
+/// A `200 OK` response with `body` that closes the connection
+pub(crate) fn ok(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,411 @@
+use std::future::Future;
+use std::time::Instant;
+use http::{HeaderValue, Method};
+use reqwest::Request as ReqwestRequest;
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::response::Response;
+
+/// W3C trace context carried between services
+///
+/// Attach one to a request's extensions to continue an existing trace;
+/// with trace propagation enabled the client sends a child of it in the
+/// `traceparent` and `tracestate` headers, or starts a new trace if none
+/// is attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    sampled: bool,
+    tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Start a new sampled trace with random ids
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_nonzero(rand::random::<u128>),
+            span_id: random_nonzero(rand::random::<u64>),
+            sampled: true,
+            tracestate: None,
+        }
+    }
+
+    /// Parse `traceparent` and optional `tracestate` header values
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Result<Self> {
+        let invalid = || Error::invalid_request(format!("Invalid traceparent: {}", traceparent));
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+        let lengths_ok = parts.len() == 4
+            && parts[0].len() == 2
+            && parts[1].len() == 32
+            && parts[2].len() == 16
+            && parts[3].len() == 2;
+        if !lengths_ok || parts[0] == "ff" {
+            return Err(invalid());
+        }
+
+        let trace_id = u128::from_str_radix(parts[1], 16).map_err(|_| invalid())?;
+        let span_id = u64::from_str_radix(parts[2], 16).map_err(|_| invalid())?;
+        let flags = u8::from_str_radix(parts[3], 16).map_err(|_| invalid())?;
+        if trace_id == 0 || span_id == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 0x01 != 0,
+            tracestate: tracestate.map(|s| s.to_string()),
+        })
+    }
+
+    /// Create a child context in the same trace with a new span id
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: random_nonzero(rand::random::<u64>),
+            ..self.clone()
+        }
+    }
+
+    /// Set the vendor-specific `tracestate` value
+    pub fn with_tracestate(mut self, tracestate: &str) -> Self {
+        self.tracestate = Some(tracestate.to_string());
+        self
+    }
+
+    /// Get the trace id as 32 lowercase hex digits
+    pub fn trace_id(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// Get the span id as 16 lowercase hex digits
+    pub fn span_id(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    /// Check whether the trace is sampled
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Get the `tracestate` value, if any
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// Format the `traceparent` header value
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id(), self.span_id(), self.sampled as u8)
+    }
+}
+
+fn random_nonzero<T: PartialEq + Default>(generate: impl Fn() -> T) -> T {
+    loop {
+        let value = generate();
+        if value != T::default() {
+            return value;
+        }
+    }
+}
+
+/// Add `traceparent`/`tracestate` headers for a child of `parent`, or a new trace
+pub(crate) fn inject(request: &mut ReqwestRequest, parent: Option<&TraceContext>) {
+    let context = parent.map(TraceContext::child).unwrap_or_else(TraceContext::new_root);
+    let headers = request.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&context.traceparent()) {
+        headers.insert("traceparent", value);
+    }
+    if let Some(value) = context.tracestate().and_then(|s| HeaderValue::from_str(s).ok()) {
+        headers.insert("tracestate", value);
+    }
+}
+
+/// Run a send inside an `http.request` span recording its status or error
+pub(crate) async fn instrument<F>(method: &Method, url: &Url, send: F) -> Result<Response>
+where
+    F: Future<Output = Result<Response>>,
+{
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+
+        let span = tracing::info_span!(
+            "http.request",
+            http.method = %method,
+            http.url = %url,
+            http.status_code = tracing::field::Empty,
+            error = tracing::field::Empty,
+        );
+        let result = send.instrument(span.clone()).await;
+        match &result {
+            Ok(response) => {
+                span.record("http.status_code", response.status().as_u16());
+            }
+            Err(e) => {
+                span.record("error", tracing::field::display(e));
+            }
+        }
+        result
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (method, url);
+        send.await
+    }
+}
+
+/// Log a send's start, then its completion or failure, as key-value records
+///
+/// Records carry an `event` of `request.start`, `request.complete` or
+/// `request.error`, plus the method, URL, and the status or error kind
+/// with the elapsed milliseconds.
+pub(crate) async fn log_lifecycle<F>(
+    level: Option<log::Level>,
+    method: &Method,
+    url: &Url,
+    send: F,
+) -> Result<Response>
+where
+    F: Future<Output = Result<Response>>,
+{
+    let Some(level) = level else {
+        return send.await;
+    };
+
+    let (method, url) = (method.as_str(), url.as_str());
+    log::log!(level, event = "request.start", method = method, url = url; "{} {} started", method, url);
+    let started = Instant::now();
+    let result = send.await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            log::log!(
+                level,
+                event = "request.complete", method = method, url = url, status = status, elapsed_ms = elapsed_ms;
+                "{} {} completed with {} in {}ms", method, url, status, elapsed_ms
+            );
+        }
+        Err(e) => {
+            let kind = error_kind(e);
+            log::log!(
+                level,
+                event = "request.error", method = method, url = url, error_kind = kind, elapsed_ms = elapsed_ms;
+                "{} {} failed ({}) after {}ms: {}", method, url, kind, elapsed_ms, e
+            );
+        }
+    }
+    result
+}
+
+/// Classify an error for lifecycle logs
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::Timeout { .. } => "timeout",
+        Error::Network(e) if e.is_timeout() => "timeout",
+        Error::Network(e) if e.is_connect() => "connect",
+        Error::Network(_) => "network",
+        Error::Tls(_) => "tls",
+        Error::Auth(_) => "auth",
+        Error::Proxy(_) => "proxy",
+        Error::Url(_) | Error::Http(_) | Error::InvalidRequest(_) => "invalid_request",
+        _ => "other",
+    }
+}
+
+/// A logger that captures records for tests, installed once per test binary
+#[cfg(test)]
+pub(crate) mod capture_log {
+    use std::sync::{Mutex, Once};
+
+    /// A captured record's target, message and `key=value` pairs
+    #[derive(Debug, Clone)]
+    pub(crate) struct Record {
+        pub(crate) target: String,
+        pub(crate) message: String,
+        pub(crate) pairs: Vec<String>,
+    }
+
+    static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    struct Pairs(Vec<String>);
+
+    impl<'kvs> log::kv::VisitSource<'kvs> for Pairs {
+        fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+            self.0.push(format!("{}={}", key, value));
+            Ok(())
+        }
+    }
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let mut pairs = Pairs(Vec::new());
+            let _ = record.key_values().visit(&mut pairs);
+            RECORDS.lock().unwrap().push(Record {
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+                pairs: pairs.0,
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Start capturing log records
+    pub(crate) fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&Capture).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    /// Get every record captured so far, from all tests
+    pub(crate) fn records() -> Vec<Record> {
+        RECORDS.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_context_roundtrip() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(header, Some("vendor=abc")).unwrap();
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id(), "00f067aa0ba902b7");
+        assert!(context.is_sampled());
+        assert_eq!(context.traceparent(), header);
+
+        let child = context.child();
+        assert_eq!(child.trace_id(), context.trace_id());
+        assert_ne!(child.span_id(), context.span_id());
+        assert_eq!(child.tracestate(), Some("vendor=abc"));
+
+        assert!(TraceContext::parse("00-abc-def-01", None).is_err());
+        assert!(TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trace_propagation_headers() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url: Url = server.uri().parse().unwrap();
+
+        let parent = TraceContext::new_root().with_tracestate("vendor=abc");
+        let client = crate::Client::builder().trace_propagation(true).build();
+        let mut builder = client.get(url.clone());
+        builder.extensions_mut().insert(parent.clone());
+        builder.send().await.unwrap();
+
+        // Without propagation no headers are added
+        crate::Client::new().get(url).send().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let traceparent = requests[0].headers.get(&"traceparent".into()).unwrap().as_str();
+        let sent = TraceContext::parse(traceparent, None).unwrap();
+        assert_eq!(sent.trace_id(), parent.trace_id());
+        assert_ne!(sent.span_id(), parent.span_id());
+        assert_eq!(requests[0].headers.get(&"tracestate".into()).unwrap().as_str(), "vendor=abc");
+        assert!(!requests[1].headers.contains_key(&"traceparent".into()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_request_span_fields() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        type Fields = Arc<Mutex<Vec<(String, String)>>>;
+
+        struct Capture(Fields);
+
+        impl Visit for Capture {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.lock().unwrap().push((field.name().to_string(), format!("{:?}", value)));
+            }
+        }
+
+        struct CaptureLayer(Fields);
+
+        impl<S: tracing::Subscriber> Layer<S> for CaptureLayer {
+            fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+                if attrs.metadata().name() == "http.request" {
+                    attrs.record(&mut Capture(self.0.clone()));
+                }
+            }
+
+            fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+                values.record(&mut Capture(self.0.clone()));
+            }
+        }
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let url: Url = format!("{}/missing", server.uri()).parse().unwrap();
+
+        let fields: Fields = Arc::default();
+        let subscriber = tracing_subscriber::registry().with(CaptureLayer(fields.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        crate::Client::new().get(url.clone()).send().await.unwrap();
+
+        let fields = fields.lock().unwrap().clone();
+        let get = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+        assert_eq!(get("http.method").as_deref(), Some("GET"));
+        assert_eq!(get("http.url"), Some(url.to_string()));
+        assert_eq!(get("http.status_code").as_deref(), Some("404"));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_logging() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        capture_log::install();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&server)
+            .await;
+        let url: Url = format!("{}/lifecycle", server.uri()).parse().unwrap();
+
+        let client = crate::Client::builder().lifecycle_logging(log::Level::Info).build();
+        client.get(url.clone()).send().await.unwrap();
+
+        let url_pair = format!("url={}", url);
+        let records: Vec<_> = capture_log::records()
+            .into_iter()
+            .filter(|r| r.pairs.contains(&url_pair))
+            .collect();
+        assert_eq!(records.len(), 2, "{:?}", records);
+        assert!(records[0].pairs.contains(&"event=request.start".to_string()));
+        assert!(records[0].pairs.contains(&"method=GET".to_string()));
+        assert!(records[1].pairs.contains(&"event=request.complete".to_string()));
+        assert!(records[1].pairs.contains(&"status=201".to_string()));
+        assert!(records[1].pairs.iter().any(|p| p.starts_with("elapsed_ms=")));
+    }
+}
@@ -0,0 +1,63 @@
+//! Helpers for resolving and decoding URLs
+
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Resolve `rel` against `base` the way a browser resolves a link
+///
+/// The base's query and fragment are dropped unless `rel` is empty or
+/// starts with `#`. Note that a base path without a trailing slash loses
+/// its last segment: `https://api.example.com/v1` joined with `users` is
+/// `https://api.example.com/users`.
+pub fn join(base: &Url, rel: &str) -> Result<Url> {
+    if base.cannot_be_a_base() {
+        return Err(Error::invalid_request(format!(
+            "Can't resolve '{}' against {}: URL can't be used as a base",
+            rel, base
+        )));
+    }
+    base.join(rel.trim()).map_err(|e| {
+        Error::invalid_request(format!("Can't resolve '{}' against {}: {}", rel, base, e))
+    })
+}
+
+/// Decode a URL's query string into name/value pairs, in order
+///
+/// Percent escapes and `+` are decoded, so `a=1%262` yields `("a", "1&2")`.
+pub fn decode_query(url: &Url) -> Vec<(String, String)> {
+    url.query_pairs().into_owned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_and_decode_query() {
+        let base: Url = "https://example.com/a/b?x=1#top".parse().unwrap();
+
+        assert_eq!(join(&base, "c").unwrap().as_str(), "https://example.com/a/c");
+        assert_eq!(join(&base, "../c?y=2").unwrap().as_str(), "https://example.com/c?y=2");
+        assert_eq!(join(&base, "?y=2").unwrap().as_str(), "https://example.com/a/b?y=2");
+        assert_eq!(join(&base, "#end").unwrap().as_str(), "https://example.com/a/b?x=1#end");
+        assert_eq!(join(&base, "//other.org/p").unwrap().as_str(), "https://other.org/p");
+
+        let mailto: Url = "mailto:someone@example.com".parse().unwrap();
+        let err = join(&mailto, "c").unwrap_err();
+        assert!(err.to_string().contains("mailto:someone@example.com"), "{}", err);
+        assert!(join(&base, "http://[::1").is_err());
+
+        let url: Url = "https://example.com/?a=1%262&b=c%3Dd&q=two+words&e=".parse().unwrap();
+        assert_eq!(
+            decode_query(&url),
+            vec![
+                ("a".to_string(), "1&2".to_string()),
+                ("b".to_string(), "c=d".to_string()),
+                ("q".to_string(), "two words".to_string()),
+                ("e".to_string(), String::new()),
+            ]
+        );
+        assert!(decode_query(&"https://example.com/".parse().unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+#![cfg(feature = "cli")]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread::{self, JoinHandle};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::method;
+
+async fn json_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"name": "ada", "age": 36})))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// The binary, isolated from color and proxy settings in the environment
+fn command() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rusttpx"));
+    for var in ["NO_COLOR", "HTTP_PROXY", "HTTPS_PROXY", "NO_PROXY", "http_proxy", "https_proxy", "no_proxy"] {
+        command.env_remove(var);
+    }
+    command
+}
+
+fn run(args: &[&str]) -> String {
+    let output = command().args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn test_redirected_output_has_no_color() {
+    let server = json_server().await;
+
+    let stdout = run(&[&server.uri(), "--show-headers"]);
+    assert!(stdout.contains("200 OK"));
+    assert!(stdout.contains("\"name\": \"ada\""));
+    assert!(!stdout.contains('\x1b'));
+}
+
+#[tokio::test]
+async fn test_color_always() {
+    let server = json_server().await;
+
+    let stdout = run(&[&server.uri(), "--show-headers", "--color", "always"]);
+    assert!(stdout.contains("\x1b["));
+    assert!(stdout.contains("\x1b[33m\"name\"\x1b[0m"));
+}
+
+#[tokio::test]
+async fn test_verbose_output() {
+    let server = json_server().await;
+
+    let output = command().args([&server.uri(), "-v"]).output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("> GET /"));
+    assert!(stderr.contains("< HTTP/1.1 200 OK"));
+    assert!(stderr.contains("* Timing: dns="));
+    assert!(stderr.contains("total="));
+    // Verbose output doesn't mix into the body on stdout
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("> GET"));
+}
+
+#[tokio::test]
+async fn test_form_fields() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    run(&[&server.uri(), "-m", "post", "--form", "name=ada", "-F", "lang=rust & c"]);
+
+    let requests = server.received_requests().await.unwrap();
+    let content_type = requests[0].headers.get(&"content-type".into()).unwrap().as_str();
+    assert_eq!(content_type, "application/x-www-form-urlencoded");
+    assert_eq!(String::from_utf8_lossy(&requests[0].body), "name=ada&lang=rust+%26+c");
+}
+
+#[tokio::test]
+async fn test_multipart_file_upload() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    let path = std::env::temp_dir().join(format!("rusttpx-upload-{}.txt", std::process::id()));
+    std::fs::write(&path, "file contents").unwrap();
+    let file_arg = format!("upload=@{}", path.display());
+    run(&[&server.uri(), "-m", "post", "--form", "name=ada", "--file", &file_arg]);
+    std::fs::remove_file(&path).unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let content_type = requests[0].headers.get(&"content-type".into()).unwrap().as_str();
+    assert!(content_type.starts_with("multipart/form-data; boundary="));
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains("name=\"name\"\r\n\r\nada"));
+    assert!(body.contains("name=\"upload\"; filename=\"rusttpx-upload-"));
+    assert!(body.contains("file contents"));
+}
+
+#[test]
+fn test_body_conflicts_with_form() {
+    let output = command()
+        .args(["http://localhost/", "--body", "{}", "--form", "a=b"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[tokio::test]
+async fn test_retry_flag() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+        .mount(&server)
+        .await;
+
+    let stdout = run(&[&server.uri(), "--retry", "3", "--retry-delay", "0", "--show-headers"]);
+    assert!(stdout.contains("200 OK"));
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
+
+/// A forward proxy that answers one request itself and returns its head
+fn forward_proxy() -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            socket.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\nConnection: close\r\n\r\nvia proxy")
+            .unwrap();
+        String::from_utf8(head).unwrap()
+    });
+    (url, handle)
+}
+
+#[test]
+fn test_proxy_flag() {
+    let (proxy, handle) = forward_proxy();
+
+    let stdout = run(&["http://upstream.test/path", "--proxy", &proxy, "--proxy-user", "ada:secret"]);
+    assert!(stdout.contains("via proxy"));
+
+    let head = handle.join().unwrap().to_ascii_lowercase();
+    assert!(head.starts_with("get http://upstream.test/path http/1.1\r\n"));
+    // base64 of "ada:secret"
+    assert!(head.contains("proxy-authorization: basic ywrhonnly3jlda==\r\n"));
+}
+
+#[tokio::test]
+async fn test_proxy_environment() {
+    let (proxy, handle) = forward_proxy();
+
+    let output = command()
+        .arg("http://upstream.test/")
+        .env("HTTP_PROXY", &proxy)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("via proxy"));
+    assert!(handle.join().unwrap().starts_with("GET http://upstream.test/ HTTP/1.1"));
+
+    // --no-proxy ignores the environment and connects directly
+    let server = json_server().await;
+    let output = command()
+        .args([&server.uri(), "--no-proxy"])
+        .env("HTTP_PROXY", "http://127.0.0.1:9")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// DER contents of the single PEM block in a fixture
+fn pem_der(name: &str) -> Vec<u8> {
+    use base64::Engine as _;
+
+    let pem = std::fs::read_to_string(fixture(name)).unwrap();
+    let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+    base64::engine::general_purpose::STANDARD.decode(body).unwrap()
+}
+
+/// An HTTPS server for `localhost` using the self-signed fixture certificate
+fn https_server() -> String {
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(pem_der("server.crt"))],
+            rustls::PrivateKey(pem_der("server.key")),
+        )
+        .unwrap();
+    let config = std::sync::Arc::new(config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://localhost:{}/", listener.local_addr().unwrap().port());
+    thread::spawn(move || {
+        for socket in listener.incoming() {
+            let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+            let mut stream = rustls::StreamOwned::new(connection, socket.unwrap());
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            // Clients that reject the certificate end the handshake here
+            while !head.ends_with(b"\r\n\r\n") {
+                if stream.read_exact(&mut byte).is_err() {
+                    break;
+                }
+                head.push(byte[0]);
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecure");
+            let _ = stream.flush();
+        }
+    });
+    url
+}
+
+#[test]
+fn test_tls_flags() {
+    let url = https_server();
+
+    // The self-signed certificate isn't trusted by default
+    let output = command().arg(&url).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+    assert!(stderr.contains("certificate"), "{}", stderr);
+
+    assert!(run(&[&url, "--insecure"]).contains("secure"));
+    assert!(run(&[&url, "--cacert", &fixture("server.crt")]).contains("secure"));
+}